@@ -0,0 +1,29 @@
+use std::io::Write;
+
+use ollama_rest::{models::create::CreationProgress, Ollama};
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    let (model_name, path) = match (args.get(1), args.get(2)) {
+        (Some(model_name), Some(path)) => (model_name, path),
+        _ => {
+            println!("Usage: create-model-from-gguf <model name> <path to GGUF file>");
+            return;
+        }
+    };
+
+    // Make sure Ollama serves at 127.0.0.1:11434
+    let ollama = Ollama::default();
+
+    ollama.create_model_from_gguf(model_name, path, Some(|progress: &CreationProgress| {
+        match progress {
+            CreationProgress::Upload(upload) => {
+                print!("\ruploading {} / {}", upload.completed, upload.total.unwrap_or(0));
+                std::io::stdout().flush().unwrap();
+            }
+            CreationProgress::Status(status) => println!("\n{}", status.status),
+        }
+    })).await.unwrap();
+}
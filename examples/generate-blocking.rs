@@ -0,0 +1,28 @@
+//! Completion streaming with the blocking client, no async runtime needed
+
+use std::io::Write;
+
+use ollama_rest::{blocking::Ollama, models::generate::GenerationRequest};
+use serde_json::json;
+
+fn main() {
+    // Make sure Ollama serves at 127.0.0.1:11434
+    let ollama = Ollama::default();
+
+    let request = serde_json::from_value::<GenerationRequest>(json!({
+        "model": "llama3.2:1b",
+        "prompt": "Why is the sky blue?",
+    })).unwrap();
+
+    for res in ollama.generate_streamed(&request).unwrap() {
+        let res = res.unwrap();
+
+        if !res.done {
+            print!("{}", res.response);
+            // Flush stdout for each word to allow realtime output
+            std::io::stdout().flush().unwrap();
+        }
+    }
+
+    println!();
+}
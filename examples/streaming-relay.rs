@@ -12,7 +12,7 @@ use tokio::net::TcpListener;
 
 const HOST_ADDR: &str = "127.0.0.1:9890";
 
-static API: Lazy<Ollama> = Lazy::new(|| Ollama::default());
+static API: Lazy<Ollama> = Lazy::new(Ollama::default);
 
 #[tokio::main]
 async fn main() {
@@ -0,0 +1,25 @@
+//! Show a model, tweak its Modelfile and create a new model from it
+
+use ollama_rest::{models::{create::CreationRequest, model::ModelShowRequest, Status}, modelfile::Modelfile, Ollama};
+
+#[tokio::main]
+async fn main() {
+    // Make sure Ollama serves at 127.0.0.1:11434
+    let ollama = Ollama::default();
+
+    let shown = ollama.model(&ModelShowRequest {
+        name: "llama3.2:1b".to_string(),
+        verbose: None,
+    }).await.unwrap();
+
+    let mut modelfile: Modelfile = shown.modelfile.unwrap().parse().unwrap();
+
+    modelfile.set_parameter("temperature", "0.2");
+    modelfile.set_system("You are a terse assistant. Answer in one sentence.");
+
+    ollama.create(&CreationRequest {
+        name: Some("llama3.2-terse".to_string()),
+        modelfile: Some(modelfile.to_string()),
+        ..Default::default()
+    }, Some(|res: &Status| println!("{}", res.status))).await.unwrap();
+}
@@ -0,0 +1,25 @@
+use std::io::Write;
+
+use ollama_rest::{models::blob::BlobUploadProgress, Ollama};
+
+#[tokio::main]
+async fn main() {
+    let path = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            println!("Error: No file path provided");
+            return;
+        }
+    };
+
+    // Make sure Ollama serves at 127.0.0.1:11434
+    let ollama = Ollama::default();
+
+    // The file is hashed first, and only uploaded if the server doesn't have it yet
+    let digest = ollama.upload_blob_from_path(&path, Some(|progress: &BlobUploadProgress| {
+        print!("\r{} / {}", progress.completed, progress.total.unwrap_or(0));
+        std::io::stdout().flush().unwrap();
+    })).await.unwrap();
+
+    println!("\n{digest}");
+}
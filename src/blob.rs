@@ -0,0 +1,26 @@
+//! Blob helpers
+
+use bytes::Bytes;
+use futures::Stream;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Size of chunks read from files and readers
+pub(crate) const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Turn an [`AsyncRead`] into a stream of byte chunks
+pub(crate) fn reader_stream<R>(reader: R) -> impl Stream<Item = std::io::Result<Bytes>>
+where
+    R: AsyncRead + Unpin,
+{
+    futures::stream::try_unfold(reader, |mut reader| async move {
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        let n = reader.read(&mut buf).await?;
+
+        if n == 0 {
+            Ok(None)
+        } else {
+            buf.truncate(n);
+            Ok(Some((Bytes::from(buf), reader)))
+        }
+    })
+}
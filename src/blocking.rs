@@ -0,0 +1,543 @@
+//! Blocking (synchronous) client
+//!
+//! Mirrors [`crate::Ollama`] with the same models and errors, for code that
+//! doesn't want to run an async runtime. Streamed responses are exposed as
+//! iterators.
+//!
+//! Requires the `blocking` feature.
+//!
+//! ## Examples
+//!
+//! ```rust,no_run
+//! use ollama_rest::{blocking::Ollama, models::generate::GenerationRequest};
+//! use serde_json::json;
+//!
+//! let ollama = Ollama::default();
+//!
+//! let request = serde_json::from_value::<GenerationRequest>(json!({
+//!     "model": "llama3.2:1b",
+//!     "prompt": "Why is the sky blue?",
+//! })).unwrap();
+//!
+//! for res in ollama.generate_streamed(&request).unwrap() {
+//!     print!("{}", res.unwrap().response);
+//! }
+//! ```
+
+use std::{collections::BTreeMap, fs::File, io::{BufRead, BufReader, Lines, Read}, marker::PhantomData, path::Path, str::FromStr, sync::mpsc, thread};
+
+use reqwest::{blocking::{Body, Client, ClientBuilder, Response}, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use sha2::{Digest as _, Sha256};
+
+use crate::{
+    errors::Error,
+    host::Host,
+    models::{
+        blob::BlobUploadProgress,
+        chat::{ChatRequest, ChatResponse},
+        create::{CreationProgress, CreationRequest},
+        digest::Digest,
+        embeddings::{EmbedRequest, EmbedResponse, EmbeddingGenerationRequest, EmbeddingGenerationResponse},
+        generate::{GenerationRequest, GenerationResponse},
+        model::*,
+        version::{ServerFeatures, VersionResponse},
+        Status,
+    },
+};
+
+/// Iterator over a streamed (newline-delimited JSON) response
+pub struct JsonStream<T> {
+    lines: Lines<BufReader<Response>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T> JsonStream<T> {
+    fn new(res: Response) -> Self {
+        Self {
+            lines: BufReader::new(res).lines(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for JsonStream<T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            return match self.lines.next()? {
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => Some(serde_json::from_str::<T>(&line).map_err(Error::from)),
+                Err(err) => Some(Err(err.into())),
+            };
+        }
+    }
+}
+
+macro_rules! blocking_streamed_request_wrapper {
+    {
+        $(
+            $(#[$attr:meta])*
+            $($kw:ident)? fn $func_name:ident($pathname:literal, $req_ty:ty) -> $res_ty:ty
+            => streamed as [
+                $(#[$attr2:meta])*
+                $($kw2:ident)? fn $streamed_func_name:ident
+            ]
+        );*
+        $(;)?
+    } => {
+        $(
+            $(#[$attr])*
+            $($kw)? fn $func_name<T>(&self, request: &$req_ty, mut on_stream: Option<T>) -> Result<$res_ty, Error>
+            where
+                T: FnMut(&$res_ty)
+            {
+                let res = self.client.post(self.host.join($pathname)?)
+                    .json(request)
+                    .send()?;
+
+                if request.stream.unwrap_or(true) {
+                    // Handle streamed response
+                    let mut final_res: Option<$res_ty> = None;
+
+                    if let Some(ref mut f) = on_stream {
+                        for cur_res in JsonStream::<$res_ty>::new(res) {
+                            let cur_res = cur_res?;
+                            f(&cur_res);
+                            final_res = Some(cur_res);
+                        }
+                    }
+
+                    final_res.ok_or(if on_stream.is_some() { Error::EmptyResponse } else { Error::NoCallback })
+                } else {
+                    // Handle normal response
+                    Ok(res.json::<$res_ty>()?)
+                }
+            }
+
+            $(#[$attr2])*
+            $($kw2)? fn $streamed_func_name(&self, request: &$req_ty) -> Result<impl Iterator<Item = Result<$res_ty, Error>>, Error> {
+                if !request.stream.unwrap_or(true) {
+                    return Err(Error::StreamingOff);
+                }
+
+                let res = self.client.post(self.host.join($pathname)?)
+                    .json(request)
+                    .send()?;
+
+                Ok(JsonStream::<$res_ty>::new(res))
+            }
+        )*
+    };
+}
+
+/// The blocking Ollama instance
+///
+/// See [`crate::Ollama`] for the async counterpart.
+#[derive(Clone)]
+pub struct Ollama {
+    host: Host,
+    client: Client,
+}
+
+impl Ollama {
+    pub fn new(host: Url) -> Result<Self, Error> {
+        let host = Host::from(host);
+
+        let builder = ClientBuilder::new()
+            .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+            // Generation may take much longer than reqwest's default timeout
+            .timeout(None);
+
+        let builder = match host.socket()? {
+            #[cfg(unix)]
+            Some(socket) => builder.unix_socket(socket),
+            _ => builder,
+        };
+
+        Ok(Self {
+            host,
+            client: builder.build()?,
+        })
+    }
+
+    /// Get host info as a str reference
+    pub fn host(&self) -> &str {
+        self.host.as_str()
+    }
+
+    blocking_streamed_request_wrapper! {
+        #[doc = "Generate completion response for one single prompt (Callback API)"]
+        pub fn generate("/api/generate", GenerationRequest) -> GenerationResponse
+            => streamed as [
+                #[doc = "Generate completion response for one single prompt (Iterator API)"]
+                pub fn generate_streamed
+            ];
+
+        #[doc = "Generate completion response from chat history (Callback API)"]
+        pub fn chat("/api/chat", ChatRequest) -> ChatResponse
+            => streamed as [
+                #[doc = "Generate completion response from chat history (Iterator API)"]
+                pub fn chat_streamed
+            ];
+
+        #[doc = "create a model (Callback API)"]
+        pub fn create("/api/create", CreationRequest) -> Status
+            => streamed as [
+                #[doc = "create a model (Iterator API)"]
+                pub fn create_streamed
+            ];
+    }
+
+    /// Load a model
+    ///
+    /// It calls `/api/generate` with no prompt, which makes Ollama to load
+    /// the model.
+    pub fn load_model(&self, model: &str) -> Result<GenerationResponse, Error> {
+        Ok(self.client.post(self.host.join("/api/generate")?)
+            .json(&serde_json::json!({ "model": model }))
+            .send()?
+            .json::<GenerationResponse>()?)
+    }
+
+    /// Check if blob exists on the server side (not ollama.com)
+    ///
+    /// See [`crate::Ollama::blob_exists()`].
+    pub fn blob_exists(&self, digest: &Digest) -> Result<(), Error> {
+        let status = self.client.head(self.host.join(format!("/api/blobs/{}", digest.with_prefix(true)).as_str())?)
+            .send()?
+            .status();
+
+        match status {
+            StatusCode::OK => Ok(()),
+            StatusCode::NOT_FOUND => Err(Error::NotExists),
+            status => Err(Error::ErrorStatus(status)),
+        }
+    }
+
+    /// Create a blob
+    ///
+    /// See [`crate::Ollama::create_blob()`].
+    pub fn create_blob(&self, digest: &Digest, file: File) -> Result<(), Error> {
+        let status = self.client.post(self.host.join(format!("/api/blobs/{}", digest.with_prefix(true)).as_str())?)
+            .body(file)
+            .send()?
+            .status();
+
+        if let StatusCode::CREATED = status {
+            Ok(())
+        } else {
+            Err(Error::ErrorStatus(status))
+        }
+    }
+
+    /// Upload a blob from a reader
+    ///
+    /// See [`crate::Ollama::upload_blob()`].
+    pub fn upload_blob_from_reader<R, F>(&self, digest: &Digest, reader: R, total: Option<u64>, mut on_progress: Option<F>) -> Result<(), Error>
+    where
+        R: Read + Send + 'static,
+        F: FnMut(&BlobUploadProgress),
+    {
+        let (progress_tx, progress_rx) = mpsc::channel::<u64>();
+        let (done_tx, done_rx) = mpsc::channel::<Sha256>();
+
+        let reader = HashingReader {
+            inner: reader,
+            hasher: Sha256::new(),
+            progress_tx,
+            done_tx,
+        };
+
+        let url = self.host.join(format!("/api/blobs/{}", digest.with_prefix(true)).as_str())?;
+        let body = match total {
+            Some(total) => Body::sized(reader, total),
+            None => Body::new(reader),
+        };
+
+        let mut progress = BlobUploadProgress {
+            digest: *digest,
+            completed: 0,
+            total,
+        };
+
+        // The request runs on another thread, so that progress can be reported
+        // here without requiring the callback to be `Send`.
+        let res = thread::scope(|scope| {
+            let request = scope.spawn(|| self.client.post(url).body(body).send());
+
+            for sent in progress_rx.iter() {
+                progress.completed += sent;
+                if let Some(ref mut f) = on_progress {
+                    f(&progress);
+                }
+            }
+
+            request.join().expect("blob upload thread panicked")
+        });
+
+        let status = res?.status();
+        if status != StatusCode::CREATED {
+            return Err(Error::ErrorStatus(status));
+        }
+
+        // The reader has been dropped once all progress is received, so the
+        // hasher is always available here.
+        let hasher = done_rx.recv().map_err(|_| Error::Io(std::io::ErrorKind::UnexpectedEof.into()))?;
+
+        let actual = Digest::from_hasher(hasher);
+        if actual != *digest {
+            return Err(Error::DigestMismatch { expected: *digest, actual });
+        }
+
+        Ok(())
+    }
+
+    /// Upload a file as a blob
+    ///
+    /// See [`crate::Ollama::upload_blob_from_path()`].
+    pub fn upload_blob_from_path<P, F>(&self, path: P, on_progress: Option<F>) -> Result<Digest, Error>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&BlobUploadProgress),
+    {
+        let path = path.as_ref();
+        let digest = Digest::from_reader_blocking(File::open(path)?)?;
+
+        match self.blob_exists(&digest) {
+            Ok(()) => return Ok(digest),
+            Err(Error::NotExists) => {}
+            Err(err) => return Err(err),
+        }
+
+        let file = File::open(path)?;
+        let total = file.metadata()?.len();
+
+        self.upload_blob_from_reader(&digest, file, Some(total), on_progress)?;
+
+        Ok(digest)
+    }
+
+    /// Create a model from a GGUF file
+    ///
+    /// See [`crate::Ollama::create_model_from_gguf()`].
+    pub fn create_model_from_gguf<P, F>(&self, model: &str, path: P, mut on_progress: Option<F>) -> Result<Status, Error>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&CreationProgress),
+    {
+        let path = path.as_ref();
+        let file_name = path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "model.gguf".to_string());
+
+        let digest = self.upload_blob_from_path(path, on_progress.as_mut().map(|f| {
+            |progress: &BlobUploadProgress| f(&CreationProgress::Upload(progress.clone()))
+        }))?;
+
+        let request = CreationRequest {
+            model: Some(model.to_string()),
+            files: Some(BTreeMap::from([(file_name, digest)])),
+            stream: Some(on_progress.is_some()),
+            ..Default::default()
+        };
+
+        self.create(&request, on_progress.as_mut().map(|f| {
+            |status: &Status| f(&CreationProgress::Status(status.clone()))
+        }))
+    }
+
+    /// List local models
+    pub fn local_models(&self) -> Result<ModelListResponse, Error> {
+        Ok(self.client.get(self.host.join("/api/tags")?)
+            .send()?
+            .json::<ModelListResponse>()?)
+    }
+
+    /// Show model information
+    pub fn model(&self, request: &ModelShowRequest) -> Result<ModelShowResponse, Error> {
+        Ok(self.client.post(self.host.join("/api/show")?)
+            .json(request)
+            .send()?
+            .json::<ModelShowResponse>()?)
+    }
+
+    /// Get model capabilities
+    ///
+    /// See [`crate::Ollama::model_capabilities()`].
+    pub fn model_capabilities(&self, name: &str) -> Result<Vec<Capability>, Error> {
+        let res = self.model(&ModelShowRequest {
+            name: name.to_string(),
+            verbose: None,
+        })?;
+
+        Ok(res.capabilities.unwrap_or_default())
+    }
+
+    /// Copy a model
+    ///
+    /// See [`crate::Ollama::copy_model()`].
+    pub fn copy_model(&self, request: &ModelCopyRequest) -> Result<(), Error> {
+        let status = self.client.post(self.host.join("/api/copy")?)
+            .json(request)
+            .send()?
+            .status();
+
+        match status {
+            StatusCode::OK => Ok(()),
+            StatusCode::NOT_FOUND => Err(Error::NotExists),
+            status => Err(Error::ErrorStatus(status)),
+        }
+    }
+
+    /// Delete a model
+    ///
+    /// See [`crate::Ollama::delete_model()`].
+    pub fn delete_model(&self, request: &ModelDeletionRequest) -> Result<(), Error> {
+        let status = self.client.delete(self.host.join("/api/delete")?)
+            .json(request)
+            .send()?
+            .status();
+
+        match status {
+            StatusCode::OK => Ok(()),
+            StatusCode::NOT_FOUND => Err(Error::NotExists),
+            status => Err(Error::ErrorStatus(status)),
+        }
+    }
+
+    /// Pull a model (Callback API)
+    pub fn pull_model<T>(&self, request: &ModelSyncRequest, on_stream: Option<T>) -> Result<ModelPullStatus, Error>
+    where
+        T: FnMut(&ModelPullStatus),
+    {
+        self.sync_model("/api/pull", request, on_stream)
+    }
+
+    /// Pull a model (Iterator API)
+    pub fn pull_model_streamed(&self, request: &ModelSyncRequest) -> Result<impl Iterator<Item = Result<ModelPullStatus, Error>>, Error> {
+        self.sync_model_streamed("/api/pull", request)
+    }
+
+    /// Push a model (Callback API)
+    pub fn push_model<T>(&self, request: &ModelSyncRequest, on_stream: Option<T>) -> Result<ModelPushStatus, Error>
+    where
+        T: FnMut(&ModelPushStatus),
+    {
+        self.sync_model("/api/push", request, on_stream)
+    }
+
+    /// Push a model (Iterator API)
+    pub fn push_model_streamed(&self, request: &ModelSyncRequest) -> Result<impl Iterator<Item = Result<ModelPushStatus, Error>>, Error> {
+        self.sync_model_streamed("/api/push", request)
+    }
+
+    /// Pull and push always answer with a stream, whatever `stream` says
+    fn sync_model<R, T>(&self, pathname: &str, request: &ModelSyncRequest, mut on_stream: Option<T>) -> Result<R, Error>
+    where
+        R: DeserializeOwned,
+        T: FnMut(&R),
+    {
+        let mut final_res: Option<R> = None;
+
+        if let Some(ref mut f) = on_stream {
+            for cur_res in self.sync_model_streamed::<R>(pathname, request)? {
+                let cur_res = cur_res?;
+                f(&cur_res);
+                final_res = Some(cur_res);
+            }
+        }
+
+        final_res.ok_or(if on_stream.is_some() { Error::EmptyResponse } else { Error::NoCallback })
+    }
+
+    fn sync_model_streamed<R>(&self, pathname: &str, request: &ModelSyncRequest) -> Result<JsonStream<R>, Error> {
+        let res = self.client.post(self.host.join(pathname)?)
+            .json(request)
+            .send()?;
+
+        Ok(JsonStream::new(res))
+    }
+
+    /// Generate embeddings
+    pub fn generate_embeddings(&self, request: &EmbeddingGenerationRequest) -> Result<EmbeddingGenerationResponse, Error> {
+        Ok(self.client.post(self.host.join("/api/embeddings")?)
+            .json(request)
+            .send()?
+            .json::<EmbeddingGenerationResponse>()?)
+    }
+
+    /// Generate embeddings for one or more inputs
+    ///
+    /// See [`crate::Ollama::embed()`].
+    pub fn embed(&self, request: &EmbedRequest) -> Result<EmbedResponse, Error> {
+        Ok(self.client.post(self.host.join("/api/embed")?)
+            .json(request)
+            .send()?
+            .json::<EmbedResponse>()?)
+    }
+
+    /// List running models
+    pub fn running_models(&self) -> Result<RunningModelResponse, Error> {
+        Ok(self.client.get(self.host.join("/api/ps")?)
+            .send()?
+            .json::<RunningModelResponse>()?)
+    }
+
+    /// Get server version
+    pub fn version(&self) -> Result<VersionResponse, Error> {
+        Ok(self.client.get(self.host.join("/api/version")?)
+            .send()?
+            .json::<VersionResponse>()?)
+    }
+
+    /// Get features supported by the server, based on its version
+    pub fn server_features(&self) -> Result<ServerFeatures, Error> {
+        Ok(self.version()?.into())
+    }
+}
+
+impl FromStr for Ollama {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(Url::from_str(s)?)
+    }
+}
+
+impl Default for Ollama {
+    fn default() -> Self {
+        Self::from_str("http://127.0.0.1:11434").unwrap()
+    }
+}
+
+/// Reader hashing and reporting everything read through it
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+    progress_tx: mpsc::Sender<u64>,
+    /// Receives the hasher of everything read, once the reader is dropped
+    done_tx: mpsc::Sender<Sha256>,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+
+        if n > 0 {
+            self.hasher.update(&buf[..n]);
+            let _ = self.progress_tx.send(n as u64);
+        }
+
+        Ok(n)
+    }
+}
+
+impl<R> Drop for HashingReader<R> {
+    fn drop(&mut self) {
+        // Sized bodies are not necessarily read up to EOF, and a failed read
+        // must still be verified, so the hasher is handed over here.
+        let _ = self.done_tx.send(std::mem::take(&mut self.hasher));
+    }
+}
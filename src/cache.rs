@@ -0,0 +1,466 @@
+//! Response caching
+//!
+//! A [`ResponseCache`] attached with [`Ollama::with_cache`](crate::Ollama::with_cache)
+//! answers repeated embedding requests, and repeated generation and chat
+//! requests that are deterministic (`temperature` of 0 or a `seed` set), from
+//! a [`CacheBackend`] instead of the server. Streamed calls are cached too and
+//! replayed chunk by chunk.
+//!
+//! Entries are keyed by [`ResponseCache::key`]: a hash of the endpoint, the
+//! digest of the model and the request body, so re-pulling a model under the
+//! same name does not serve stale responses.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use ollama_rest::{cache::{DiskCache, MemoryCache, ResponseCache}, Ollama};
+//!
+//! // Keep the 1000 most recently used responses in memory
+//! let ollama = Ollama::default().with_cache(ResponseCache::new(MemoryCache::new(1000)));
+//!
+//! // Keep responses on disk across runs
+//! let ollama = Ollama::default().with_cache(ResponseCache::new(DiskCache::new(".ollama-cache")));
+//!
+//! // ...
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use bytes::{Bytes, BytesMut};
+use futures::{future::BoxFuture, stream, StreamExt};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, CONTENT_TYPE},
+    Method, StatusCode,
+};
+use serde_json::{Map, Value};
+
+use crate::{
+    errors::Error,
+    models::{digest::Digest, model::ModelListResponse},
+    pool::canonical_model,
+    transport::TransportResponse,
+};
+
+/// Endpoints whose responses may be cached
+const CACHED_PATHS: [&str; 4] = ["/api/generate", "/api/chat", "/api/embed", "/api/embeddings"];
+
+/// Request fields that do not affect the response
+const IGNORED_FIELDS: [&str; 2] = ["model", "keep_alive"];
+
+/// Storage of cached response bodies
+///
+/// Caching is best-effort: backends report failures as misses and may drop
+/// entries at any time.
+pub trait CacheBackend: Send + Sync {
+    fn get(&self, key: &str) -> BoxFuture<'_, Option<Bytes>>;
+
+    fn put(&self, key: &str, value: Bytes) -> BoxFuture<'_, ()>;
+}
+
+impl<T: CacheBackend + ?Sized> CacheBackend for Arc<T> {
+    fn get(&self, key: &str) -> BoxFuture<'_, Option<Bytes>> {
+        (**self).get(key)
+    }
+
+    fn put(&self, key: &str, value: Bytes) -> BoxFuture<'_, ()> {
+        (**self).put(key, value)
+    }
+}
+
+#[derive(Default)]
+struct Lru {
+    /// Value and last use of every entry
+    entries: HashMap<String, (Bytes, u64)>,
+    /// Entries by last use
+    order: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl Lru {
+    fn touch(&mut self, key: &str) -> Option<Bytes> {
+        let (value, used) = self.entries.get_mut(key)?;
+
+        self.order.remove(used);
+        self.tick += 1;
+        *used = self.tick;
+        self.order.insert(self.tick, key.to_string());
+
+        Some(value.clone())
+    }
+}
+
+/// In-memory backend evicting the least recently used entries
+pub struct MemoryCache {
+    capacity: usize,
+    lru: Mutex<Lru>,
+}
+
+impl MemoryCache {
+    /// Backend holding at most `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            lru: Mutex::new(Lru::default()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lru.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        *self.lru.lock().unwrap() = Lru::default();
+    }
+}
+
+impl CacheBackend for MemoryCache {
+    fn get(&self, key: &str) -> BoxFuture<'_, Option<Bytes>> {
+        let value = self.lru.lock().unwrap().touch(key);
+        Box::pin(async move { value })
+    }
+
+    fn put(&self, key: &str, value: Bytes) -> BoxFuture<'_, ()> {
+        let mut lru = self.lru.lock().unwrap();
+
+        if lru.touch(key).is_some() {
+            lru.entries.get_mut(key).unwrap().0 = value;
+        } else {
+            while lru.entries.len() >= self.capacity {
+                let Some((_, oldest)) = lru.order.pop_first() else { break };
+                lru.entries.remove(&oldest);
+            }
+
+            lru.tick += 1;
+            let tick = lru.tick;
+            lru.entries.insert(key.to_string(), (value, tick));
+            lru.order.insert(tick, key.to_string());
+        }
+
+        Box::pin(async {})
+    }
+}
+
+/// On-disk backend, one file per entry
+///
+/// Entries never expire; delete the directory to clear the cache.
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    /// Backend storing entries in `dir`, created on first write
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &std::path::Path {
+        &self.dir
+    }
+}
+
+impl CacheBackend for DiskCache {
+    fn get(&self, key: &str) -> BoxFuture<'_, Option<Bytes>> {
+        let path = self.dir.join(key);
+        Box::pin(async move { tokio::fs::read(path).await.ok().map(Bytes::from) })
+    }
+
+    fn put(&self, key: &str, value: Bytes) -> BoxFuture<'_, ()> {
+        let path = self.dir.join(key);
+        let partial = self.dir.join(format!("{key}.partial"));
+
+        Box::pin(async move {
+            // Written aside then renamed, so readers never see a partial entry
+            let _ = async {
+                tokio::fs::create_dir_all(&self.dir).await?;
+                tokio::fs::write(&partial, &value).await?;
+                tokio::fs::rename(&partial, &path).await
+            }.await;
+        })
+    }
+}
+
+/// Key of a cacheable request
+pub(crate) struct CacheKey {
+    key: String,
+    /// Whether the response is streamed
+    streamed: bool,
+}
+
+/// Model digests, as of the last listing
+#[derive(Default)]
+struct Digests {
+    by_model: HashMap<String, String>,
+    listed_at: Option<Instant>,
+}
+
+/// Counters of a [`ResponseCache`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Cache of responses, in front of a [`CacheBackend`]
+///
+/// Clones share the same backend and counters.
+#[derive(Clone)]
+pub struct ResponseCache {
+    backend: Arc<dyn CacheBackend>,
+    deterministic_only: bool,
+    digest_ttl: Duration,
+    digests: Arc<Mutex<Digests>>,
+    counters: Arc<Counters>,
+}
+
+impl ResponseCache {
+    pub fn new<B: CacheBackend + 'static>(backend: B) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            deterministic_only: true,
+            digest_ttl: Duration::from_secs(60),
+            digests: Arc::default(),
+            counters: Arc::default(),
+        }
+    }
+
+    /// Only cache generations and chats with a `temperature` of 0 or a `seed` (default: true)
+    ///
+    /// Embeddings are always cached.
+    pub fn with_deterministic_only(mut self, deterministic_only: bool) -> Self {
+        self.deterministic_only = deterministic_only;
+        self
+    }
+
+    /// Age after which model digests are listed again (default: 60 seconds)
+    pub fn with_digest_ttl(mut self, ttl: Duration) -> Self {
+        self.digest_ttl = ttl;
+        self
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Canonical key of a request
+    ///
+    /// ## Parameters
+    /// - `endpoint`: Endpoint path, e.g. `/api/embed`
+    /// - `model_digest`: Digest of the requested model
+    /// - `request`: Request body; null fields, `model` and `keep_alive` are left out
+    ///
+    /// ## Returns
+    /// Hex SHA-256 digest of the canonical JSON of the parameters, with sorted keys
+    pub fn key(endpoint: &str, model_digest: &str, request: &Value) -> String {
+        let mut request = canonical(request);
+        if let Value::Object(fields) = &mut request {
+            for field in IGNORED_FIELDS {
+                fields.remove(field);
+            }
+        }
+
+        let canonical = serde_json::json!({
+            "endpoint": endpoint,
+            "model": model_digest,
+            "request": request,
+        });
+
+        Digest::from_bytes(canonical.to_string().as_bytes()).to_hex()
+    }
+
+    /// Whether the response to `body` sent to `path` may be cached
+    fn is_cacheable(&self, path: &str, body: &Value) -> bool {
+        match path {
+            "/api/embed" | "/api/embeddings" => true,
+            _ if !self.deterministic_only => true,
+            _ => {
+                let options = &body["options"];
+                options["temperature"].as_f64() == Some(0.0) || !options["seed"].is_null()
+            },
+        }
+    }
+
+    async fn digest<F, Fut>(&self, model: &str, list_models: F) -> Option<String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<ModelListResponse, Error>>,
+    {
+        let model = canonical_model(model);
+
+        {
+            let digests = self.digests.lock().unwrap();
+            if let (Some(digest), Some(listed_at)) = (digests.by_model.get(&model), digests.listed_at) {
+                if listed_at.elapsed() < self.digest_ttl {
+                    return Some(digest.clone());
+                }
+            }
+        }
+
+        let listed = list_models().await.ok()?;
+        let mut digests = self.digests.lock().unwrap();
+        *digests = Digests {
+            by_model: listed.models.into_iter().map(|listed| (canonical_model(&listed.name), listed.digest.to_string())).collect(),
+            listed_at: Some(Instant::now()),
+        };
+
+        digests.by_model.get(&model).cloned()
+    }
+
+    /// Key of a request, if its response may be cached
+    ///
+    /// ## Parameters
+    /// - `method`, `path`, `body`: Request method, URL path and JSON body
+    /// - `list_models`: Lists the models of the server, to find the digest of the requested one
+    pub(crate) async fn key_of<F, Fut>(&self, method: &Method, path: &str, body: Option<&[u8]>, list_models: F) -> Option<CacheKey>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<ModelListResponse, Error>>,
+    {
+        if method != Method::POST || !CACHED_PATHS.contains(&path) {
+            return None;
+        }
+
+        let body = serde_json::from_slice::<Value>(body?).ok()?;
+        if !self.is_cacheable(path, &body) {
+            return None;
+        }
+
+        let digest = self.digest(body["model"].as_str()?, list_models).await?;
+
+        Some(CacheKey {
+            key: Self::key(path, &digest, &body),
+            streamed: matches!(path, "/api/generate" | "/api/chat") && body["stream"].as_bool().unwrap_or(true),
+        })
+    }
+
+    /// Cached response of `key`, if any
+    pub(crate) async fn get(&self, key: &CacheKey) -> Option<TransportResponse> {
+        let Some(body) = self.backend.get(&key.key).await else {
+            self.counters.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        };
+        self.counters.hits.fetch_add(1, Ordering::Relaxed);
+
+        // Streamed bodies are replayed line by line
+        let content_type = match key.streamed {
+            true => "application/x-ndjson",
+            false => "application/json; charset=utf-8",
+        };
+
+        let mut chunks = Vec::new();
+        let mut rest = body;
+        while let Some(pos) = rest.iter().position(|&b| b == b'\n') {
+            chunks.push(Ok(rest.split_to(pos + 1)));
+        }
+        if !rest.is_empty() {
+            chunks.push(Ok(rest));
+        }
+
+        Some(TransportResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::from_iter([(CONTENT_TYPE, HeaderValue::from_static(content_type))]),
+            body: stream::iter(chunks).boxed(),
+        })
+    }
+
+    /// Pass `response` through, storing its body under `key` once fully and successfully read
+    pub(crate) fn store(&self, key: CacheKey, mut response: TransportResponse) -> TransportResponse {
+        if !response.status.is_success() {
+            return response;
+        }
+
+        let backend = self.backend.clone();
+        let key = key.key;
+        let body = response.body;
+
+        response.body = stream::unfold(Some((body, BytesMut::new())), move |state| {
+            let backend = backend.clone();
+            let key = key.clone();
+
+            async move {
+                let (mut body, mut whole) = state?;
+
+                match body.next().await {
+                    Some(Ok(chunk)) => {
+                        whole.extend_from_slice(&chunk);
+                        Some((Ok(chunk), Some((body, whole))))
+                    },
+                    Some(Err(err)) => Some((Err(err), None)),
+                    None => {
+                        backend.put(&key, whole.freeze()).await;
+                        None
+                    },
+                }
+            }
+        }).boxed();
+
+        response
+    }
+}
+
+/// `value` without null fields, with sorted keys
+fn canonical(value: &Value) -> Value {
+    match value {
+        Value::Object(fields) => {
+            let sorted = fields.iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(name, value)| (name.clone(), canonical(value)))
+                .collect::<BTreeMap<_, _>>();
+
+            Value::Object(sorted.into_iter().collect::<Map<_, _>>())
+        },
+        Value::Array(items) => Value::Array(items.iter().map(canonical).collect()),
+        value => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn canonical_keys() {
+        let key = ResponseCache::key("/api/embed", "sha256:00", &json!({ "model": "a", "input": "hi", "options": { "seed": 1, "top_k": null } }));
+
+        assert_eq!(key, ResponseCache::key("/api/embed", "sha256:00", &json!({ "options": { "seed": 1 }, "input": "hi", "model": "b", "keep_alive": "5m" })));
+        assert_ne!(key, ResponseCache::key("/api/embed", "sha256:01", &json!({ "model": "a", "input": "hi", "options": { "seed": 1 } })));
+        assert_ne!(key, ResponseCache::key("/api/embeddings", "sha256:00", &json!({ "model": "a", "input": "hi", "options": { "seed": 1 } })));
+    }
+
+    #[tokio::test]
+    async fn least_recently_used() {
+        let cache = MemoryCache::new(2);
+        cache.put("a", Bytes::from_static(b"1")).await;
+        cache.put("b", Bytes::from_static(b"2")).await;
+
+        assert!(cache.get("a").await.is_some());
+        cache.put("c", Bytes::from_static(b"3")).await;
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("b").await.is_none());
+        assert_eq!(cache.get("a").await, Some(Bytes::from_static(b"1")));
+    }
+}
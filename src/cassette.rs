@@ -0,0 +1,527 @@
+//! Record-and-replay HTTP cassettes
+//!
+//! A [`Cassette`] sits between [`crate::Ollama`] and the server. In record mode
+//! every interaction (request JSON, streamed response lines and their timing)
+//! is kept in memory, and written to a JSON file once the instance, its clones
+//! and their response streams are dropped. The file can later be replayed
+//! without a server.
+//!
+//! ## Examples
+//!
+//! ```rust,no_run
+//! use ollama_rest::{cassette::{Cassette, CassetteMode}, Ollama};
+//!
+//! # fn main() -> Result<(), ollama_rest::errors::Error> {
+//! let cassette = Cassette::new("tests/cassettes/generate.json")
+//!     .with_mode(CassetteMode::Auto);
+//!
+//! let ollama = Ollama::with_cassette("http://127.0.0.1:11434".parse().unwrap(), cassette)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::BTreeSet,
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures::{future::BoxFuture, stream::BoxStream, StreamExt, TryStreamExt};
+use reqwest::{header::{HeaderMap, HeaderValue, CONTENT_TYPE}, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest as _, Sha256};
+
+use crate::{
+    errors::Error,
+    models::{digest::Digest, errors::ParsingError},
+    transport::{lines, ReqwestTransport, RequestBody, Transport, TransportRequest, TransportResponse},
+};
+
+/// What a cassette does with requests
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Send requests to the server and overwrite the cassette with them
+    Record,
+    /// Answer requests from the cassette only
+    #[default]
+    Replay,
+    /// Replay if the cassette file exists, record otherwise
+    Auto,
+    /// Send requests to the server without touching the cassette
+    Passthrough,
+}
+
+impl CassetteMode {
+    /// Environment variable read by [`CassetteMode::from_env`]
+    pub const ENV_VAR: &'static str = "OLLAMA_CASSETTE_MODE";
+
+    /// Read the mode from [`CassetteMode::ENV_VAR`]
+    ///
+    /// ## Returns
+    /// - `None` if the variable is unset or holds an unknown mode
+    pub fn from_env() -> Option<Self> {
+        std::env::var(Self::ENV_VAR).ok()?.parse().ok()
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Record => "record",
+            Self::Replay => "replay",
+            Self::Auto => "auto",
+            Self::Passthrough => "passthrough",
+        }
+    }
+}
+
+impl AsRef<str> for CassetteMode {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Display for CassetteMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for CassetteMode {
+    type Err = ParsingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "record" => Ok(Self::Record),
+            "replay" => Ok(Self::Replay),
+            "auto" => Ok(Self::Auto),
+            "passthrough" | "off" => Ok(Self::Passthrough),
+            _ => Err(ParsingError::InvalidStr),
+        }
+    }
+}
+
+/// Cassette file and the rules used to replay it
+#[derive(Debug, Clone)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    ignored_fields: BTreeSet<String>,
+    replay_timing: bool,
+}
+
+impl Cassette {
+    /// Cassette stored at `path`
+    ///
+    /// The mode is taken from [`CassetteMode::ENV_VAR`], defaulting to
+    /// [`CassetteMode::Replay`]. `keep_alive` is ignored when matching requests.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            mode: CassetteMode::from_env().unwrap_or_default(),
+            ignored_fields: BTreeSet::from(["keep_alive".to_string()]),
+            replay_timing: false,
+        }
+    }
+
+    /// Override the mode
+    pub fn with_mode(mut self, mode: CassetteMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Ignore a top-level request field when matching requests
+    pub fn with_ignored_field<S: Into<String>>(mut self, field: S) -> Self {
+        self.ignored_fields.insert(field.into());
+        self
+    }
+
+    /// Reproduce the recorded delays between response lines when replaying
+    pub fn with_replay_timing(mut self, replay_timing: bool) -> Self {
+        self.replay_timing = replay_timing;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    /// Normalize a request body for matching
+    ///
+    /// Nulls are dropped recursively and ignored fields are removed, so that
+    /// `{"keep_alive": "5m", "options": {"b": 1, "a": null}}` matches `{"options": {"b": 1}}`.
+    /// Object key order never matters.
+    fn normalize(&self, body: Option<Value>) -> Option<Value> {
+        fn drop_nulls(value: Value) -> Value {
+            match value {
+                Value::Object(map) => Value::Object(
+                    map.into_iter()
+                        .filter(|(_, v)| !v.is_null())
+                        .map(|(k, v)| (k, drop_nulls(v)))
+                        .collect(),
+                ),
+                Value::Array(items) => Value::Array(items.into_iter().map(drop_nulls).collect()),
+                other => other,
+            }
+        }
+
+        let mut body = drop_nulls(body?);
+        if let Value::Object(map) = &mut body {
+            map.retain(|k, _| !self.ignored_fields.contains(k));
+        }
+
+        Some(body)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    request: InteractionRequest,
+    response: InteractionResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct InteractionRequest {
+    method: String,
+    path: String,
+    body: Option<Value>,
+    /// Streamed body, like a blob upload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stream: Option<StreamedBody>,
+}
+
+/// Length and digest of a streamed request body
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct StreamedBody {
+    length: u64,
+    digest: Digest,
+}
+
+/// Streamed request body being hashed as it is read
+#[derive(Default)]
+struct BodyHasher {
+    length: u64,
+    hasher: Sha256,
+}
+
+impl BodyHasher {
+    fn update(&mut self, chunk: &[u8]) {
+        self.length += chunk.len() as u64;
+        self.hasher.update(chunk);
+    }
+
+    fn finish(&mut self) -> StreamedBody {
+        StreamedBody {
+            length: self.length,
+            digest: Digest::from_hasher(std::mem::take(&mut self.hasher)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct InteractionResponse {
+    status: u16,
+    content_type: Option<String>,
+    chunks: Vec<Chunk>,
+}
+
+/// One response line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Chunk {
+    /// Milliseconds since the previous line (or since the request was sent)
+    delay_ms: u64,
+    data: String,
+}
+
+struct State {
+    interactions: Vec<Interaction>,
+    used: Vec<bool>,
+    /// Where recorded interactions are written, if recording
+    path: Option<PathBuf>,
+}
+
+impl State {
+    fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let file = CassetteFile { interactions: self.interactions.clone() };
+        std::fs::write(path, serde_json::to_vec_pretty(&file)?)?;
+
+        Ok(())
+    }
+}
+
+impl Drop for State {
+    /// Write the recording once, when neither the transport nor any response uses it anymore
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            // Nothing is left to report a failure to; it only loses the recording
+            let _ = self.save(path);
+        }
+    }
+}
+
+/// Response body being recorded
+///
+/// The interaction is kept when the tee is dropped, so that responses whose
+/// body is never read (or only partly read) are recorded as well.
+struct Tee {
+    lines: BoxStream<'static, Result<Bytes, Error>>,
+    last: Instant,
+    interaction: Option<Interaction>,
+    state: Arc<Mutex<State>>,
+}
+
+impl Drop for Tee {
+    fn drop(&mut self) {
+        if let Some(interaction) = self.interaction.take() {
+            let mut state = self.state.lock().unwrap();
+            state.interactions.push(interaction);
+            state.used.push(true);
+        }
+    }
+}
+
+/// Transport recording to or replaying from a [`Cassette`]
+pub(crate) struct CassetteTransport {
+    cassette: Cassette,
+    /// Resolved mode, never [`CassetteMode::Auto`]
+    mode: CassetteMode,
+    inner: ReqwestTransport,
+    state: Arc<Mutex<State>>,
+}
+
+impl CassetteTransport {
+    pub fn new(cassette: Cassette, inner: ReqwestTransport) -> Result<Self, Error> {
+        let mode = match cassette.mode {
+            CassetteMode::Auto if cassette.path.exists() => CassetteMode::Replay,
+            CassetteMode::Auto => CassetteMode::Record,
+            mode => mode,
+        };
+
+        let interactions = match mode {
+            CassetteMode::Replay => serde_json::from_slice::<CassetteFile>(&std::fs::read(&cassette.path)?)?.interactions,
+            _ => Vec::new(),
+        };
+
+        let state = State {
+            used: vec![false; interactions.len()],
+            interactions,
+            path: (mode == CassetteMode::Record).then(|| cassette.path.clone()),
+        };
+
+        Ok(Self {
+            cassette,
+            mode,
+            inner,
+            state: Arc::new(Mutex::new(state)),
+        })
+    }
+
+    fn request_body(request: &TransportRequest) -> Option<Value> {
+        request.json_body().map(|body| serde_json::from_slice(body).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned())))
+    }
+
+    /// Hash a streamed request body while it is sent
+    fn hash_stream(request: &mut TransportRequest) -> Option<Arc<Mutex<BodyHasher>>> {
+        match std::mem::replace(&mut request.body, RequestBody::Empty) {
+            RequestBody::Stream(stream) => {
+                let hasher = Arc::new(Mutex::new(BodyHasher::default()));
+                let hashing = hasher.clone();
+
+                request.body = RequestBody::Stream(stream.inspect_ok(move |chunk| hashing.lock().unwrap().update(chunk)).boxed());
+                Some(hasher)
+            }
+            body => {
+                request.body = body;
+                None
+            }
+        }
+    }
+
+    async fn replay(&self, mut request: TransportRequest) -> Result<TransportResponse, Error> {
+        let path = request.url.path().to_string();
+        let body = self.cassette.normalize(Self::request_body(&request));
+
+        // Streamed bodies are read as if sent, and matched by length and digest
+        let stream = match std::mem::replace(&mut request.body, RequestBody::Empty) {
+            RequestBody::Stream(mut stream) => {
+                let mut hasher = BodyHasher::default();
+                while let Some(chunk) = stream.try_next().await.map_err(|err| Error::Io(std::io::Error::other(err)))? {
+                    hasher.update(&chunk);
+                }
+                Some(hasher.finish())
+            }
+            _ => None,
+        };
+
+        let response = {
+            let mut state = self.state.lock().unwrap();
+            let State { interactions, used, .. } = &mut *state;
+
+            let index = interactions.iter()
+                .zip(used.iter())
+                .position(|(interaction, used)| !used
+                    && interaction.request.method == request.method.as_str()
+                    && interaction.request.path == path
+                    && interaction.request.stream == stream
+                    && self.cassette.normalize(interaction.request.body.clone()) == body)
+                .ok_or_else(|| Error::NoRecordedInteraction {
+                    method: request.method.to_string(),
+                    path: path.clone(),
+                })?;
+
+            used[index] = true;
+            interactions[index].response.clone()
+        };
+
+        let mut headers = HeaderMap::new();
+        if let Some(value) = response.content_type.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+            headers.insert(CONTENT_TYPE, value);
+        }
+
+        let replay_timing = self.cassette.replay_timing;
+        let body = futures::stream::iter(response.chunks).then(move |chunk| async move {
+            if replay_timing && chunk.delay_ms > 0 {
+                tokio::time::sleep(Duration::from_millis(chunk.delay_ms)).await;
+            }
+
+            let mut data = chunk.data.into_bytes();
+            data.push(b'\n');
+            Ok(Bytes::from(data))
+        });
+
+        Ok(TransportResponse {
+            status: StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            headers,
+            body: body.boxed(),
+        })
+    }
+
+    async fn record(&self, mut request: TransportRequest) -> Result<TransportResponse, Error> {
+        let mut recorded = InteractionRequest {
+            method: request.method.to_string(),
+            path: request.url.path().to_string(),
+            body: Self::request_body(&request),
+            stream: None,
+        };
+        let hasher = Self::hash_stream(&mut request);
+
+        let started = Instant::now();
+        let response = self.inner.send(request).await?;
+
+        // The body has been sent by the time the response arrives
+        recorded.stream = hasher.map(|hasher| hasher.lock().unwrap().finish());
+
+        let interaction = Interaction {
+            request: recorded,
+            response: InteractionResponse {
+                status: response.status.as_u16(),
+                content_type: response.headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).map(str::to_string),
+                chunks: Vec::new(),
+            },
+        };
+
+        let tee = Tee {
+            lines: lines(response.body).boxed(),
+            last: started,
+            interaction: Some(interaction),
+            state: self.state.clone(),
+        };
+
+        // Copy every line into the interaction, which is kept once the tee is dropped
+        let body = futures::stream::unfold(Some(tee), |tee| async move {
+            let mut tee = tee?;
+
+            match tee.lines.next().await {
+                Some(Ok(line)) => {
+                    let now = Instant::now();
+                    if let Some(interaction) = tee.interaction.as_mut() {
+                        interaction.response.chunks.push(Chunk {
+                            delay_ms: now.duration_since(tee.last).as_millis() as u64,
+                            data: String::from_utf8_lossy(&line).into_owned(),
+                        });
+                    }
+                    tee.last = now;
+
+                    let mut data = line.to_vec();
+                    data.push(b'\n');
+                    Some((Ok(Bytes::from(data)), Some(tee)))
+                },
+                Some(Err(err)) => Some((Err(err), None)),
+                None => None,
+            }
+        });
+
+        Ok(TransportResponse {
+            status: response.status,
+            headers: response.headers,
+            body: body.boxed(),
+        })
+    }
+}
+
+impl Transport for CassetteTransport {
+    fn send(&self, request: TransportRequest) -> BoxFuture<'_, Result<TransportResponse, Error>> {
+        Box::pin(async move {
+            match self.mode {
+                CassetteMode::Replay => self.replay(request).await,
+                CassetteMode::Record => self.record(request).await,
+                _ => self.inner.send(request).await,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn parse_modes() {
+        assert_eq!("Record".parse::<CassetteMode>().unwrap(), CassetteMode::Record);
+        assert_eq!(" replay ".parse::<CassetteMode>().unwrap(), CassetteMode::Replay);
+        assert_eq!("off".parse::<CassetteMode>().unwrap(), CassetteMode::Passthrough);
+        assert!("rewind".parse::<CassetteMode>().is_err());
+    }
+
+    #[test]
+    fn normalize_request_bodies() {
+        let cassette = Cassette::new("unused.json").with_ignored_field("seed");
+
+        let recorded = json!({
+            "model": "llama3",
+            "keep_alive": "5m",
+            "seed": 1,
+            "options": { "temperature": 0.2, "top_k": 40, "stop": null },
+        });
+        let sent = json!({
+            "options": { "top_k": 40, "temperature": 0.2 },
+            "model": "llama3",
+            "keep_alive": null,
+        });
+
+        assert_eq!(cassette.normalize(Some(recorded)), cassette.normalize(Some(sent)));
+        assert_ne!(
+            cassette.normalize(Some(json!({ "model": "llama3" }))),
+            cassette.normalize(Some(json!({ "model": "mistral" }))),
+        );
+    }
+}
@@ -4,22 +4,69 @@ use std::fmt::Display;
 
 use reqwest::StatusCode;
 
+use crate::{models::{digest::Digest, version::{ServerFeature, Version}}, transport::BoxError};
+
 #[derive(Debug)]
 pub enum Error {
     ClientCreation(reqwest::Error),
+    /// Digest of the uploaded content does not match the expected one
+    DigestMismatch {
+        expected: Digest,
+        actual: Digest,
+    },
+    /// Vectors do not have the same length
+    DimensionMismatch {
+        expected: usize,
+        actual: usize,
+    },
     EmptyResponse,
     ErrorStatus(StatusCode),
     Event,
+    Io(std::io::Error),
+    /// Rejected by a middleware layer
+    Middleware(BoxError),
     NoCallback,
+    /// Every host of a pool is unavailable
+    NoAvailableHost,
+    /// No recorded interaction in a cassette matches the request
+    NoRecordedInteraction {
+        method: String,
+        path: String,
+    },
     NotExists,
+    /// Concurrency limiter queue is full
+    QueueFull,
     StreamingOff,
+    /// Server version does not support a feature
+    UnsupportedFeature {
+        feature: ServerFeature,
+        version: Version,
+    },
     UrlParsing(url::ParseError),
     JsonDecoding(serde_json::Error),
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{self}")
+        match self {
+            Self::ClientCreation(err) => write!(f, "HTTP client error: {err}"),
+            Self::DigestMismatch { expected, actual } => write!(f, "digest mismatch: expected {expected}, got {actual}"),
+            Self::DimensionMismatch { expected, actual } => write!(f, "vector dimension mismatch: expected {expected}, got {actual}"),
+            Self::EmptyResponse => write!(f, "empty response"),
+            Self::ErrorStatus(status) => write!(f, "server responded with status {status}"),
+            Self::Event => write!(f, "event error"),
+            Self::Io(err) => write!(f, "I/O error: {err}"),
+            Self::Middleware(err) => write!(f, "middleware error: {err}"),
+            Self::NoCallback => write!(f, "no callback provided for streamed response"),
+            Self::NoAvailableHost => write!(f, "no available host"),
+            Self::NoRecordedInteraction { method, path } => write!(f, "no recorded interaction matches {method} {path}"),
+            Self::NotExists => write!(f, "resource does not exist"),
+            Self::QueueFull => write!(f, "request queue is full"),
+            Self::StreamingOff => write!(f, "streaming is turned off in the request"),
+            Self::UnsupportedFeature { feature, version } => write!(f, "{feature} requires Ollama {} or later, server is {version}", feature.min_version()),
+            Self::UrlParsing(err) => write!(f, "URL parsing error: {err}"),
+            Self::JsonDecoding(err) => write!(f, "JSON decoding error: {err}"),
+        }
     }
 }
 
@@ -31,6 +78,12 @@ impl From<reqwest::Error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
 impl From<url::ParseError> for Error {
     fn from(value: url::ParseError) -> Self {
         Self::UrlParsing(value)
@@ -0,0 +1,83 @@
+//! Host URLs shared by the clients
+
+use std::path::{Path, PathBuf};
+
+use reqwest::Url;
+
+use crate::errors::Error;
+
+/// Scheme of Unix domain socket hosts, e.g. `unix:///run/ollama.sock`
+pub(crate) const UNIX_SCHEME: &str = "unix";
+
+/// Server location
+#[derive(Debug, Clone)]
+pub(crate) struct Host {
+    /// URL as given
+    url: Url,
+    /// URL endpoint paths are joined onto
+    base: Url,
+    /// Socket path of `unix://` hosts
+    socket: Option<PathBuf>,
+}
+
+impl Host {
+    pub fn as_str(&self) -> &str {
+        self.url.as_str()
+    }
+
+    /// URL of an endpoint
+    pub fn join(&self, path: &str) -> Result<Url, url::ParseError> {
+        self.base.join(path)
+    }
+
+    /// Socket path of `unix://` hosts
+    ///
+    /// ## Returns
+    /// Fails on platforms without Unix domain sockets
+    pub fn socket(&self) -> Result<Option<&Path>, Error> {
+        match &self.socket {
+            Some(_) if cfg!(not(unix)) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Unix domain sockets are not supported on this platform",
+            ).into()),
+            socket => Ok(socket.as_deref()),
+        }
+    }
+}
+
+impl From<Url> for Host {
+    fn from(url: Url) -> Self {
+        if url.scheme() != UNIX_SCHEME {
+            return Self { base: url.clone(), url, socket: None };
+        }
+
+        // Requests still need an HTTP URL; the socket decides where they go
+        let socket = url.to_file_path().unwrap_or_else(|_| PathBuf::from(url.path()));
+
+        Self {
+            url,
+            base: Url::parse("http://localhost/").unwrap(),
+            socket: Some(socket),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_hosts() {
+        let host = Host::from(Url::parse("unix:///run/ollama%20proxy.sock").unwrap());
+
+        assert_eq!(host.as_str(), "unix:///run/ollama%20proxy.sock");
+        assert_eq!(host.join("/api/generate").unwrap().as_str(), "http://localhost/api/generate");
+
+        #[cfg(unix)]
+        assert_eq!(host.socket().unwrap(), Some(Path::new("/run/ollama proxy.sock")));
+
+        let host = Host::from(Url::parse("http://127.0.0.1:11434").unwrap());
+        assert_eq!(host.join("/api/tags").unwrap().as_str(), "http://127.0.0.1:11434/api/tags");
+        assert!(host.socket().unwrap().is_none());
+    }
+}
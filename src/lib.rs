@@ -1,17 +1,49 @@
 //! Asynchronous Rust bindings of Ollama REST API.
 
-use std::str::FromStr;
+use std::{collections::BTreeMap, path::Path, str::FromStr, sync::{Arc, Mutex}};
 
+use bytes::Bytes;
+use cache::ResponseCache;
 use errors::Error;
-use futures::{Stream, StreamExt};
+use futures::{future::{self, Either}, Stream, StreamExt, TryStreamExt};
 use models::{
-    chat::{ChatRequest, ChatResponse}, create::CreationRequest, embeddings::{EmbeddingGenerationRequest, EmbeddingGenerationResponse}, generate::{GenerationRequest, GenerationResponse}, model::*, version::VersionResponse, Status
+    blob::BlobUploadProgress, chat::{ChatRequest, ChatResponse}, create::{CreationProgress, CreationRequest}, digest::Digest, embeddings::{EmbedRequest, EmbedResponse, EmbeddingGenerationRequest, EmbeddingGenerationResponse}, generate::{GenerationRequest, GenerationResponse}, model::*, version::{ServerFeatures, VersionResponse}, Status
 };
-use reqwest::{Client, ClientBuilder, StatusCode, Url};
-use tokio::fs::File;
-
+use reqwest::{Client, ClientBuilder, Method, StatusCode, Url};
+use sha2::{Digest as _, Sha256};
+use host::Host;
+use limiter::{ConcurrencyLimiter, Priority};
+use metrics::{Collector, MetricsSink};
+use middleware::{Chain, Middleware};
+use tokio::{fs::File, io::AsyncRead};
+use transport::{BoxError, ReqwestTransport, Transport, TransportRequest, TransportResponse};
+
+mod blob;
+mod host;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod cache;
+#[cfg(feature = "cassette")]
+pub mod cassette;
 pub mod errors;
+pub mod limiter;
+pub mod metrics;
+pub mod middleware;
+pub mod modelfile;
 pub mod models;
+pub mod pool;
+#[cfg(feature = "rag")]
+pub mod rag;
+pub mod router;
+pub mod template;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "tracing")]
+mod trace;
+pub mod transport;
+#[cfg(feature = "vector")]
+pub mod vector;
 
 // Re-exports
 #[cfg(feature = "chrono")]
@@ -32,60 +64,82 @@ macro_rules! streamed_request_wrapper {
     } => {
         $(
             $(#[$attr])*
+            #[cfg_attr(feature = "tracing", tracing::instrument(
+                skip_all,
+                err(Display),
+                fields(endpoint = $pathname, model = trace::TracedRequest::model(request), status, chunks, ttft_ms, prompt_eval_count, eval_count, prompt),
+            ))]
             $($kw)? async fn $func_name<T>(&self, request: &$req_ty, mut on_stream: Option<T>) -> Result<$res_ty, Error>
             where 
                 T: FnMut(&$res_ty)
             {
-                let res = self.client.post(self.host.join($pathname)?)
-                    .json(request)
-                    .send()
-                    .await?;
+                #[cfg(feature = "tracing")]
+                let mut observer = trace::Observer::new();
+                #[cfg(feature = "tracing")]
+                trace::record_request(request, self.trace_prompts);
+
+                let (res, mut collector) = self.send_metered(self.request(Method::POST, $pathname)?.json(request)?).await?;
 
                 if request.stream.unwrap_or(true) {
                     // Handle streamed response
-                    let mut stream = res.bytes_stream();
+                    let mut stream = std::pin::pin!(res.json_lines::<$res_ty>());
 
                     let mut final_res: Option<$res_ty> = None;
 
                     if let Some(ref mut f) = on_stream {
-                        while let Some(result) = stream.next().await {
-                            let bytes = result?;
-
-                            let cur_res = serde_json::from_slice::<$res_ty>(&bytes)?;
+                        while let Some(cur_res) = stream.next().await {
+                            let cur_res = cur_res.inspect_err(|_| collector.fail())?;
+                            collector.observe(&cur_res);
+                            #[cfg(feature = "tracing")]
+                            observer.observe(&cur_res);
                             f(&cur_res);
                             final_res = Some(cur_res);
                         }
                     }
 
                     final_res.ok_or(if on_stream.is_some() { Error::EmptyResponse } else { Error::NoCallback })
+                        .inspect_err(|_| collector.fail())
                 } else {
                     // Handle normal response
-                    Ok(res.json::<$res_ty>().await?)
+                    let res = res.json::<$res_ty>().await.inspect_err(|_| collector.fail())?;
+                    collector.observe(&res);
+                    #[cfg(feature = "tracing")]
+                    observer.observe(&res);
+
+                    Ok(res)
                 }
             }
 
             $(
                 $(#[$attr2])*
+                #[cfg_attr(feature = "tracing", tracing::instrument(
+                    skip_all,
+                    err(Display),
+                    fields(endpoint = $pathname, model = trace::TracedRequest::model(request), status, chunks, ttft_ms, prompt_eval_count, eval_count, prompt),
+                ))]
                 $($kw2)? async fn $streamed_func_name(&self, request: &$req_ty) -> Result<impl Stream<Item = Result<$res_ty, Error>>, Error> {
+                    #[cfg(feature = "tracing")]
+                    let mut observer = trace::Observer::new();
+                    #[cfg(feature = "tracing")]
+                    trace::record_request(request, self.trace_prompts);
+
                     if !request.stream.unwrap_or(true) {
                         return Err(Error::StreamingOff);
                     }
 
-                    let res = self.client.post(self.host.join($pathname)?)
-                        .json(request)
-                        .send()
-                        .await?;
-
-                    Ok(
-                        res.bytes_stream()
-                        .map(|result| {
-                            result.map_err(|err| Error::from(err))
-                                .and_then(|ref bytes| {
-                                    serde_json::from_slice::<$res_ty>(bytes)
-                                        .map_err(|err| Error::from(err))
-                                })
-                        })
-                    )
+                    let (res, mut collector) = self.send_metered(self.request(Method::POST, $pathname)?.json(request)?).await?;
+
+                    let stream = res.json_lines::<$res_ty>()
+                        .inspect(move |res| match res {
+                            Ok(res) => collector.observe(res),
+                            Err(_) => collector.fail(),
+                        });
+                    #[cfg(feature = "tracing")]
+                    let stream = stream.inspect(move |res| if let Ok(res) = res {
+                        observer.observe(res);
+                    });
+
+                    Ok(stream)
                 }
 
             )?
@@ -106,51 +160,68 @@ macro_rules! streamed_request_wrapper {
     } => {
         $(
             $(#[$attr])*
+            #[cfg_attr(feature = "tracing", tracing::instrument(
+                skip_all,
+                err(Display),
+                fields(endpoint = $pathname, model = trace::TracedRequest::model(request), status, chunks, ttft_ms, prompt_eval_count, eval_count, prompt),
+            ))]
             $($kw)? async fn $func_name<T>(&self, request: &$req_ty, mut on_stream: Option<T>) -> Result<$res_ty, Error>
             where 
                 T: FnMut(&$res_ty)
             {
-                let res = self.client.post(self.host.join($pathname)?)
-                    .json(request)
-                    .send()
-                    .await?;
+                #[cfg(feature = "tracing")]
+                let mut observer = trace::Observer::new();
+                #[cfg(feature = "tracing")]
+                trace::record_request(request, self.trace_prompts);
+
+                let (res, mut collector) = self.send_metered(self.request(Method::POST, $pathname)?.json(request)?).await?;
 
                 // Handle streamed response
-                let mut stream = res.bytes_stream();
+                let mut stream = std::pin::pin!(res.json_lines::<$res_ty>());
 
                 let mut final_res: Option<$res_ty> = None;
 
                 if let Some(ref mut f) = on_stream {
-                    while let Some(result) = stream.next().await {
-                        let bytes = result?;
-
-                        let cur_res = serde_json::from_slice::<$res_ty>(&bytes)?;
+                    while let Some(cur_res) = stream.next().await {
+                        let cur_res = cur_res.inspect_err(|_| collector.fail())?;
+                        collector.observe(&cur_res);
+                        #[cfg(feature = "tracing")]
+                        observer.observe(&cur_res);
                         f(&cur_res);
                         final_res = Some(cur_res);
                     }
                 }
 
                 final_res.ok_or(if on_stream.is_some() { Error::EmptyResponse } else { Error::NoCallback })
+                    .inspect_err(|_| collector.fail())
             }
 
             $(
                 $(#[$attr2])*
+                #[cfg_attr(feature = "tracing", tracing::instrument(
+                    skip_all,
+                    err(Display),
+                    fields(endpoint = $pathname, model = trace::TracedRequest::model(request), status, chunks, ttft_ms, prompt_eval_count, eval_count, prompt),
+                ))]
                 $($kw2)? async fn $streamed_func_name(&self, request: &$req_ty) -> Result<impl Stream<Item = Result<$res_ty, Error>>, Error> {
-                    let res = self.client.post(self.host.join($pathname)?)
-                        .json(request)
-                        .send()
-                        .await?;
-
-                    Ok(
-                        res.bytes_stream()
-                        .map(|result| {
-                            result.map_err(|err| Error::from(err))
-                                .and_then(|ref bytes| {
-                                    serde_json::from_slice::<$res_ty>(bytes)
-                                        .map_err(|err| Error::from(err))
-                                })
-                        })
-                    )
+                    #[cfg(feature = "tracing")]
+                    let mut observer = trace::Observer::new();
+                    #[cfg(feature = "tracing")]
+                    trace::record_request(request, self.trace_prompts);
+
+                    let (res, mut collector) = self.send_metered(self.request(Method::POST, $pathname)?.json(request)?).await?;
+
+                    let stream = res.json_lines::<$res_ty>()
+                        .inspect(move |res| match res {
+                            Ok(res) => collector.observe(res),
+                            Err(_) => collector.fail(),
+                        });
+                    #[cfg(feature = "tracing")]
+                    let stream = stream.inspect(move |res| if let Ok(res) = res {
+                        observer.observe(res);
+                    });
+
+                    Ok(stream)
                 }
 
             )?
@@ -193,21 +264,143 @@ macro_rules! streamed_request_wrapper {
 ///
 /// // ...
 /// ```
+///
+/// ### Connect through a Unix domain socket
+///
+/// ```rust
+/// use ollama_rest::Ollama;
+/// use std::str::FromStr;
+///
+/// let ollama = Ollama::from_str("unix:///run/ollama.sock").unwrap();
+///
+/// // ...
+/// ```
 #[derive(Clone)]
 pub struct Ollama {
-    host: Url,
-    client: Client,
+    host: Host,
+    transport: Arc<dyn Transport>,
+    limiter: Option<ConcurrencyLimiter>,
+    priority: Priority,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    middleware: Chain,
+    cache: Option<ResponseCache>,
+    #[cfg(feature = "tracing")]
+    trace_prompts: bool,
 }
 
 impl Ollama {
-    #[must_use]
     pub fn new(host: Url) -> Result<Self, Error> {
-        Ok(Self {
+        let host = Host::from(host);
+        let client = Self::default_client(&host)?;
+
+        Ok(Self::from_parts(host, Arc::new(ReqwestTransport::new(client))))
+    }
+
+    /// Create an instance recording to or replaying from a cassette
+    ///
+    /// ## Parameters
+    /// - `host`: Server to record from; unused when replaying
+    /// - `cassette`: Cassette file and matching rules
+    ///
+    /// ## Returns
+    /// Fails if the cassette is replayed but cannot be read. Recordings are
+    /// written once the instance, its clones and their streams are dropped.
+    #[cfg(feature = "cassette")]
+    pub fn with_cassette(host: Url, cassette: cassette::Cassette) -> Result<Self, Error> {
+        let host = Host::from(host);
+        let transport = cassette::CassetteTransport::new(cassette, ReqwestTransport::new(Self::default_client(&host)?))?;
+
+        Ok(Self::from_parts(host, Arc::new(transport)))
+    }
+
+    /// Create an instance sending requests through a custom transport
+    ///
+    /// ## Parameters
+    /// - `host`: Base URL joined with the endpoint paths (`unix://` hosts use `http://localhost`)
+    /// - `transport`: Backend carrying the requests
+    pub fn with_transport<T: Transport + 'static>(host: Url, transport: T) -> Self {
+        Self::from_parts(host.into(), Arc::new(transport))
+    }
+
+    fn from_parts(host: Host, transport: Arc<dyn Transport>) -> Self {
+        Self {
             host,
-            client: ClientBuilder::new()
-                .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
-                .build()?,
-        })
+            transport,
+            limiter: None,
+            priority: Priority::default(),
+            metrics: None,
+            middleware: Chain::default(),
+            cache: None,
+            #[cfg(feature = "tracing")]
+            trace_prompts: false,
+        }
+    }
+
+    /// Limit concurrent generation, chat and embedding requests
+    ///
+    /// Clones of this instance, and other instances given a clone of the same
+    /// limiter, share its permits.
+    pub fn with_limiter(mut self, limiter: ConcurrencyLimiter) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// Queue priority of the requests sent by this instance (see [`Ollama::with_limiter`])
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn limiter(&self) -> Option<&ConcurrencyLimiter> {
+        self.limiter.as_ref()
+    }
+
+    /// Report the [`metrics::RequestMetrics`] of every call to `sink`
+    ///
+    /// Clones of this instance report to the same sink.
+    pub fn with_metrics<S: MetricsSink + 'static>(mut self, sink: S) -> Self {
+        self.metrics = Some(Arc::new(sink));
+        self
+    }
+
+    /// Answer repeated requests from `cache` (see [`cache`] for what gets cached)
+    ///
+    /// Clones of this instance, and other instances given a clone of the same
+    /// cache, share its entries.
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Add a middleware layer around every call
+    ///
+    /// Layers see requests in the order they were added, and response items
+    /// in reverse order.
+    pub fn with_middleware<M: Middleware + 'static>(mut self, layer: M) -> Self {
+        self.middleware.push(Arc::new(layer));
+        self
+    }
+
+    /// Record prompts in the `prompt` field of generation and chat spans
+    ///
+    /// Off by default, as prompts may hold sensitive content.
+    #[cfg(feature = "tracing")]
+    pub fn with_prompt_tracing(mut self, enabled: bool) -> Self {
+        self.trace_prompts = enabled;
+        self
+    }
+
+    fn default_client(host: &Host) -> Result<Client, Error> {
+        let builder = ClientBuilder::new()
+            .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")));
+
+        let builder = match host.socket()? {
+            #[cfg(unix)]
+            Some(socket) => builder.unix_socket(socket),
+            _ => builder,
+        };
+
+        Ok(builder.build()?)
     }
 
     /// Get host info as a str reference
@@ -215,6 +408,110 @@ impl Ollama {
         self.host.as_str()
     }
 
+    fn request(&self, method: Method, path: &str) -> Result<TransportRequest, Error> {
+        Ok(TransportRequest::new(method, self.host.join(path)?))
+    }
+
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, Error> {
+        let (mut response, mut collector) = self.send_metered(request).await?;
+
+        // Without a decoded response to observe, the call ends with its body
+        if collector.is_active() {
+            response.body = response.body
+                .inspect(move |chunk| if chunk.is_err() {
+                    collector.fail();
+                })
+                .boxed();
+        }
+
+        Ok(response)
+    }
+
+    /// Send a request, along with the collector measuring it
+    async fn send_metered(&self, request: TransportRequest) -> Result<(TransportResponse, Collector), Error> {
+        let request = match self.middleware.is_empty() {
+            true => request,
+            false => self.middleware.request(request)?,
+        };
+
+        let limiter = self.limiter.as_ref()
+            .filter(|_| request.method == Method::POST && limiter::LIMITED_PATHS.contains(&request.url.path()));
+
+        let body = match (limiter, &self.metrics) {
+            (None, None) => None,
+            _ => request.json_body().and_then(|body| serde_json::from_slice::<serde_json::Value>(body).ok()),
+        };
+        let model = body.as_ref().and_then(|body| body["model"].as_str().or(body["name"].as_str()));
+
+        let mut collector = Collector::new(self.metrics.as_ref(), request.url.path(), model);
+
+        let cache_key = match &self.cache {
+            Some(cache) => cache.key_of(&request.method, request.url.path(), request.json_body(), || self.listed_models()).await,
+            None => None,
+        };
+        let cached = match (&self.cache, &cache_key) {
+            (Some(cache), Some(key)) => cache.get(key).await,
+            _ => None,
+        };
+
+        let sent = (!self.middleware.is_empty()).then(|| (request.method.clone(), request.url.clone(), request.headers.clone()));
+
+        let (mut response, permit) = match cached {
+            Some(response) => {
+                collector.cached();
+                (response, None)
+            },
+            None => {
+                let permit = match limiter {
+                    Some(limiter) => {
+                        let queued_at = std::time::Instant::now();
+                        let permit = limiter.acquire(model, self.priority).await.inspect_err(|_| collector.fail())?;
+                        collector.queued(queued_at.elapsed());
+
+                        Some(permit)
+                    },
+                    None => None,
+                };
+
+                let response = self.transport.send(request).await.inspect_err(|_| collector.fail())?;
+                let response = match (&self.cache, cache_key) {
+                    (Some(cache), Some(key)) => cache.store(key, response),
+                    _ => response,
+                };
+
+                (response, permit)
+            },
+        };
+
+        collector.status(response.status);
+        #[cfg(feature = "tracing")]
+        trace::record_status(response.status);
+
+        if let Some((method, url, headers)) = sent {
+            response = self.middleware.response(method, url, headers, response);
+        }
+
+        // Streamed responses hold the permit until fully read
+        if let Some(permit) = permit {
+            response.body = response.body
+                .map(move |chunk| {
+                    let _ = &permit;
+                    chunk
+                })
+                .boxed();
+        }
+
+        Ok((response, collector))
+    }
+
+    /// List models straight through the transport, for the digests of [`cache::ResponseCache`]
+    async fn listed_models(&self) -> Result<ModelListResponse, Error> {
+        self.transport.send(self.request(Method::GET, "/api/tags")?)
+            .await?
+            .json::<ModelListResponse>()
+            .await
+    }
+
     streamed_request_wrapper! {
         #[doc = "Generate completion response for one single prompt (Callback API)"]
         pub fn generate("/api/generate", GenerationRequest) -> GenerationResponse
@@ -242,31 +539,29 @@ impl Ollama {
     ///
     /// It calls `/api/generate` with no prompt, which makes Ollama to load
     /// the model.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(endpoint = "/api/generate", model = model, status)))]
     pub async fn load_model(&self, model: &str) -> Result<GenerationResponse, Error> {
-        Ok(self.client.post(self.host.join("/api/generate")?)
-            .json(&serde_json::json!({ "model": model }))
-            .send()
-            .await?
-            .json::<GenerationResponse>()
-            .await?)
+        let (res, mut collector) = self.send_metered(self.request(Method::POST, "/api/generate")?.json(&serde_json::json!({ "model": model }))?).await?;
+        let res = res.json::<GenerationResponse>().await.inspect_err(|_| collector.fail())?;
+        collector.observe(&res);
+
+        Ok(res)
     }
 
     /// Check if blob exists on the server side (not ollama.com)
     ///
     /// ## Parameters
     /// - `digest`: SHA256 digest of the blob
-    ///     **Be aware**: Currently the digest will be directly appended into the URL
-    ///     without sanitization, please don't expose digest input to end user side.
     ///
     /// ## Returns
     /// - `Ok(())`: Blob exists
     /// - `Err(Error::NotExists)`: Blob not exists
     /// - `Err(_)`: Other error
-    pub async fn blob_exists(&self, digest: &str) -> Result<(), Error> {
-        let status = self.client.head(self.host.join(format!("/api/blobs/sha256:{}", digest).as_str())?)
-            .send()
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(endpoint = "/api/blobs", digest = %digest, status)))]
+    pub async fn blob_exists(&self, digest: &Digest) -> Result<(), Error> {
+        let status = self.send(self.request(Method::HEAD, &format!("/api/blobs/{}", digest.with_prefix(true)))?)
             .await?
-            .status();
+            .status;
 
         match status {
             StatusCode::OK => Ok(()),
@@ -279,19 +574,19 @@ impl Ollama {
     ///
     /// ## Parameters
     /// - `digest`: SHA256 digest of the blob
-    ///     **Be aware**: Currently the digest will be directly appended into the URL
-    ///     without sanitization, please don't expose digest input to end user side.
     /// - `file`: Tokio File instance
     ///
     /// ## Returns
     /// - `Ok(())`: Blob created
     /// - `Err(_)`: Error occurred
-    pub async fn create_blob(&self, digest: &str, file: File) -> Result<(), Error> {
-        let status = self.client.post(self.host.join(format!("/api/blobs/sha256:{}", digest).as_str())?)
-            .body(file)
-            .send()
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(endpoint = "/api/blobs", digest = %digest, status)))]
+    pub async fn create_blob(&self, digest: &Digest, file: File) -> Result<(), Error> {
+        let request = self.request(Method::POST, &format!("/api/blobs/{}", digest.with_prefix(true)))?
+            .stream(blob::reader_stream(file).map_err(BoxError::from));
+
+        let status = self.send(request)
             .await?
-            .status();
+            .status;
 
         if let StatusCode::CREATED = status {
             Ok(())
@@ -300,23 +595,201 @@ impl Ollama {
         }
     }
 
+    /// Upload a blob from a byte stream
+    ///
+    /// The content is hashed while being sent, and checked against `digest`
+    /// once the upload is finished.
+    ///
+    /// ## Parameters
+    /// - `digest`: SHA256 digest of the blob
+    /// - `stream`: Stream of byte chunks
+    /// - `total`: Total size in bytes, if known (only used for progress reporting)
+    /// - `on_progress`: Progress callback
+    ///
+    /// ## Returns
+    /// - `Ok(())`: Blob created
+    /// - `Err(Error::ErrorStatus(_))`: Server rejected the upload
+    /// - `Err(Error::DigestMismatch { .. })`: Server accepted content that does not match `digest`
+    /// - `Err(_)`: Other error
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(endpoint = "/api/blobs", digest = %digest, total = total, status)))]
+    pub async fn upload_blob<S, B, E, F>(&self, digest: &Digest, stream: S, total: Option<u64>, mut on_progress: Option<F>) -> Result<(), Error>
+    where
+        S: Stream<Item = Result<B, E>> + Send + 'static,
+        B: Into<Bytes>,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+        F: FnMut(&BlobUploadProgress),
+    {
+        let hasher = Arc::new(Mutex::new(Sha256::new()));
+        let (progress_tx, mut progress_rx) = futures::channel::mpsc::unbounded::<u64>();
+
+        let body = {
+            let hasher = hasher.clone();
+
+            stream.map(move |chunk| {
+                let chunk: Bytes = chunk.map_err(Into::into)?.into();
+
+                hasher.lock().unwrap().update(&chunk);
+                let _ = progress_tx.unbounded_send(chunk.len() as u64);
+
+                Ok::<_, BoxError>(chunk)
+            })
+        };
+
+        let mut send = Box::pin(self.send(self.request(Method::POST, &format!("/api/blobs/{}", digest.with_prefix(true)))?.stream(body)));
+
+        let mut progress = BlobUploadProgress {
+            digest: *digest,
+            completed: 0,
+            total,
+        };
+
+        let mut report = |sent: u64| {
+            progress.completed += sent;
+            if let Some(ref mut f) = on_progress {
+                f(&progress);
+            }
+        };
+
+        let res = loop {
+            match future::select(send, progress_rx.next()).await {
+                Either::Left((res, _)) => break res,
+                Either::Right((Some(sent), pending)) => {
+                    report(sent);
+                    send = pending;
+                }
+                Either::Right((None, pending)) => break pending.await,
+            }
+        };
+
+        progress_rx.close();
+        while let Ok(sent) = progress_rx.try_recv() {
+            report(sent);
+        }
+
+        let status = res?.status;
+        if status != StatusCode::CREATED {
+            return Err(Error::ErrorStatus(status));
+        }
+
+        let hasher = std::mem::take(&mut *hasher.lock().unwrap());
+        let actual = Digest::from_hasher(hasher);
+        if actual != *digest {
+            return Err(Error::DigestMismatch { expected: *digest, actual });
+        }
+
+        Ok(())
+    }
+
+    /// Upload a blob from an async reader
+    ///
+    /// See [`Ollama::upload_blob()`] for details.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(digest = %digest, total = total)))]
+    pub async fn upload_blob_from_reader<R, F>(&self, digest: &Digest, reader: R, total: Option<u64>, on_progress: Option<F>) -> Result<(), Error>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        F: FnMut(&BlobUploadProgress),
+    {
+        self.upload_blob(digest, blob::reader_stream(reader), total, on_progress).await
+    }
+
+    /// Upload a file as a blob
+    ///
+    /// The SHA256 digest of the file is computed first. If the server already
+    /// has the blob, the upload is skipped.
+    ///
+    /// ## Returns
+    /// - `Ok(digest)`: Blob exists on the server, with its digest
+    /// - `Err(_)`: Error occurred
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(digest)))]
+    pub async fn upload_blob_from_path<P, F>(&self, path: P, on_progress: Option<F>) -> Result<Digest, Error>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&BlobUploadProgress),
+    {
+        let path = path.as_ref();
+        let digest = Digest::from_reader(File::open(path).await?).await?;
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("digest", tracing::field::display(&digest));
+
+        match self.blob_exists(&digest).await {
+            Ok(()) => return Ok(digest),
+            Err(Error::NotExists) => {}
+            Err(err) => return Err(err),
+        }
+
+        let file = File::open(path).await?;
+        let total = file.metadata().await?.len();
+
+        self.upload_blob_from_reader(&digest, file, Some(total), on_progress).await?;
+
+        Ok(digest)
+    }
+
+    /// Create a model from a GGUF file
+    ///
+    /// The file is hashed and uploaded as a blob (skipped if the server already
+    /// has it), then `/api/create` is called with the blob as the model file.
+    ///
+    /// ## Parameters
+    /// - `model`: Name of the model to create
+    /// - `path`: Path to the GGUF file
+    /// - `on_progress`: Progress callback, called for upload progress and creation status
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(endpoint = "/api/create", model = model, status)))]
+    pub async fn create_model_from_gguf<P, F>(&self, model: &str, path: P, mut on_progress: Option<F>) -> Result<Status, Error>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&CreationProgress),
+    {
+        let path = path.as_ref();
+        let file_name = path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "model.gguf".to_string());
+
+        let digest = self.upload_blob_from_path(path, on_progress.as_mut().map(|f| {
+            |progress: &BlobUploadProgress| f(&CreationProgress::Upload(progress.clone()))
+        })).await?;
+
+        let request = CreationRequest {
+            model: Some(model.to_string()),
+            files: Some(BTreeMap::from([(file_name, digest)])),
+            stream: Some(on_progress.is_some()),
+            ..Default::default()
+        };
+
+        self.create(&request, on_progress.as_mut().map(|f| {
+            |status: &Status| f(&CreationProgress::Status(status.clone()))
+        })).await
+    }
+
     /// List local models
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(endpoint = "/api/tags", status)))]
     pub async fn local_models(&self) -> Result<ModelListResponse, Error> {
-        Ok(self.client.get(self.host.join("/api/tags")?)
-            .send()
+        self.send(self.request(Method::GET, "/api/tags")?)
             .await?
             .json::<ModelListResponse>()
-            .await?)
+            .await
     }
 
     /// Show model information
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(endpoint = "/api/show", model = %request.name, status)))]
     pub async fn model(&self, request: &ModelShowRequest) -> Result<ModelShowResponse, Error> {
-        Ok(self.client.post(self.host.join("/api/show")?)
-            .json(request)
-            .send()
+        self.send(self.request(Method::POST, "/api/show")?.json(request)?)
             .await?
             .json::<ModelShowResponse>()
-            .await?)
+            .await
+    }
+
+    /// Get model capabilities
+    ///
+    /// Empty for servers not reporting capabilities (older than 0.6.4).
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(endpoint = "/api/show", model = name, status)))]
+    pub async fn model_capabilities(&self, name: &str) -> Result<Vec<Capability>, Error> {
+        let res = self.model(&ModelShowRequest {
+            name: name.to_string(),
+            verbose: None,
+        }).await?;
+
+        Ok(res.capabilities.unwrap_or_default())
     }
 
     /// Copy a model
@@ -325,12 +798,11 @@ impl Ollama {
     /// - `Ok(())`: Model copied
     /// - `Err(Error::NotExists)`: Source model not exists
     /// - `Err(_)`: Other error
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(endpoint = "/api/copy", model = %request.source, destination = %request.destination, status)))]
     pub async fn copy_model(&self, request: &ModelCopyRequest) -> Result<(), Error> {
-        let status = self.client.post(self.host.join("/api/copy")?)
-            .json(request)
-            .send()
+        let status = self.send(self.request(Method::POST, "/api/copy")?.json(request)?)
             .await?
-            .status();
+            .status;
 
         match status {
             StatusCode::OK => Ok(()),
@@ -345,12 +817,11 @@ impl Ollama {
     /// - `Ok(())`: Model deleted
     /// - `Err(Error::NotExists)`: Target model not exists
     /// - `Err(_)`: Other error
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(endpoint = "/api/delete", model = %request.name, status)))]
     pub async fn delete_model(&self, request: &ModelDeletionRequest) -> Result<(), Error> {
-        let status = self.client.delete(self.host.join("/api/delete")?)
-            .json(request)
-            .send()
+        let status = self.send(self.request(Method::DELETE, "/api/delete")?.json(request)?)
             .await?
-            .status();
+            .status;
 
         match status {
             StatusCode::OK => Ok(()),
@@ -378,30 +849,66 @@ impl Ollama {
     }
 
     /// Generate embeddings
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(endpoint = "/api/embeddings", model = %request.model, status)))]
     pub async fn generate_embeddings(&self, request: &EmbeddingGenerationRequest) -> Result<EmbeddingGenerationResponse, Error> {
-        Ok(self.client.post(self.host.join("/api/embeddings")?)
-            .json(request)
-            .send()
+        self.send(self.request(Method::POST, "/api/embeddings")?.json(request)?)
             .await?
             .json::<EmbeddingGenerationResponse>()
-            .await?)
+            .await
+    }
+
+    /// Generate embeddings for one or more inputs
+    ///
+    /// Requires Ollama 0.3.4 or later (see [`ServerFeatures`]); fall back to
+    /// [`Ollama::generate_embeddings()`] for older servers.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(endpoint = "/api/embed", model = %request.model, status, prompt_eval_count)))]
+    pub async fn embed(&self, request: &EmbedRequest) -> Result<EmbedResponse, Error> {
+        let (res, mut collector) = self.send_metered(self.request(Method::POST, "/api/embed")?.json(request)?).await?;
+        let res = res.json::<EmbedResponse>().await.inspect_err(|_| collector.fail())?;
+        collector.observe(&res);
+        #[cfg(feature = "tracing")]
+        trace::record_response(&res);
+
+        Ok(res)
     }
 
     /// List running models
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(endpoint = "/api/ps", status)))]
     pub async fn running_models(&self) -> Result<RunningModelResponse, Error> {
-        Ok(self.client.get(self.host.join("/api/ps")?)
-            .send()
+        self.send(self.request(Method::GET, "/api/ps")?)
             .await?
             .json::<RunningModelResponse>()
-            .await?)
+            .await
     }
 
+    /// Get server version
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(endpoint = "/api/version", status)))]
     pub async fn version(&self) -> Result<VersionResponse, Error> {
-        Ok(self.client.get(self.host.join("/api/version")?)
-            .send()
+        self.send(self.request(Method::GET, "/api/version")?)
             .await?
             .json::<VersionResponse>()
-            .await?)
+            .await
+    }
+
+    /// Get features supported by the server, based on its version
+    ///
+    /// ## Examples
+    ///
+    /// ```rust,no_run
+    /// use ollama_rest::{models::version::ServerFeature, Ollama};
+    ///
+    /// # async fn run() -> Result<(), ollama_rest::errors::Error> {
+    /// let ollama = Ollama::default();
+    /// let features = ollama.server_features().await?;
+    ///
+    /// // Fail fast instead of sending tools to a server that ignores them
+    /// features.require(ServerFeature::Tools)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, err(Display), fields(endpoint = "/api/version", status)))]
+    pub async fn server_features(&self) -> Result<ServerFeatures, Error> {
+        Ok(self.version().await?.into())
     }
 }
 
@@ -0,0 +1,409 @@
+//! Client-side concurrency limiting
+//!
+//! Ollama runs a limited number of requests in parallel (`OLLAMA_NUM_PARALLEL`)
+//! and queues the rest server-side, where they eat into client timeouts. A
+//! [`ConcurrencyLimiter`] attached with [`crate::Ollama::with_limiter`] keeps
+//! the excess on the client instead: generation, chat and embedding requests
+//! wait for a permit in a bounded priority queue, and are rejected with
+//! [`Error::QueueFull`] once the queue is full.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use ollama_rest::{limiter::{ConcurrencyLimiter, Priority}, Ollama};
+//!
+//! let limiter = ConcurrencyLimiter::new(4)
+//!     .with_model_limit("llama3.1:70b", 1)
+//!     .with_max_queued(32);
+//!
+//! let ollama = Ollama::default().with_limiter(limiter.clone());
+//! let urgent = ollama.clone().with_priority(Priority::High);
+//!
+//! // ...
+//!
+//! println!("mean queue time: {:?}", limiter.stats().mean_queue_time());
+//! ```
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt::Display,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::channel::oneshot;
+
+use crate::{errors::Error, models::errors::ParsingError, pool::canonical_model};
+
+/// Endpoints subject to the limits; everything else is sent right away
+pub(crate) const LIMITED_PATHS: [&str; 4] = ["/api/generate", "/api/chat", "/api/embed", "/api/embeddings"];
+
+/// Queue priority of a request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl Priority {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Low => "low",
+            Self::Normal => "normal",
+            Self::High => "high",
+        }
+    }
+}
+
+impl AsRef<str> for Priority {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Priority {
+    type Err = ParsingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(Self::Low),
+            "normal" => Ok(Self::Normal),
+            "high" => Ok(Self::High),
+            _ => Err(ParsingError::InvalidStr),
+        }
+    }
+}
+
+/// Counters of a [`ConcurrencyLimiter`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LimiterStats {
+    /// Requests holding a permit
+    pub running: usize,
+    /// Requests waiting for a permit
+    pub queued: usize,
+    /// Requests given a permit, right away or after queueing
+    pub admitted: u64,
+    /// Requests rejected because the queue was full
+    pub rejected: u64,
+    /// Requests that had to queue before being admitted
+    pub waited: u64,
+    /// Time spent in the queue by the `waited` requests
+    pub total_queue_time: Duration,
+    pub max_queue_time: Duration,
+}
+
+impl LimiterStats {
+    /// Mean queue time over every admitted request
+    pub fn mean_queue_time(&self) -> Duration {
+        match self.admitted {
+            0 => Duration::ZERO,
+            admitted => self.total_queue_time.div_f64(admitted as f64),
+        }
+    }
+}
+
+struct Waiter {
+    priority: Priority,
+    seq: u64,
+    model: Option<String>,
+    enqueued_at: Instant,
+    grant: oneshot::Sender<Permit>,
+}
+
+struct State {
+    /// Per-model limits, by canonical model name
+    model_limits: HashMap<String, usize>,
+    max_queued: usize,
+    running: usize,
+    running_per_model: HashMap<String, usize>,
+    queue: Vec<Waiter>,
+    next_seq: u64,
+    stats: LimiterStats,
+}
+
+struct Inner {
+    max_concurrent: usize,
+    state: Mutex<State>,
+}
+
+impl Inner {
+    fn can_run(&self, state: &State, model: Option<&str>) -> bool {
+        let model_ok = match model.and_then(|model| Some((model, state.model_limits.get(model)?))) {
+            Some((model, limit)) => state.running_per_model.get(model).copied().unwrap_or(0) < *limit,
+            None => true,
+        };
+
+        state.running < self.max_concurrent && model_ok
+    }
+
+    fn start(self: &Arc<Self>, state: &mut State, model: Option<String>) -> Permit {
+        state.running += 1;
+        if let Some(model) = &model {
+            *state.running_per_model.entry(model.clone()).or_default() += 1;
+        }
+        state.stats.admitted += 1;
+
+        Permit { limiter: self.clone(), model }
+    }
+
+    /// Hand permits to the waiters able to run, highest priority first
+    fn grant(self: &Arc<Self>) {
+        let granted = {
+            let mut state = self.state.lock().unwrap();
+            let mut granted = Vec::new();
+
+            state.queue.retain(|waiter| !waiter.grant.is_canceled());
+            state.queue.sort_by_key(|waiter| (Reverse(waiter.priority), waiter.seq));
+
+            let mut i = 0;
+            while i < state.queue.len() {
+                if !self.can_run(&state, state.queue[i].model.as_deref()) {
+                    i += 1;
+                    continue;
+                }
+
+                let waiter = state.queue.remove(i);
+                let waited = waiter.enqueued_at.elapsed();
+
+                state.stats.waited += 1;
+                state.stats.total_queue_time += waited;
+                state.stats.max_queue_time = state.stats.max_queue_time.max(waited);
+
+                let permit = self.start(&mut state, waiter.model);
+                granted.push((waiter.grant, permit));
+            }
+
+            granted
+        };
+
+        // Outside the lock: a waiter gone in the meantime drops its permit, which grants again
+        for (grant, permit) in granted {
+            let _ = grant.send(permit);
+        }
+    }
+}
+
+/// Permit to run one request, released on drop
+pub(crate) struct Permit {
+    limiter: Arc<Inner>,
+    model: Option<String>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        {
+            let mut state = self.limiter.state.lock().unwrap();
+            state.running -= 1;
+
+            if let Some(model) = &self.model {
+                if let Some(running) = state.running_per_model.get_mut(model) {
+                    *running -= 1;
+                    if *running == 0 {
+                        state.running_per_model.remove(model);
+                    }
+                }
+            }
+        }
+
+        self.limiter.grant();
+    }
+}
+
+/// Semaphore-like limiter with per-model limits and a bounded priority queue
+///
+/// Clones share the same permits, limits and counters. Model names are
+/// compared in their canonical form, so `llama3.1` and `llama3.1:latest`
+/// share one limit.
+#[derive(Clone)]
+pub struct ConcurrencyLimiter {
+    inner: Arc<Inner>,
+}
+
+impl ConcurrencyLimiter {
+    /// Limiter running at most `max_concurrent` requests at once, queueing up to 256 more
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                max_concurrent: max_concurrent.max(1),
+                state: Mutex::new(State {
+                    model_limits: HashMap::new(),
+                    max_queued: 256,
+                    running: 0,
+                    running_per_model: HashMap::new(),
+                    queue: Vec::new(),
+                    next_seq: 0,
+                    stats: LimiterStats::default(),
+                }),
+            }),
+        }
+    }
+
+    /// Run at most `limit` requests for `model` at once, within the overall limit
+    ///
+    /// Applies to every clone of the limiter.
+    pub fn with_model_limit<S: AsRef<str>>(self, model: S, limit: usize) -> Self {
+        self.inner.state.lock().unwrap().model_limits.insert(canonical_model(model.as_ref()), limit.max(1));
+
+        // A raised limit may let queued requests run
+        self.inner.grant();
+        self
+    }
+
+    /// Maximum number of waiting requests before rejecting with [`Error::QueueFull`]
+    ///
+    /// Applies to every clone of the limiter.
+    pub fn with_max_queued(self, max_queued: usize) -> Self {
+        self.inner.state.lock().unwrap().max_queued = max_queued;
+        self
+    }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.inner.max_concurrent
+    }
+
+    /// Current counters, including queue times
+    pub fn stats(&self) -> LimiterStats {
+        let state = self.inner.state.lock().unwrap();
+
+        LimiterStats {
+            running: state.running,
+            queued: state.queue.iter().filter(|waiter| !waiter.grant.is_canceled()).count(),
+            ..state.stats.clone()
+        }
+    }
+
+    /// Wait for a permit
+    ///
+    /// ## Returns
+    /// [`Error::QueueFull`] if the request can neither run nor queue
+    pub(crate) async fn acquire(&self, model: Option<&str>, priority: Priority) -> Result<Permit, Error> {
+        let model = model.map(canonical_model);
+
+        let granted = {
+            let mut state = self.inner.state.lock().unwrap();
+            state.queue.retain(|waiter| !waiter.grant.is_canceled());
+
+            if self.inner.can_run(&state, model.as_deref()) {
+                return Ok(self.inner.start(&mut state, model));
+            }
+
+            if state.queue.len() >= state.max_queued {
+                state.stats.rejected += 1;
+                return Err(Error::QueueFull);
+            }
+
+            let (grant, granted) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+
+            state.queue.push(Waiter {
+                priority,
+                seq,
+                model,
+                enqueued_at: Instant::now(),
+                grant,
+            });
+
+            granted
+        };
+
+        // The sender lives in the queue, which only drops it after sending
+        granted.await.map_err(|_| Error::QueueFull)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn priority_order() {
+        let limiter = ConcurrencyLimiter::new(1);
+        let running = limiter.acquire(None, Priority::Normal).await.unwrap();
+
+        let mut low = Box::pin(limiter.acquire(None, Priority::Low));
+        let mut high = Box::pin(limiter.acquire(None, Priority::High));
+        assert!((&mut low).now_or_never().is_none());
+        assert!((&mut high).now_or_never().is_none());
+        assert_eq!(limiter.stats().queued, 2);
+
+        drop(running);
+        let high = high.await.unwrap();
+        assert!((&mut low).now_or_never().is_none());
+
+        drop(high);
+        drop(low.await.unwrap());
+
+        let stats = limiter.stats();
+        assert_eq!((stats.running, stats.queued, stats.admitted, stats.waited), (0, 0, 3, 2));
+    }
+
+    #[tokio::test]
+    async fn model_limits_and_full_queue() {
+        let limiter = ConcurrencyLimiter::new(3)
+            .with_model_limit("big", 1)
+            .with_max_queued(1);
+
+        let big = limiter.acquire(Some("big"), Priority::Normal).await.unwrap();
+
+        // Other models are not held up by the per-model limit
+        let small = limiter.acquire(Some("small"), Priority::Normal).await.unwrap();
+
+        let mut waiting = Box::pin(limiter.acquire(Some("big"), Priority::Normal));
+        assert!((&mut waiting).now_or_never().is_none());
+        assert!(matches!(limiter.acquire(Some("big"), Priority::High).await, Err(Error::QueueFull)));
+
+        drop(small);
+        assert!((&mut waiting).now_or_never().is_none());
+
+        drop(big);
+        waiting.await.unwrap();
+        assert_eq!(limiter.stats().rejected, 1);
+    }
+
+    #[tokio::test]
+    async fn configure_shared_limiters() {
+        let limiter = ConcurrencyLimiter::new(2);
+        let shared = limiter.clone().with_model_limit("llama3.1", 1);
+
+        // Both spellings of the model count against the same limit, on every clone
+        let running = limiter.acquire(Some("llama3.1:latest"), Priority::Normal).await.unwrap();
+        let mut waiting = Box::pin(shared.acquire(Some("llama3.1"), Priority::Normal));
+        assert!((&mut waiting).now_or_never().is_none());
+
+        // Raising the limit lets the waiter run
+        let _limiter = limiter.with_model_limit("llama3.1:latest", 2);
+        drop(waiting.await.unwrap());
+        drop(running);
+    }
+
+    #[tokio::test]
+    async fn cancelled_waiters_free_their_slot() {
+        let limiter = ConcurrencyLimiter::new(1).with_max_queued(1);
+        let running = limiter.acquire(None, Priority::Normal).await.unwrap();
+
+        let mut waiting = Box::pin(limiter.acquire(None, Priority::Normal));
+        assert!((&mut waiting).now_or_never().is_none());
+        drop(waiting);
+
+        let mut waiting = Box::pin(limiter.acquire(None, Priority::Normal));
+        assert!((&mut waiting).now_or_never().is_none());
+
+        drop(running);
+        drop(waiting.await.unwrap());
+        assert_eq!(limiter.stats().running, 0);
+    }
+}
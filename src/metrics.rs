@@ -0,0 +1,290 @@
+//! Request metrics
+//!
+//! An [`Ollama`](crate::Ollama) instance given a [`MetricsSink`] with
+//! [`Ollama::with_metrics`](crate::Ollama::with_metrics) reports every call
+//! once it completes: endpoint, model, outcome, duration, time spent in the
+//! client-side queue and, for generations, chats and embeddings, the token
+//! counts and durations reported by the server.
+//!
+//! Adapters are provided for the [`metrics`](https://docs.rs/metrics) crate
+//! (feature `metrics`, see [`facade::FacadeSink`]) and for OpenTelemetry
+//! (feature `opentelemetry`, see [`otel::OpenTelemetrySink`]).
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use ollama_rest::{metrics::{MetricsSink, RequestMetrics}, Ollama};
+//!
+//! struct Log;
+//!
+//! impl MetricsSink for Log {
+//!     fn record(&self, metrics: &RequestMetrics) {
+//!         println!("{} {:?}: {:?} tokens/s", metrics.endpoint, metrics.model, metrics.tokens_per_second());
+//!     }
+//! }
+//!
+//! let ollama = Ollama::default().with_metrics(Log);
+//!
+//! // ...
+//! ```
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use reqwest::StatusCode;
+
+use crate::models::{
+    chat::ChatResponse,
+    create::CreationProgress,
+    embeddings::{EmbedResponse, EmbeddingGenerationResponse},
+    generate::GenerationResponse,
+    model::{ModelPullStatus, ModelPushStatus},
+    stats::{self, GenerationStats},
+    Status,
+};
+
+#[cfg(feature = "metrics")]
+pub mod facade;
+#[cfg(feature = "opentelemetry")]
+pub mod otel;
+
+/// Measurements of one completed call
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestMetrics {
+    /// Endpoint path, e.g. `/api/generate`
+    pub endpoint: String,
+    pub model: Option<String>,
+    /// HTTP status, if a response was received
+    pub status: Option<u16>,
+    /// Whether the call failed, including HTTP error statuses and broken streams
+    pub failed: bool,
+    /// Whether the response came from a [`ResponseCache`](crate::cache::ResponseCache)
+    pub cached: bool,
+    /// Time from the call to its last response chunk
+    pub duration: Duration,
+    /// Time spent waiting for a [`ConcurrencyLimiter`](crate::limiter::ConcurrencyLimiter) permit
+    pub queue_time: Option<Duration>,
+    /// Time from the call to the first chunk carrying generated content
+    pub time_to_first_token: Option<Duration>,
+    /// Time the server spent loading the model
+    pub load_duration: Option<Duration>,
+    pub prompt_eval_count: Option<u64>,
+    pub prompt_eval_duration: Option<Duration>,
+    pub eval_count: Option<u64>,
+    pub eval_duration: Option<Duration>,
+}
+
+impl RequestMetrics {
+    /// Generated tokens per second, as measured by the server
+    pub fn tokens_per_second(&self) -> Option<f64> {
+        stats::rate(self.eval_count? as usize, self.eval_duration?)
+    }
+
+    /// Prompt tokens evaluated per second, as measured by the server
+    pub fn prompt_tokens_per_second(&self) -> Option<f64> {
+        stats::rate(self.prompt_eval_count? as usize, self.prompt_eval_duration?)
+    }
+}
+
+/// Destination of the [`RequestMetrics`] of every call
+pub trait MetricsSink: Send + Sync {
+    fn record(&self, metrics: &RequestMetrics);
+}
+
+impl<T: MetricsSink + ?Sized> MetricsSink for Arc<T> {
+    fn record(&self, metrics: &RequestMetrics) {
+        (**self).record(metrics)
+    }
+}
+
+/// Response fields worth measuring
+pub(crate) trait MeteredResponse {
+    /// Whether this chunk carries generated content, for the time to first token
+    fn has_token(&self) -> bool {
+        false
+    }
+
+    fn record(&self, _metrics: &mut RequestMetrics) {}
+}
+
+/// Fill in the stats reported in the final chunk
+fn record_stats(stats: &GenerationStats, metrics: &mut RequestMetrics) {
+    metrics.load_duration = stats.load_duration().or(metrics.load_duration);
+    metrics.prompt_eval_count = stats.prompt_eval_count.map(|count| count as u64).or(metrics.prompt_eval_count);
+    metrics.prompt_eval_duration = stats.prompt_eval_duration().or(metrics.prompt_eval_duration);
+    metrics.eval_count = stats.eval_count.map(|count| count as u64).or(metrics.eval_count);
+    metrics.eval_duration = stats.eval_duration().or(metrics.eval_duration);
+}
+
+impl MeteredResponse for GenerationResponse {
+    fn has_token(&self) -> bool {
+        !self.response.is_empty()
+    }
+
+    fn record(&self, metrics: &mut RequestMetrics) {
+        record_stats(&self.stats, metrics);
+    }
+}
+
+impl MeteredResponse for ChatResponse {
+    fn has_token(&self) -> bool {
+        self.message.as_ref().is_some_and(|message| {
+            !message.content.is_empty()
+                || message.thinking.as_ref().is_some_and(|thinking| !thinking.is_empty())
+                || message.tool_calls.as_ref().is_some_and(|calls| !calls.is_empty())
+        })
+    }
+
+    fn record(&self, metrics: &mut RequestMetrics) {
+        record_stats(&self.stats, metrics);
+    }
+}
+
+impl MeteredResponse for EmbedResponse {
+    fn record(&self, metrics: &mut RequestMetrics) {
+        record_stats(&self.stats, metrics);
+    }
+}
+
+impl MeteredResponse for EmbeddingGenerationResponse {}
+impl MeteredResponse for Status {}
+impl MeteredResponse for CreationProgress {}
+impl MeteredResponse for ModelPullStatus {}
+impl MeteredResponse for ModelPushStatus {}
+
+/// Endpoint of a request path, without path parameters
+fn endpoint(path: &str) -> &str {
+    match path.find("/api/blobs/") {
+        Some(pos) => &path[..pos + "/api/blobs".len()],
+        None => path,
+    }
+}
+
+/// Measures one call, reporting to the sink when dropped
+///
+/// Does nothing without a sink.
+pub(crate) struct Collector {
+    sink: Option<Arc<dyn MetricsSink>>,
+    started: Instant,
+    finished: Option<Duration>,
+    metrics: RequestMetrics,
+}
+
+impl Collector {
+    /// Collector of a call to `path`, timing from now
+    pub fn new(sink: Option<&Arc<dyn MetricsSink>>, path: &str, model: Option<&str>) -> Self {
+        let metrics = match sink {
+            Some(_) => RequestMetrics {
+                endpoint: endpoint(path).to_string(),
+                model: model.map(str::to_string),
+                ..Default::default()
+            },
+            None => RequestMetrics::default(),
+        };
+
+        Self {
+            sink: sink.cloned(),
+            started: Instant::now(),
+            finished: None,
+            metrics,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    pub fn queued(&mut self, time: Duration) {
+        self.metrics.queue_time = Some(time);
+    }
+
+    pub fn status(&mut self, status: StatusCode) {
+        self.metrics.status = Some(status.as_u16());
+        if status.is_client_error() || status.is_server_error() {
+            self.metrics.failed = true;
+        }
+    }
+
+    pub fn cached(&mut self) {
+        self.metrics.cached = true;
+    }
+
+    pub fn fail(&mut self) {
+        self.metrics.failed = true;
+    }
+
+    pub fn observe<R: MeteredResponse>(&mut self, response: &R) {
+        if !self.is_active() {
+            return;
+        }
+
+        let elapsed = self.started.elapsed();
+        self.finished = Some(elapsed);
+
+        if self.metrics.time_to_first_token.is_none() && response.has_token() {
+            self.metrics.time_to_first_token = Some(elapsed);
+        }
+
+        response.record(&mut self.metrics);
+    }
+}
+
+impl Drop for Collector {
+    fn drop(&mut self) {
+        if let Some(sink) = self.sink.take() {
+            self.metrics.duration = self.finished.unwrap_or_else(|| self.started.elapsed());
+            sink.record(&self.metrics);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Collected(Mutex<Vec<RequestMetrics>>);
+
+    impl MetricsSink for Collected {
+        fn record(&self, metrics: &RequestMetrics) {
+            self.0.lock().unwrap().push(metrics.clone());
+        }
+    }
+
+    #[test]
+    fn blob_endpoints() {
+        assert_eq!(endpoint("/api/blobs/sha256:0123"), "/api/blobs");
+        assert_eq!(endpoint("/api/generate"), "/api/generate");
+    }
+
+    #[test]
+    fn collect_on_drop() {
+        let collected = Arc::new(Collected::default());
+        let sink: Arc<dyn MetricsSink> = collected.clone();
+
+        let mut collector = Collector::new(Some(&sink), "/api/generate", Some("llama3.2:1b"));
+        collector.status(StatusCode::OK);
+        collector.observe(&serde_json::from_value::<GenerationResponse>(serde_json::json!({
+            "model": "llama3.2:1b",
+            "created_at": "2024-01-01T00:00:00Z",
+            "response": "",
+            "done": true,
+            "eval_count": 10,
+            "eval_duration": 2_000_000_000u64,
+        })).unwrap());
+        assert!(collected.0.lock().unwrap().is_empty());
+
+        drop(collector);
+
+        let collected = collected.0.lock().unwrap();
+        assert_eq!(collected.len(), 1);
+        assert_eq!(collected[0].model.as_deref(), Some("llama3.2:1b"));
+        assert_eq!(collected[0].tokens_per_second(), Some(5.0));
+        assert_eq!(collected[0].time_to_first_token, None);
+        assert!(!collected[0].failed);
+    }
+}
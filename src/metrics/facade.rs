@@ -0,0 +1,85 @@
+//! [`metrics`](https://docs.rs/metrics) crate adapter
+//!
+//! Records to the globally installed `metrics` recorder, e.g. the
+//! `metrics-exporter-prometheus` one:
+//!
+//! | Metric                                | Kind      | Unit    |
+//! | ------------------------------------- | --------- | ------- |
+//! | `ollama_requests_total`               | counter   |         |
+//! | `ollama_request_errors_total`         | counter   |         |
+//! | `ollama_request_duration_seconds`     | histogram | seconds |
+//! | `ollama_queue_duration_seconds`       | histogram | seconds |
+//! | `ollama_time_to_first_token_seconds`  | histogram | seconds |
+//! | `ollama_load_duration_seconds`        | histogram | seconds |
+//! | `ollama_prompt_tokens_total`          | counter   |         |
+//! | `ollama_completion_tokens_total`      | counter   |         |
+//! | `ollama_tokens_per_second`            | histogram |         |
+//!
+//! Every metric is labelled with `endpoint`, and with `model` when the call
+//! is for a model.
+
+use ::metrics::{counter, describe_counter, describe_histogram, histogram, Label, Unit};
+
+use super::{MetricsSink, RequestMetrics};
+
+/// Sink recording to the `metrics` facade
+#[derive(Debug, Clone, Copy)]
+pub struct FacadeSink;
+
+impl FacadeSink {
+    /// Sink with the metric descriptions registered on the current recorder
+    pub fn new() -> Self {
+        describe_counter!("ollama_requests_total", "Completed Ollama calls");
+        describe_counter!("ollama_request_errors_total", "Failed Ollama calls");
+        describe_histogram!("ollama_request_duration_seconds", Unit::Seconds, "Duration of Ollama calls");
+        describe_histogram!("ollama_queue_duration_seconds", Unit::Seconds, "Time spent in the client-side queue");
+        describe_histogram!("ollama_time_to_first_token_seconds", Unit::Seconds, "Time to the first generated token");
+        describe_histogram!("ollama_load_duration_seconds", Unit::Seconds, "Time the server spent loading the model");
+        describe_counter!("ollama_prompt_tokens_total", "Evaluated prompt tokens");
+        describe_counter!("ollama_completion_tokens_total", "Generated tokens");
+        describe_histogram!("ollama_tokens_per_second", "Generation speed reported by the server");
+
+        Self
+    }
+}
+
+impl Default for FacadeSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetricsSink for FacadeSink {
+    fn record(&self, metrics: &RequestMetrics) {
+        let mut labels = vec![Label::new("endpoint", metrics.endpoint.clone())];
+        if let Some(model) = &metrics.model {
+            labels.push(Label::new("model", model.clone()));
+        }
+
+        counter!("ollama_requests_total", labels.iter()).increment(1);
+        if metrics.failed {
+            counter!("ollama_request_errors_total", labels.iter()).increment(1);
+        }
+
+        histogram!("ollama_request_duration_seconds", labels.iter()).record(metrics.duration);
+
+        if let Some(time) = metrics.queue_time {
+            histogram!("ollama_queue_duration_seconds", labels.iter()).record(time);
+        }
+        if let Some(time) = metrics.time_to_first_token {
+            histogram!("ollama_time_to_first_token_seconds", labels.iter()).record(time);
+        }
+        if let Some(time) = metrics.load_duration {
+            histogram!("ollama_load_duration_seconds", labels.iter()).record(time);
+        }
+        if let Some(count) = metrics.prompt_eval_count {
+            counter!("ollama_prompt_tokens_total", labels.iter()).increment(count);
+        }
+        if let Some(count) = metrics.eval_count {
+            counter!("ollama_completion_tokens_total", labels.iter()).increment(count);
+        }
+        if let Some(speed) = metrics.tokens_per_second() {
+            histogram!("ollama_tokens_per_second", labels.iter()).record(speed);
+        }
+    }
+}
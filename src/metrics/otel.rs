@@ -0,0 +1,124 @@
+//! OpenTelemetry adapter
+//!
+//! | Instrument                    | Kind      | Unit        |
+//! | ----------------------------- | --------- | ----------- |
+//! | `ollama.requests`             | counter   | `{request}` |
+//! | `ollama.request.errors`       | counter   | `{request}` |
+//! | `ollama.request.duration`     | histogram | `s`         |
+//! | `ollama.queue.duration`       | histogram | `s`         |
+//! | `ollama.time_to_first_token`  | histogram | `s`         |
+//! | `ollama.load.duration`        | histogram | `s`         |
+//! | `ollama.tokens.prompt`        | counter   | `{token}`   |
+//! | `ollama.tokens.completion`    | counter   | `{token}`   |
+//! | `ollama.tokens.rate`          | histogram | `{token}/s` |
+//!
+//! Every instrument is given the `endpoint` attribute, and `model` when the
+//! call is for a model.
+
+use opentelemetry::{
+    global,
+    metrics::{Counter, Histogram, Meter},
+    KeyValue,
+};
+
+use super::{MetricsSink, RequestMetrics};
+
+/// Sink recording to OpenTelemetry instruments
+#[derive(Debug, Clone)]
+pub struct OpenTelemetrySink {
+    requests: Counter<u64>,
+    errors: Counter<u64>,
+    duration: Histogram<f64>,
+    queue_time: Histogram<f64>,
+    time_to_first_token: Histogram<f64>,
+    load_duration: Histogram<f64>,
+    prompt_tokens: Counter<u64>,
+    completion_tokens: Counter<u64>,
+    tokens_per_second: Histogram<f64>,
+}
+
+impl OpenTelemetrySink {
+    /// Sink creating its instruments with `meter`
+    pub fn new(meter: &Meter) -> Self {
+        Self {
+            requests: meter.u64_counter("ollama.requests")
+                .with_description("Completed Ollama calls")
+                .with_unit("{request}")
+                .build(),
+            errors: meter.u64_counter("ollama.request.errors")
+                .with_description("Failed Ollama calls")
+                .with_unit("{request}")
+                .build(),
+            duration: meter.f64_histogram("ollama.request.duration")
+                .with_description("Duration of Ollama calls")
+                .with_unit("s")
+                .build(),
+            queue_time: meter.f64_histogram("ollama.queue.duration")
+                .with_description("Time spent in the client-side queue")
+                .with_unit("s")
+                .build(),
+            time_to_first_token: meter.f64_histogram("ollama.time_to_first_token")
+                .with_description("Time to the first generated token")
+                .with_unit("s")
+                .build(),
+            load_duration: meter.f64_histogram("ollama.load.duration")
+                .with_description("Time the server spent loading the model")
+                .with_unit("s")
+                .build(),
+            prompt_tokens: meter.u64_counter("ollama.tokens.prompt")
+                .with_description("Evaluated prompt tokens")
+                .with_unit("{token}")
+                .build(),
+            completion_tokens: meter.u64_counter("ollama.tokens.completion")
+                .with_description("Generated tokens")
+                .with_unit("{token}")
+                .build(),
+            tokens_per_second: meter.f64_histogram("ollama.tokens.rate")
+                .with_description("Generation speed reported by the server")
+                .with_unit("{token}/s")
+                .build(),
+        }
+    }
+}
+
+impl Default for OpenTelemetrySink {
+    /// Sink using the `ollama-rest` meter of the global meter provider
+    fn default() -> Self {
+        Self::new(&global::meter(env!("CARGO_PKG_NAME")))
+    }
+}
+
+impl MetricsSink for OpenTelemetrySink {
+    fn record(&self, metrics: &RequestMetrics) {
+        let mut attributes = vec![KeyValue::new("endpoint", metrics.endpoint.clone())];
+        if let Some(model) = &metrics.model {
+            attributes.push(KeyValue::new("model", model.clone()));
+        }
+
+        self.requests.add(1, &attributes);
+        if metrics.failed {
+            self.errors.add(1, &attributes);
+        }
+
+        self.duration.record(metrics.duration.as_secs_f64(), &attributes);
+
+        if let Some(time) = metrics.queue_time {
+            self.queue_time.record(time.as_secs_f64(), &attributes);
+        }
+        if let Some(time) = metrics.time_to_first_token {
+            self.time_to_first_token.record(time.as_secs_f64(), &attributes);
+        }
+        if let Some(time) = metrics.load_duration {
+            self.load_duration.record(time.as_secs_f64(), &attributes);
+        }
+        if let Some(count) = metrics.prompt_eval_count {
+            self.prompt_tokens.add(count, &attributes);
+        }
+        if let Some(count) = metrics.eval_count {
+            self.completion_tokens.add(count, &attributes);
+        }
+        if let Some(speed) = metrics.tokens_per_second() {
+            self.tokens_per_second.record(speed, &attributes);
+        }
+    }
+}
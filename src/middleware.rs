@@ -0,0 +1,203 @@
+//! Request and response middleware
+//!
+//! Layers added with [`Ollama::with_middleware`](crate::Ollama::with_middleware)
+//! run around every call: [`Middleware::on_request`] in the order the layers
+//! were added, before the request is sent, then [`Middleware::on_item`] in
+//! reverse order on each JSON item of the response, before it is decoded.
+//! A streamed response has one item per chunk; other JSON responses have a
+//! single item. Responses without a JSON body skip [`Middleware::on_item`].
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use ollama_rest::{
+//!     middleware::{Middleware, MiddlewareRequest, ResponseContext},
+//!     transport::BoxError,
+//!     Ollama,
+//! };
+//! use serde_json::Value;
+//!
+//! /// Tags every request and logs every response item
+//! struct Audit;
+//!
+//! impl Middleware for Audit {
+//!     fn on_request(&self, request: &mut MiddlewareRequest) -> Result<(), BoxError> {
+//!         request.headers.insert("x-request-id", "42".parse()?);
+//!         Ok(())
+//!     }
+//!
+//!     fn on_item(&self, context: &ResponseContext, item: &mut Value) -> Result<(), BoxError> {
+//!         println!("{} {}: {item}", context.request_headers["x-request-id"].to_str()?, context.url.path());
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let ollama = Ollama::default().with_middleware(Audit);
+//!
+//! // ...
+//! ```
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures::{stream, StreamExt, TryStreamExt};
+use reqwest::{
+    header::{HeaderMap, CONTENT_TYPE},
+    Method, StatusCode, Url,
+};
+use serde_json::Value;
+
+use crate::{
+    errors::Error,
+    transport::{lines, BoxError, RequestBody, TransportRequest, TransportResponse},
+};
+
+/// Outgoing request, as seen by [`Middleware::on_request`]
+#[derive(Debug, Clone)]
+pub struct MiddlewareRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    /// JSON body, if any
+    ///
+    /// `None` for requests without a body and for blob uploads.
+    pub body: Option<Value>,
+}
+
+/// Request and response a response item belongs to
+#[derive(Debug, Clone)]
+pub struct ResponseContext {
+    pub method: Method,
+    pub url: Url,
+    /// Headers sent with the request, after every [`Middleware::on_request`]
+    pub request_headers: HeaderMap,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+}
+
+/// Layer of a middleware chain
+///
+/// Errors abort the call with [`Error::Middleware`].
+pub trait Middleware: Send + Sync {
+    /// Inspect or modify a request before it is sent
+    fn on_request(&self, _request: &mut MiddlewareRequest) -> Result<(), BoxError> {
+        Ok(())
+    }
+
+    /// Inspect or modify a response item before it is decoded
+    fn on_item(&self, _context: &ResponseContext, _item: &mut Value) -> Result<(), BoxError> {
+        Ok(())
+    }
+}
+
+impl<T: Middleware + ?Sized> Middleware for Arc<T> {
+    fn on_request(&self, request: &mut MiddlewareRequest) -> Result<(), BoxError> {
+        (**self).on_request(request)
+    }
+
+    fn on_item(&self, context: &ResponseContext, item: &mut Value) -> Result<(), BoxError> {
+        (**self).on_item(context, item)
+    }
+}
+
+/// Ordered middleware layers
+#[derive(Clone, Default)]
+pub(crate) struct Chain {
+    layers: Vec<Arc<dyn Middleware>>,
+}
+
+impl Chain {
+    pub fn push(&mut self, layer: Arc<dyn Middleware>) {
+        self.layers.push(layer);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Run [`Middleware::on_request`] of every layer, in order
+    pub fn request(&self, request: TransportRequest) -> Result<TransportRequest, Error> {
+        let TransportRequest { method, url, headers, body } = request;
+
+        let (json, body) = match body {
+            RequestBody::Json(bytes) => (Some(serde_json::from_slice(&bytes)?), None),
+            body => (None, Some(body)),
+        };
+
+        let mut request = MiddlewareRequest { method, url, headers, body: json };
+        for layer in &self.layers {
+            layer.on_request(&mut request).map_err(Error::Middleware)?;
+        }
+
+        let body = match (request.body, body) {
+            (Some(json), _) => RequestBody::Json(serde_json::to_vec(&json)?.into()),
+            (None, Some(body)) => body,
+            (None, None) => RequestBody::Empty,
+        };
+
+        Ok(TransportRequest {
+            method: request.method,
+            url: request.url,
+            headers: request.headers,
+            body,
+        })
+    }
+
+    /// Run [`Middleware::on_item`] of every layer, in reverse order, on each item of `response`
+    pub fn response(&self, method: Method, url: Url, request_headers: HeaderMap, mut response: TransportResponse) -> TransportResponse {
+        let content_type = response.headers.get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+
+        let streamed = content_type.contains("ndjson");
+        if !streamed && !content_type.contains("json") {
+            return response;
+        }
+
+        let layers = self.layers.clone();
+        let context = ResponseContext {
+            method,
+            url,
+            request_headers,
+            status: response.status,
+            headers: response.headers.clone(),
+        };
+
+        let apply = move |item: Bytes| -> Result<Bytes, Error> {
+            if item.iter().all(u8::is_ascii_whitespace) {
+                return Ok(item);
+            }
+
+            let mut item = serde_json::from_slice::<Value>(&item)?;
+            for layer in layers.iter().rev() {
+                layer.on_item(&context, &mut item).map_err(Error::Middleware)?;
+            }
+
+            let mut item = serde_json::to_vec(&item)?;
+            if streamed {
+                item.push(b'\n');
+            }
+
+            Ok(item.into())
+        };
+
+        response.body = if streamed {
+            lines(response.body)
+                .map(move |line| apply(line?))
+                .boxed()
+        } else {
+            let body = response.body;
+
+            stream::once(async move {
+                let whole = body.try_fold(Vec::new(), |mut whole, chunk| async move {
+                    whole.extend_from_slice(&chunk);
+                    Ok(whole)
+                }).await?;
+
+                apply(whole.into())
+            }).boxed()
+        };
+
+        response
+    }
+}
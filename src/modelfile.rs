@@ -0,0 +1,680 @@
+//! Modelfile parser and builder
+//!
+//! Parses [Modelfile](https://github.com/ollama/ollama/blob/main/docs/modelfile.md)
+//! text into typed instructions. Unmodified instructions, comments and blank
+//! lines are written back exactly as they were read.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use ollama_rest::modelfile::Modelfile;
+//!
+//! let mut modelfile: Modelfile = "FROM llama3.2\n# Be creative\nPARAMETER temperature 1\n".parse().unwrap();
+//!
+//! modelfile.set_parameter("temperature", "0.2");
+//! modelfile.set_system("You are Mario from Super Mario Bros.");
+//!
+//! assert_eq!(
+//!     modelfile.to_string(),
+//!     "FROM llama3.2\n# Be creative\nPARAMETER temperature 0.2\nSYSTEM You are Mario from Super Mario Bros.\n",
+//! );
+//! ```
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::models::chat::Role;
+
+/// Modelfile instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    /// `FROM <model name, path or blob>`
+    From(String),
+    /// `PARAMETER <name> <value>`
+    Parameter {
+        name: String,
+        value: String,
+    },
+    /// `TEMPLATE <template>`
+    Template(String),
+    /// `SYSTEM <system message>`
+    System(String),
+    /// `ADAPTER <path or blob>`
+    Adapter(String),
+    /// `LICENSE <license text>`
+    License(String),
+    /// `MESSAGE <role> <content>`
+    Message {
+        role: Role,
+        content: String,
+    },
+}
+
+impl Instruction {
+    /// Instruction keyword in uppercase
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Self::From(_) => "FROM",
+            Self::Parameter { .. } => "PARAMETER",
+            Self::Template(_) => "TEMPLATE",
+            Self::System(_) => "SYSTEM",
+            Self::Adapter(_) => "ADAPTER",
+            Self::License(_) => "LICENSE",
+            Self::Message { .. } => "MESSAGE",
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ", self.keyword())?;
+
+        match self {
+            Self::From(value) | Self::Adapter(value) => write!(f, "{}", quote(value)),
+            Self::Template(value) => write!(f, "{}", triple_quote(value)),
+            Self::System(value) | Self::License(value) => write!(f, "{}", quote(value)),
+            Self::Parameter { name, value } => write!(f, "{name} {}", quote(value)),
+            Self::Message { role, content } => write!(f, "{role} {}", quote(content)),
+        }
+    }
+}
+
+/// Quote a value only when a bare value would not survive parsing
+fn quote(value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value.contains('\n')
+        || value.starts_with('"')
+        || value.trim() != value;
+
+    if needs_quotes {
+        triple_quote(value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Wrap a value in `"""`, or in escaped `"` quotes when `"""` would end it early
+fn triple_quote(value: &str) -> String {
+    if value.contains("\"\"\"") || value.ends_with('"') {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        format!("\"\"\"{value}\"\"\"")
+    }
+}
+
+/// Modelfile parsing error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelfileError {
+    /// Unknown instruction keyword
+    UnknownInstruction {
+        line: usize,
+        keyword: String,
+    },
+    /// Instruction without a (complete) argument
+    MissingArgument {
+        line: usize,
+    },
+    /// Quoted value without a closing quote
+    UnterminatedQuote {
+        line: usize,
+    },
+    /// `MESSAGE` role other than `system`, `user` or `assistant`
+    InvalidRole {
+        line: usize,
+        role: String,
+    },
+    /// Text after a closing quote on the same line
+    TrailingCharacters {
+        line: usize,
+    },
+}
+
+impl Display for ModelfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownInstruction { line, keyword } => write!(f, "line {line}: unknown instruction `{keyword}`"),
+            Self::MissingArgument { line } => write!(f, "line {line}: missing argument"),
+            Self::UnterminatedQuote { line } => write!(f, "line {line}: unterminated quote"),
+            Self::InvalidRole { line, role } => write!(f, "line {line}: invalid message role `{role}`"),
+            Self::TrailingCharacters { line } => write!(f, "line {line}: unexpected characters after closing quote"),
+        }
+    }
+}
+
+impl std::error::Error for ModelfileError {}
+
+#[derive(Debug, Clone)]
+enum Node {
+    /// Comments and blank lines, kept verbatim
+    Trivia(String),
+    Instruction {
+        instruction: Instruction,
+        /// Instruction as originally parsed, and its source text
+        source: Option<(Instruction, String)>,
+    },
+}
+
+/// Parsed Modelfile
+#[derive(Debug, Clone, Default)]
+pub struct Modelfile {
+    nodes: Vec<Node>,
+}
+
+impl Modelfile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start building a Modelfile
+    pub fn builder() -> ModelfileBuilder {
+        ModelfileBuilder::default()
+    }
+
+    /// Parse Modelfile text
+    pub fn parse(text: &str) -> Result<Self, ModelfileError> {
+        Parser { text, pos: 0, line: 1 }.parse()
+    }
+
+    /// All instructions in order
+    pub fn instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.nodes.iter().filter_map(|node| match node {
+            Node::Instruction { instruction, .. } => Some(instruction),
+            Node::Trivia(_) => None,
+        })
+    }
+
+    /// All instructions in order, mutably
+    pub fn instructions_mut(&mut self) -> impl Iterator<Item = &mut Instruction> {
+        self.nodes.iter_mut().filter_map(|node| match node {
+            Node::Instruction { instruction, .. } => Some(instruction),
+            Node::Trivia(_) => None,
+        })
+    }
+
+    /// Append an instruction
+    pub fn push(&mut self, instruction: Instruction) {
+        self.nodes.push(Node::Instruction { instruction, source: None });
+    }
+
+    /// Keep only the instructions for which `f` returns `true`
+    ///
+    /// Comments and blank lines are always kept.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Instruction) -> bool,
+    {
+        self.nodes.retain(|node| match node {
+            Node::Instruction { instruction, .. } => f(instruction),
+            Node::Trivia(_) => true,
+        });
+    }
+
+    /// Base model (`FROM`)
+    pub fn from(&self) -> Option<&str> {
+        self.instructions().find_map(|instruction| match instruction {
+            Instruction::From(value) => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Prompt template (`TEMPLATE`)
+    pub fn template(&self) -> Option<&str> {
+        self.instructions().find_map(|instruction| match instruction {
+            Instruction::Template(value) => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    /// System message (`SYSTEM`)
+    pub fn system(&self) -> Option<&str> {
+        self.instructions().find_map(|instruction| match instruction {
+            Instruction::System(value) => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    /// All parameters as `(name, value)` pairs
+    ///
+    /// Parameters such as `stop` may appear multiple times.
+    pub fn parameters(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.instructions().filter_map(|instruction| match instruction {
+            Instruction::Parameter { name, value } => Some((name.as_str(), value.as_str())),
+            _ => None,
+        })
+    }
+
+    /// First value of a parameter
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters().find(|(key, _)| *key == name).map(|(_, value)| value)
+    }
+
+    /// Adapters (`ADAPTER`)
+    pub fn adapters(&self) -> impl Iterator<Item = &str> {
+        self.instructions().filter_map(|instruction| match instruction {
+            Instruction::Adapter(value) => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Licenses (`LICENSE`)
+    pub fn licenses(&self) -> impl Iterator<Item = &str> {
+        self.instructions().filter_map(|instruction| match instruction {
+            Instruction::License(value) => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Messages (`MESSAGE`)
+    pub fn messages(&self) -> impl Iterator<Item = (Role, &str)> {
+        self.instructions().filter_map(|instruction| match instruction {
+            Instruction::Message { role, content } => Some((*role, content.as_str())),
+            _ => None,
+        })
+    }
+
+    /// Set a parameter, replacing all of its existing values
+    ///
+    /// The first existing occurrence is updated in place; otherwise the
+    /// parameter is appended.
+    pub fn set_parameter(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        let mut found = false;
+
+        self.nodes.retain_mut(|node| match node {
+            Node::Instruction { instruction: Instruction::Parameter { name: key, value: cur }, .. } if key == name => {
+                if found {
+                    false
+                } else {
+                    found = true;
+                    *cur = value.clone();
+                    true
+                }
+            }
+            _ => true,
+        });
+
+        if !found {
+            self.push(Instruction::Parameter { name: name.to_string(), value });
+        }
+    }
+
+    /// Remove all values of a parameter
+    pub fn remove_parameter(&mut self, name: &str) {
+        self.retain(|instruction| !matches!(instruction, Instruction::Parameter { name: key, .. } if key == name));
+    }
+
+    /// Set the base model, replacing the existing one
+    pub fn set_from(&mut self, value: impl Into<String>) {
+        self.set_single(Instruction::From(value.into()));
+    }
+
+    /// Set the prompt template, replacing the existing one
+    pub fn set_template(&mut self, value: impl Into<String>) {
+        self.set_single(Instruction::Template(value.into()));
+    }
+
+    /// Set the system message, replacing the existing one
+    pub fn set_system(&mut self, value: impl Into<String>) {
+        self.set_single(Instruction::System(value.into()));
+    }
+
+    fn set_single(&mut self, new: Instruction) {
+        let keyword = new.keyword();
+
+        let existing = self.instructions_mut().find(|instruction| instruction.keyword() == keyword);
+
+        match existing {
+            Some(instruction) => *instruction = new,
+            None => self.push(new),
+        }
+    }
+}
+
+impl FromStr for Modelfile {
+    type Err = ModelfileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl Display for Modelfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut at_line_start = true;
+
+        for node in self.nodes.iter() {
+            if !at_line_start {
+                writeln!(f)?;
+            }
+
+            let text = match node {
+                Node::Trivia(raw) => raw.clone(),
+                Node::Instruction { instruction, source: Some((original, raw)) } if instruction == original => raw.clone(),
+                Node::Instruction { instruction, .. } => format!("{instruction}\n"),
+            };
+
+            write!(f, "{text}")?;
+            at_line_start = text.is_empty() || text.ends_with('\n');
+        }
+
+        Ok(())
+    }
+}
+
+/// Modelfile builder
+///
+/// ## Examples
+///
+/// ```rust
+/// use ollama_rest::{models::chat::Role, modelfile::Modelfile};
+///
+/// let modelfile = Modelfile::builder()
+///     .from("llama3.2")
+///     .parameter("temperature", 0.7)
+///     .parameter("stop", "<|eot_id|>")
+///     .system("You are a helpful assistant.")
+///     .message(Role::User, "Hi!")
+///     .build();
+///
+/// assert_eq!(modelfile.parameter("temperature"), Some("0.7"));
+/// ```
+#[derive(Debug, Default)]
+pub struct ModelfileBuilder {
+    modelfile: Modelfile,
+}
+
+impl ModelfileBuilder {
+    pub fn from(mut self, value: impl Into<String>) -> Self {
+        self.modelfile.push(Instruction::From(value.into()));
+        self
+    }
+
+    pub fn parameter(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.modelfile.push(Instruction::Parameter { name: name.into(), value: value.to_string() });
+        self
+    }
+
+    pub fn template(mut self, value: impl Into<String>) -> Self {
+        self.modelfile.push(Instruction::Template(value.into()));
+        self
+    }
+
+    pub fn system(mut self, value: impl Into<String>) -> Self {
+        self.modelfile.push(Instruction::System(value.into()));
+        self
+    }
+
+    pub fn adapter(mut self, value: impl Into<String>) -> Self {
+        self.modelfile.push(Instruction::Adapter(value.into()));
+        self
+    }
+
+    pub fn license(mut self, value: impl Into<String>) -> Self {
+        self.modelfile.push(Instruction::License(value.into()));
+        self
+    }
+
+    pub fn message(mut self, role: Role, content: impl Into<String>) -> Self {
+        self.modelfile.push(Instruction::Message { role, content: content.into() });
+        self
+    }
+
+    pub fn build(self) -> Modelfile {
+        self.modelfile
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(mut self) -> Result<Modelfile, ModelfileError> {
+        let mut nodes = Vec::new();
+
+        while self.pos < self.text.len() {
+            let start = self.pos;
+            let line = self.current_line();
+
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                self.advance_line();
+                nodes.push(Node::Trivia(self.text[start..self.pos].to_string()));
+                continue;
+            }
+
+            let instruction = self.parse_instruction()?;
+            let raw = self.text[start..self.pos].to_string();
+
+            nodes.push(Node::Instruction {
+                instruction: instruction.clone(),
+                source: Some((instruction, raw)),
+            });
+        }
+
+        Ok(Modelfile { nodes })
+    }
+
+    /// Rest of the current line, without the line break
+    fn current_line(&self) -> &'a str {
+        let rest = &self.text[self.pos..];
+        &rest[..rest.find('\n').unwrap_or(rest.len())]
+    }
+
+    /// Move past the next line break (or to the end of the text)
+    fn advance_line(&mut self) {
+        self.pos += self.current_line().len();
+        if self.pos < self.text.len() {
+            self.pos += 1;
+            self.line += 1;
+        }
+    }
+
+    fn skip_inline_whitespace(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t']).len();
+    }
+
+    /// Read a word delimited by whitespace
+    fn word(&mut self) -> &'a str {
+        self.skip_inline_whitespace();
+
+        let rest = self.current_line();
+        let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        self.pos += len;
+
+        &rest[..len]
+    }
+
+    fn parse_instruction(&mut self) -> Result<Instruction, ModelfileError> {
+        let line = self.line;
+        let keyword = self.word().to_ascii_uppercase();
+
+        Ok(match keyword.as_str() {
+            "FROM" => Instruction::From(self.value()?),
+            "TEMPLATE" => Instruction::Template(self.value()?),
+            "SYSTEM" => Instruction::System(self.value()?),
+            "ADAPTER" => Instruction::Adapter(self.value()?),
+            "LICENSE" => Instruction::License(self.value()?),
+            "PARAMETER" => {
+                let name = self.word().to_string();
+                if name.is_empty() {
+                    return Err(ModelfileError::MissingArgument { line });
+                }
+
+                Instruction::Parameter { name, value: self.value()? }
+            }
+            "MESSAGE" => {
+                let role = self.word().to_string();
+                let role = match role.as_str() {
+                    "system" | "user" | "assistant" => Role::from_str(&role).map_err(|_| ModelfileError::InvalidRole { line, role })?,
+                    "" => return Err(ModelfileError::MissingArgument { line }),
+                    _ => return Err(ModelfileError::InvalidRole { line, role }),
+                };
+
+                Instruction::Message { role, content: self.value()? }
+            }
+            _ => return Err(ModelfileError::UnknownInstruction { line, keyword }),
+        })
+    }
+
+    /// Read an instruction argument, which may be bare, `"quoted"` or `"""triple-quoted"""`
+    ///
+    /// Quoted values may span lines, and `\"` and `\\` are unescaped in them.
+    fn value(&mut self) -> Result<String, ModelfileError> {
+        let line = self.line;
+        self.skip_inline_whitespace();
+
+        let rest = &self.text[self.pos..];
+
+        let value = if let Some(body) = rest.strip_prefix("\"\"\"") {
+            let end = body.find("\"\"\"").ok_or(ModelfileError::UnterminatedQuote { line })?;
+            let value = body[..end].to_string();
+
+            self.line += value.matches('\n').count();
+            self.pos += 3 + end + 3;
+
+            value
+        } else if let Some(body) = rest.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = body.char_indices();
+            let mut end = None;
+
+            while let Some((idx, c)) = chars.next() {
+                match c {
+                    '\\' => match chars.next() {
+                        Some((_, escaped @ ('"' | '\\'))) => value.push(escaped),
+                        Some((_, other)) => {
+                            value.push('\\');
+                            value.push(other);
+                        }
+                        None => value.push('\\'),
+                    },
+                    '"' => {
+                        end = Some(idx);
+                        break;
+                    }
+                    c => value.push(c),
+                }
+            }
+
+            let end = end.ok_or(ModelfileError::UnterminatedQuote { line })?;
+
+            self.line += body[..end].matches('\n').count();
+            self.pos += 1 + end + 1;
+
+            value
+        } else {
+            let value = self.current_line().trim().to_string();
+            if value.is_empty() {
+                return Err(ModelfileError::MissingArgument { line });
+            }
+
+            self.advance_line();
+            return Ok(value);
+        };
+
+        // Only whitespace may follow a closing quote
+        if !self.current_line().trim().is_empty() {
+            return Err(ModelfileError::TrailingCharacters { line: self.line });
+        }
+        self.advance_line();
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHOWN_MODELFILE: &str = r#"# Modelfile generated by "ollama show"
+# To build a new Modelfile based on this, replace FROM with:
+# FROM llama3.2:1b
+
+FROM /usr/share/ollama/.ollama/models/blobs/sha256-74701a8c35f6c8d9a4b91f3f3497643001d63e0c7a84e085bed452548fa88d45
+TEMPLATE """<|start_header_id|>system<|end_header_id|>
+
+{{ .System }}<|eot_id|>"""
+PARAMETER stop <|start_header_id|>
+PARAMETER stop <|end_header_id|>
+parameter stop "<|eot_id|>"
+SYSTEM "You are \"Mario\"."
+MESSAGE user Is Toronto in Canada?
+MESSAGE assistant yes
+LICENSE """LLAMA 3.2 COMMUNITY LICENSE AGREEMENT
+Llama 3.2 Version Release Date: September 25, 2024"""
+"#;
+
+    #[test]
+    fn parse_shown_modelfile() {
+        let modelfile = Modelfile::parse(SHOWN_MODELFILE).unwrap();
+
+        assert!(modelfile.from().unwrap().ends_with("fa88d45"));
+        assert_eq!(modelfile.template(), Some("<|start_header_id|>system<|end_header_id|>\n\n{{ .System }}<|eot_id|>"));
+        assert_eq!(modelfile.system(), Some("You are \"Mario\"."));
+        assert_eq!(
+            modelfile.parameters().collect::<Vec<_>>(),
+            vec![("stop", "<|start_header_id|>"), ("stop", "<|end_header_id|>"), ("stop", "<|eot_id|>")],
+        );
+        assert_eq!(modelfile.messages().collect::<Vec<_>>(), vec![(Role::User, "Is Toronto in Canada?"), (Role::Assistant, "yes")]);
+        assert_eq!(modelfile.licenses().count(), 1);
+    }
+
+    #[test]
+    fn round_trip_is_byte_faithful() {
+        let modelfile = Modelfile::parse(SHOWN_MODELFILE).unwrap();
+        assert_eq!(modelfile.to_string(), SHOWN_MODELFILE);
+
+        let no_trailing_newline = "FROM llama3.2\r\n  # indented comment\n\nPARAMETER  temperature   0.5";
+        assert_eq!(Modelfile::parse(no_trailing_newline).unwrap().to_string(), no_trailing_newline);
+    }
+
+    #[test]
+    fn modify_and_reparse() {
+        let mut modelfile = Modelfile::parse(SHOWN_MODELFILE).unwrap();
+
+        modelfile.set_parameter("stop", "</s>");
+        modelfile.set_parameter("num_ctx", "4096");
+        modelfile.set_system("  padded\nmulti-line  ");
+
+        let text = modelfile.to_string();
+        assert!(text.contains("PARAMETER stop </s>\nSYSTEM \"\"\"  padded\nmulti-line  \"\"\"\n"));
+        assert!(text.ends_with("PARAMETER num_ctx 4096\n"));
+
+        let reparsed = Modelfile::parse(&text).unwrap();
+        assert_eq!(reparsed.instructions().collect::<Vec<_>>(), modelfile.instructions().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn values_with_triple_quotes_round_trip() {
+        let template = "{{ .Prompt }}\n\"\"\"quoted\"\"\" \\n";
+        let system = "Say \"hi\"";
+
+        let modelfile = Modelfile::builder()
+            .template(template)
+            .system(format!("  {system}"))
+            .message(Role::User, "\"\"\"")
+            .parameter("num_ctx", 2048)
+            .build();
+
+        let text = modelfile.to_string();
+        assert!(text.starts_with("TEMPLATE \"{{ .Prompt }}\n\\\"\\\"\\\"quoted"));
+
+        let reparsed = Modelfile::parse(&text).unwrap();
+        assert_eq!(reparsed.instructions().collect::<Vec<_>>(), modelfile.instructions().collect::<Vec<_>>());
+        assert_eq!(reparsed.parameter("num_ctx"), Some("2048"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Modelfile::parse("FROM llama3.2\nRUN rm -rf /").unwrap_err(), ModelfileError::UnknownInstruction { line: 2, keyword: "RUN".to_string() });
+        assert_eq!(Modelfile::parse("FROM").unwrap_err(), ModelfileError::MissingArgument { line: 1 });
+        assert_eq!(Modelfile::parse("SYSTEM \"\"\"never\nclosed").unwrap_err(), ModelfileError::UnterminatedQuote { line: 1 });
+        assert_eq!(Modelfile::parse("SYSTEM \"never\nclosed").unwrap_err(), ModelfileError::UnterminatedQuote { line: 1 });
+        assert_eq!(Modelfile::parse("MESSAGE tool hi").unwrap_err(), ModelfileError::InvalidRole { line: 1, role: "tool".to_string() });
+        assert_eq!(Modelfile::parse("SYSTEM \"a\" b").unwrap_err(), ModelfileError::TrailingCharacters { line: 1 });
+        assert_eq!(Modelfile::parse("FROM llama3.2\nTEMPLATE \"\"\"a\nb\"\"\" c").unwrap_err(), ModelfileError::TrailingCharacters { line: 3 });
+        assert_eq!(Modelfile::parse("SYSTEM \"a\" \r\nFROM llama3.2").unwrap().system(), Some("a"));
+    }
+}
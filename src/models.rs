@@ -5,13 +5,19 @@ use std::{fmt::Display, str::FromStr};
 use errors::ParsingError;
 use serde::{Deserialize, Serialize};
 
+pub mod blob;
 pub mod chat;
 pub mod create;
+pub mod digest;
+pub mod embedding;
 pub mod embeddings;
 pub mod errors;
 pub mod generate;
 pub mod json_schema;
 pub mod model;
+pub mod model_info;
+pub mod options;
+pub mod stats;
 pub mod version;
 
 /// Request format
@@ -44,7 +50,7 @@ impl FromStr for RequestFormat {
 }
 
 /// Status message
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Status {
     pub status: String,
 }
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+use super::digest::Digest;
+
+/// Blob upload progress
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobUploadProgress {
+    /// Digest of the blob being uploaded
+    pub digest: Digest,
+    /// Bytes sent so far
+    pub completed: u64,
+    /// Total size in bytes, if known
+    pub total: Option<u64>,
+}
@@ -5,9 +5,9 @@ use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
-use super::{errors::ParsingError, json_schema::JsonSchema, RequestFormat};
+use super::{errors::ParsingError, json_schema::JsonSchema, model::Capability, stats::GenerationStats, RequestFormat};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Role {
     System,
@@ -94,6 +94,27 @@ pub struct ChatRequest {
     pub think: Option<bool>,
 }
 
+impl ChatRequest {
+    /// Capabilities the model needs to serve this request
+    pub fn required_capabilities(&self) -> Vec<Capability> {
+        let mut capabilities = vec![Capability::Completion];
+
+        if self.tools.as_ref().is_some_and(|tools| !tools.is_empty()) {
+            capabilities.push(Capability::Tools);
+        }
+
+        if self.messages.iter().any(|msg| msg.images.as_ref().is_some_and(|images| !images.is_empty())) {
+            capabilities.push(Capability::Vision);
+        }
+
+        if self.think == Some(true) {
+            capabilities.push(Capability::Thinking);
+        }
+
+        capabilities
+    }
+}
+
 /// Chat completion response
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChatResponse {
@@ -107,10 +128,7 @@ pub struct ChatResponse {
     pub message: Option<Message>,
     pub done: bool,
 
-    pub total_duration: Option<u64>,
-    pub load_duration: Option<u64>,
-    pub prompt_eval_count: Option<usize>,
-    pub prompt_eval_duration: Option<u64>,
-    pub eval_count: Option<usize>,
-    pub eval_duration: Option<u64>,
+    /// Performance stats, sent with the final chunk
+    #[serde(flatten)]
+    pub stats: GenerationStats,
 }
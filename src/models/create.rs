@@ -1,11 +1,88 @@
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::PathBuf};
 
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
-#[derive(Debug, Serialize, Deserialize)]
+use super::{blob::BlobUploadProgress, chat::Message, digest::Digest, Status};
+
+/// Model creation request
+///
+/// Two forms are supported:
+/// - Current form (Ollama 0.5.5+): `model` together with `from`, `files`,
+///   `adapters` and the other fields replacing Modelfile instructions.
+/// - Legacy form: `name` together with `modelfile` or `path`.
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct CreationRequest {
-    pub name: String,
-    pub modelfile: Option<String>,
+    /// Name of the model to create
+    pub model: Option<String>,
+    /// Existing model to create from
+    pub from: Option<String>,
+    /// File names mapped to digests of uploaded blobs (e.g. GGUF or safetensors files)
+    pub files: Option<BTreeMap<String, Digest>>,
+    /// File names mapped to digests of uploaded LoRA adapter blobs
+    pub adapters: Option<BTreeMap<String, Digest>>,
+    pub template: Option<String>,
+    pub license: Option<License>,
+    pub system: Option<String>,
+    pub parameters: Option<Map<String, Value>>,
+    pub messages: Option<Vec<Message>>,
+    /// Quantization type (e.g. `q4_K_M`, `q8_0`) of a non-quantized model
+    pub quantize: Option<String>,
+
     pub stream: Option<bool>,
+
+    /// Name of the model to create (legacy form)
+    pub name: Option<String>,
+    /// Modelfile content (legacy form)
+    pub modelfile: Option<String>,
+    /// Modelfile path (legacy form)
     pub path: Option<PathBuf>,
 }
+
+/// License(s) of a model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum License {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+/// Progress of [`crate::Ollama::create_model_from_gguf()`]
+#[derive(Debug)]
+pub enum CreationProgress {
+    /// Uploading the model file as a blob
+    Upload(BlobUploadProgress),
+    /// Status reported by `/api/create`
+    Status(Status),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_request_forms() {
+        let digest = Digest::from_bytes(b"");
+
+        let current = serde_json::to_value(CreationRequest {
+            model: Some("mario".to_string()),
+            files: Some(BTreeMap::from([("model.gguf".to_string(), digest)])),
+            quantize: Some("q4_K_M".to_string()),
+            ..Default::default()
+        }).unwrap();
+
+        assert_eq!(current["model"], "mario");
+        assert_eq!(current["files"]["model.gguf"], digest.to_string());
+        assert_eq!(current["quantize"], "q4_K_M");
+
+        let legacy = serde_json::from_value::<CreationRequest>(serde_json::json!({
+            "name": "mario",
+            "modelfile": "FROM llama3.2\nSYSTEM You are mario from Super Mario Bros.",
+            "license": ["MIT", "Apache-2.0"],
+        })).unwrap();
+
+        assert_eq!(legacy.name.as_deref(), Some("mario"));
+        assert!(legacy.model.is_none());
+        assert!(matches!(legacy.license, Some(License::Multiple(ref licenses)) if licenses.len() == 2));
+    }
+}
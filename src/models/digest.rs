@@ -0,0 +1,233 @@
+use std::{cmp::Ordering, fmt::{Debug, Display}, hash::{Hash, Hasher}, str::FromStr};
+
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest as _, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use super::errors::ParsingError;
+
+/// SHA256 digest of a blob or model
+///
+/// Parsed from either `sha256:<64 hex digits>` or bare hex, and displayed in
+/// the same form, so that responses round-trip unchanged. Computed digests use
+/// Ollama's `sha256:<hex>` format. The form is ignored when comparing digests.
+///
+/// ## Examples
+///
+/// ```rust
+/// use ollama_rest::models::digest::Digest;
+///
+/// let digest: Digest = "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".parse().unwrap();
+///
+/// assert_eq!(digest, Digest::from_bytes(b""));
+/// assert!("sha256:../../api/delete".parse::<Digest>().is_err());
+///
+/// let bare: Digest = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".parse().unwrap();
+///
+/// assert_eq!(bare, digest);
+/// assert_eq!(bare.to_string(), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+/// ```
+#[derive(Clone, Copy)]
+pub struct Digest {
+    bytes: [u8; 32],
+    /// Whether the `sha256:` prefix is displayed
+    prefixed: bool,
+}
+
+impl Digest {
+    /// Algorithm prefix used by Ollama
+    pub const PREFIX: &'static str = "sha256:";
+
+    /// Compute the digest of a byte slice
+    pub fn from_bytes(data: &[u8]) -> Self {
+        Self::from_hasher(Sha256::new_with_prefix(data))
+    }
+
+    /// Compute the digest of everything readable from `reader`
+    pub async fn from_reader<R>(mut reader: R) -> std::io::Result<Self>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; crate::blob::CHUNK_SIZE];
+
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+
+            hasher.update(&buf[..n]);
+        }
+
+        Ok(Self::from_hasher(hasher))
+    }
+
+    /// Compute the digest of everything readable from a blocking `reader`
+    pub fn from_reader_blocking<R>(mut reader: R) -> std::io::Result<Self>
+    where
+        R: std::io::Read,
+    {
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut reader, &mut hasher)?;
+
+        Ok(Self::from_hasher(hasher))
+    }
+
+    pub(crate) fn from_hasher(hasher: Sha256) -> Self {
+        Self::from(<[u8; 32]>::from(hasher.finalize()))
+    }
+
+    /// Set whether the `sha256:` prefix is displayed
+    pub fn with_prefix(mut self, prefixed: bool) -> Self {
+        self.prefixed = prefixed;
+        self
+    }
+
+    /// Whether the `sha256:` prefix is displayed
+    pub fn is_prefixed(&self) -> bool {
+        self.prefixed
+    }
+
+    /// Raw digest bytes
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.bytes
+    }
+
+    /// Lowercase hex representation, without the `sha256:` prefix
+    pub fn to_hex(&self) -> String {
+        self.bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+}
+
+impl From<[u8; 32]> for Digest {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self { bytes, prefixed: true }
+    }
+}
+
+impl PartialEq for Digest {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Eq for Digest {}
+
+impl Hash for Digest {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bytes.hash(state);
+    }
+}
+
+impl PartialOrd for Digest {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Digest {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.bytes.cmp(&other.bytes)
+    }
+}
+
+impl FromStr for Digest {
+    type Err = ParsingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (hex, prefixed) = match s.strip_prefix(Self::PREFIX) {
+            Some(hex) => (hex.as_bytes(), true),
+            None => (s.as_bytes(), false),
+        };
+
+        if hex.len() != 64 {
+            return Err(ParsingError::InvalidDigest);
+        }
+
+        let mut bytes = [0u8; 32];
+        for (byte, pair) in bytes.iter_mut().zip(hex.chunks_exact(2)) {
+            let hi = (pair[0] as char).to_digit(16).ok_or(ParsingError::InvalidDigest)?;
+            let lo = (pair[1] as char).to_digit(16).ok_or(ParsingError::InvalidDigest)?;
+            *byte = (hi * 16 + lo) as u8;
+        }
+
+        Ok(Self { bytes, prefixed })
+    }
+}
+
+impl Display for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.prefixed {
+            f.write_str(Self::PREFIX)?;
+        }
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl Debug for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Digest({self})")
+    }
+}
+
+impl Serialize for Digest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Digest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(|_| D::Error::custom(format!("invalid digest: {s}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn parse_valid_digests() {
+        let expected = format!("sha256:{EMPTY_SHA256}");
+
+        assert_eq!(EMPTY_SHA256.parse::<Digest>().unwrap().to_string(), EMPTY_SHA256);
+        assert_eq!(expected.parse::<Digest>().unwrap().to_string(), expected);
+        assert_eq!(EMPTY_SHA256.to_uppercase().parse::<Digest>().unwrap().to_string(), EMPTY_SHA256);
+        assert_eq!(EMPTY_SHA256.parse::<Digest>().unwrap().with_prefix(true).to_string(), expected);
+        assert_eq!(EMPTY_SHA256.parse::<Digest>().unwrap(), expected.parse::<Digest>().unwrap());
+    }
+
+    #[test]
+    fn reject_invalid_digests() {
+        assert!("".parse::<Digest>().is_err());
+        assert!("sha256:".parse::<Digest>().is_err());
+        assert!(EMPTY_SHA256[1..].parse::<Digest>().is_err());
+        assert!(format!("{EMPTY_SHA256}/../../api/delete").parse::<Digest>().is_err());
+        assert!(format!("sha512:{EMPTY_SHA256}").parse::<Digest>().is_err());
+        assert!(format!("sha256:{}zz", &EMPTY_SHA256[2..]).parse::<Digest>().is_err());
+    }
+
+    #[tokio::test]
+    async fn compute_digests() {
+        let from_bytes = Digest::from_bytes(b"abc");
+        let from_reader = Digest::from_reader(&b"abc"[..]).await.unwrap();
+
+        assert_eq!(from_bytes, from_reader);
+        assert_eq!(from_bytes, Digest::from_reader_blocking(&b"abc"[..]).unwrap());
+        assert_eq!(from_bytes.to_hex(), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(Digest::from_bytes(b"").to_hex(), EMPTY_SHA256);
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        for form in [EMPTY_SHA256.to_string(), format!("sha256:{EMPTY_SHA256}")] {
+            let digest: Digest = serde_json::from_value(serde_json::json!(form)).unwrap();
+
+            assert_eq!(serde_json::to_value(digest).unwrap(), serde_json::json!(form));
+        }
+    }
+}
@@ -0,0 +1,457 @@
+use std::{fmt::Display, str::FromStr};
+
+use half::f16;
+use serde::{ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::errors::Error;
+
+use super::errors::ParsingError;
+
+/// Storage precision of an [`Embedding`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    /// Half precision, a quarter of the memory of [`Precision::F64`]
+    F16,
+    /// Single precision, half the memory of [`Precision::F64`]
+    F32,
+    /// Double precision, as decoded from responses
+    #[default]
+    F64,
+}
+
+impl Precision {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::F16 => "f16",
+            Self::F32 => "f32",
+            Self::F64 => "f64",
+        }
+    }
+
+    /// Size of one value in bytes
+    pub fn size(&self) -> usize {
+        match self {
+            Self::F16 => 2,
+            Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+}
+
+impl AsRef<str> for Precision {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Display for Precision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Precision {
+    type Err = ParsingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "f16" => Self::F16,
+            "f32" => Self::F32,
+            "f64" => Self::F64,
+            _ => return Err(ParsingError::InvalidStr),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Values {
+    F16(Vec<f16>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+}
+
+/// Embedding vector
+///
+/// Decoded from responses in double precision; convert it with
+/// [`Embedding::to_precision`] to save memory. Math is done in double
+/// precision whatever the storage.
+///
+/// Serializes to a JSON array of numbers, like in Ollama responses, and to a
+/// compact binary format with [`Embedding::to_bytes`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Embedding(Values);
+
+impl Embedding {
+    pub fn precision(&self) -> Precision {
+        match self.0 {
+            Values::F16(_) => Precision::F16,
+            Values::F32(_) => Precision::F32,
+            Values::F64(_) => Precision::F64,
+        }
+    }
+
+    /// Number of dimensions
+    pub fn len(&self) -> usize {
+        match &self.0 {
+            Values::F16(values) => values.len(),
+            Values::F32(values) => values.len(),
+            Values::F64(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<f64> {
+        match &self.0 {
+            Values::F16(values) => values.get(index).map(|x| x.to_f64()),
+            Values::F32(values) => values.get(index).map(|&x| x as f64),
+            Values::F64(values) => values.get(index).copied(),
+        }
+    }
+
+    /// Values in double precision
+    pub fn iter(&self) -> Iter<'_> {
+        match &self.0 {
+            Values::F16(values) => Iter::F16(values.iter()),
+            Values::F32(values) => Iter::F32(values.iter()),
+            Values::F64(values) => Iter::F64(values.iter()),
+        }
+    }
+
+    pub fn as_f16(&self) -> Option<&[f16]> {
+        match &self.0 {
+            Values::F16(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<&[f32]> {
+        match &self.0 {
+            Values::F32(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<&[f64]> {
+        match &self.0 {
+            Values::F64(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<f64> {
+        self.iter().collect()
+    }
+
+    /// Same values stored with `precision`, rounded to nearest
+    pub fn to_precision(&self, precision: Precision) -> Self {
+        if precision == self.precision() {
+            return self.clone();
+        }
+
+        Self(match precision {
+            Precision::F16 => Values::F16(self.iter().map(f16::from_f64).collect()),
+            Precision::F32 => Values::F32(self.iter().map(|x| x as f32).collect()),
+            Precision::F64 => Values::F64(self.to_vec()),
+        })
+    }
+
+    /// Memory taken by the values, in bytes
+    pub fn size(&self) -> usize {
+        self.len() * self.precision().size()
+    }
+
+    /// Euclidean norm
+    pub fn norm(&self) -> f64 {
+        self.iter().map(|x| x * x).sum::<f64>().sqrt()
+    }
+
+    /// Scale to a norm of 1, keeping the precision
+    ///
+    /// Zero vectors are left unchanged.
+    pub fn normalize(&mut self) {
+        let norm = self.norm();
+        if norm == 0.0 {
+            return;
+        }
+
+        match &mut self.0 {
+            Values::F16(values) => values.iter_mut().for_each(|x| *x = f16::from_f64(x.to_f64() / norm)),
+            Values::F32(values) => values.iter_mut().for_each(|x| *x = (*x as f64 / norm) as f32),
+            Values::F64(values) => values.iter_mut().for_each(|x| *x /= norm),
+        }
+    }
+
+    pub fn normalized(mut self) -> Self {
+        self.normalize();
+        self
+    }
+
+    fn check_len(&self, other: &Self) -> Result<(), Error> {
+        match (self.len(), other.len()) {
+            (expected, actual) if expected != actual => Err(Error::DimensionMismatch { expected, actual }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Dot product
+    ///
+    /// ## Returns
+    /// [`Error::DimensionMismatch`] if the embeddings have different lengths
+    pub fn dot(&self, other: &Self) -> Result<f64, Error> {
+        self.check_len(other)?;
+        Ok(self.iter().zip(other.iter()).map(|(a, b)| a * b).sum())
+    }
+
+    /// Cosine similarity, from -1 to 1
+    ///
+    /// 0 if either embedding is a zero vector.
+    ///
+    /// ## Returns
+    /// [`Error::DimensionMismatch`] if the embeddings have different lengths
+    pub fn cosine(&self, other: &Self) -> Result<f64, Error> {
+        Ok(cosine(self.dot(other)?, self.norm(), other.norm()))
+    }
+
+    /// Euclidean distance
+    ///
+    /// ## Returns
+    /// [`Error::DimensionMismatch`] if the embeddings have different lengths
+    pub fn euclidean(&self, other: &Self) -> Result<f64, Error> {
+        self.check_len(other)?;
+        Ok(self.iter().zip(other.iter()).map(|(a, b)| (a - b) * (a - b)).sum::<f64>().sqrt())
+    }
+
+    /// Cosine similarity of every embedding of `rows` to every embedding of `columns`
+    ///
+    /// ## Returns
+    /// - `Ok(matrix)`: `matrix[i][j]` is the similarity of `rows[i]` to `columns[j]`
+    /// - `Err(Error::DimensionMismatch)`: Embeddings have different lengths
+    pub fn similarity_matrix(rows: &[Self], columns: &[Self]) -> Result<Vec<Vec<f64>>, Error> {
+        if let Some(first) = rows.first().or(columns.first()) {
+            rows.iter().chain(columns).try_for_each(|embedding| first.check_len(embedding))?;
+        }
+
+        let column_norms = columns.iter().map(Self::norm).collect::<Vec<_>>();
+
+        Ok(rows.iter()
+            .map(|row| {
+                let norm = row.norm();
+                columns.iter()
+                    .zip(&column_norms)
+                    .map(|(column, &column_norm)| {
+                        let dot = row.iter().zip(column.iter()).map(|(a, b)| a * b).sum();
+                        cosine(dot, norm, column_norm)
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Encode to bytes
+    ///
+    /// The format is the size of one value in bytes (2, 4 or 8), the number of
+    /// values as a little-endian `u32`, then the values, little-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(5 + self.size());
+        bytes.push(self.precision().size() as u8);
+        bytes.extend_from_slice(&(self.len() as u32).to_le_bytes());
+
+        match &self.0 {
+            Values::F16(values) => values.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+            Values::F32(values) => values.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+            Values::F64(values) => values.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+        }
+
+        bytes
+    }
+
+    /// Decode bytes encoded with [`Embedding::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ParsingError> {
+        let (&size, rest) = bytes.split_first().ok_or(ParsingError::InvalidEmbedding)?;
+        let (len, values) = rest.split_first_chunk::<4>().ok_or(ParsingError::InvalidEmbedding)?;
+        let len = u32::from_le_bytes(*len) as usize;
+
+        if Some(values.len()) != len.checked_mul(size as usize) {
+            return Err(ParsingError::InvalidEmbedding);
+        }
+
+        Ok(Self(match size {
+            2 => Values::F16(values.chunks_exact(2).map(|x| f16::from_le_bytes([x[0], x[1]])).collect()),
+            4 => Values::F32(values.chunks_exact(4).map(|x| f32::from_le_bytes(x.try_into().unwrap())).collect()),
+            8 => Values::F64(values.chunks_exact(8).map(|x| f64::from_le_bytes(x.try_into().unwrap())).collect()),
+            _ => return Err(ParsingError::InvalidEmbedding),
+        }))
+    }
+}
+
+fn cosine(dot: f64, a_norm: f64, b_norm: f64) -> f64 {
+    match a_norm * b_norm {
+        0.0 => 0.0,
+        norms => dot / norms,
+    }
+}
+
+impl From<Vec<f64>> for Embedding {
+    fn from(value: Vec<f64>) -> Self {
+        Self(Values::F64(value))
+    }
+}
+
+impl From<Vec<f32>> for Embedding {
+    fn from(value: Vec<f32>) -> Self {
+        Self(Values::F32(value))
+    }
+}
+
+impl From<Vec<f16>> for Embedding {
+    fn from(value: Vec<f16>) -> Self {
+        Self(Values::F16(value))
+    }
+}
+
+impl From<Embedding> for Vec<f64> {
+    fn from(value: Embedding) -> Self {
+        match value.0 {
+            Values::F64(values) => values,
+            _ => value.to_vec(),
+        }
+    }
+}
+
+impl FromIterator<f64> for Embedding {
+    fn from_iter<I: IntoIterator<Item = f64>>(iter: I) -> Self {
+        Self(Values::F64(iter.into_iter().collect()))
+    }
+}
+
+impl<'a> IntoIterator for &'a Embedding {
+    type Item = f64;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Serialize for Embedding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.0 {
+            Values::F16(values) => {
+                let mut seq = serializer.serialize_seq(Some(values.len()))?;
+                for value in values {
+                    seq.serialize_element(&value.to_f32())?;
+                }
+                seq.end()
+            }
+            Values::F32(values) => values.serialize(serializer),
+            Values::F64(values) => values.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Embedding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<f64>::deserialize(deserializer).map(Self::from)
+    }
+}
+
+/// Iterator over the values of an [`Embedding`], in double precision
+#[derive(Debug, Clone)]
+pub enum Iter<'a> {
+    F16(std::slice::Iter<'a, f16>),
+    F32(std::slice::Iter<'a, f32>),
+    F64(std::slice::Iter<'a, f64>),
+}
+
+impl Iterator for Iter<'_> {
+    type Item = f64;
+
+    #[inline]
+    fn next(&mut self) -> Option<f64> {
+        match self {
+            Self::F16(iter) => iter.next().map(|x| x.to_f64()),
+            Self::F32(iter) => iter.next().map(|&x| x as f64),
+            Self::F64(iter) => iter.next().copied(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Self::F16(iter) => iter.size_hint(),
+            Self::F32(iter) => iter.size_hint(),
+            Self::F64(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vector_math() {
+        let a = Embedding::from(vec![3.0, 4.0]);
+        let b = Embedding::from(vec![4.0, 3.0]);
+
+        assert_eq!(a.norm(), 5.0);
+        assert_eq!(a.dot(&b).unwrap(), 24.0);
+        assert_eq!(a.cosine(&b).unwrap(), 0.96);
+        assert_eq!(a.euclidean(&b).unwrap(), 2f64.sqrt());
+        assert_eq!(a.clone().normalized().to_vec(), [0.6, 0.8]);
+        assert_eq!(Embedding::from(vec![0.0, 0.0]).cosine(&a).unwrap(), 0.0);
+
+        assert!(matches!(
+            a.dot(&Embedding::from(vec![1.0])),
+            Err(Error::DimensionMismatch { expected: 2, actual: 1 }),
+        ));
+
+        let matrix = Embedding::similarity_matrix(&[a.clone(), b.clone()], &[a.clone(), b, a.iter().map(|x| -x).collect()]).unwrap();
+        assert_eq!(matrix, [[1.0, 0.96, -1.0], [0.96, 1.0, -0.96]]);
+    }
+
+    #[test]
+    fn precision() {
+        let embedding = Embedding::from(vec![0.1, -0.5, 0.25]);
+
+        let half = embedding.to_precision(Precision::F16);
+        assert_eq!(half.precision(), Precision::F16);
+        assert_eq!(half.size(), 6);
+        assert_eq!(half.get(1), Some(-0.5));
+        assert!((half.cosine(&embedding).unwrap() - 1.0).abs() < 1e-6);
+
+        let single = embedding.to_precision(Precision::F32);
+        assert_eq!(single.as_f32(), Some(&[0.1f32, -0.5, 0.25][..]));
+        assert_eq!(serde_json::to_string(&single).unwrap(), "[0.1,-0.5,0.25]");
+
+        let mut normalized = single.clone();
+        normalized.normalize();
+        assert_eq!(normalized.precision(), Precision::F32);
+        assert!((normalized.norm() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn serialization() {
+        let embedding = serde_json::from_str::<Embedding>("[0.1, -0.5, 0.25]").unwrap();
+        assert_eq!(embedding.as_f64(), Some(&[0.1, -0.5, 0.25][..]));
+        assert_eq!(serde_json::to_string(&embedding).unwrap(), "[0.1,-0.5,0.25]");
+
+        for precision in [Precision::F16, Precision::F32, Precision::F64] {
+            let embedding = embedding.to_precision(precision);
+            let bytes = embedding.to_bytes();
+            assert_eq!(bytes.len(), 5 + embedding.size());
+            assert_eq!(Embedding::from_bytes(&bytes).unwrap(), embedding);
+        }
+
+        let bytes = embedding.to_bytes();
+        assert!(Embedding::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Embedding::from_bytes(&[3, 0, 0, 0, 0]).is_err());
+        assert!(Embedding::from_bytes(&[]).is_err());
+    }
+}
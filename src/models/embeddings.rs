@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Map;
 
+use super::{embedding::Embedding, stats::GenerationStats};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EmbeddingGenerationRequest {
     pub model: String,
@@ -12,5 +14,38 @@ pub struct EmbeddingGenerationRequest {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EmbeddingGenerationResponse {
-    pub embedding: Vec<f64>,
+    pub embedding: Embedding,
+}
+
+/// Input of [`EmbedRequest`], either a single text or a batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EmbedInput {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+/// Embedding request for `/api/embed`
+///
+/// Since Ollama 0.3.4
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbedRequest {
+    pub model: String,
+    pub input: EmbedInput,
+    /// Truncate inputs exceeding the context length instead of failing
+    pub truncate: Option<bool>,
+
+    pub options: Option<Map<String, serde_json::Value>>,
+    pub keep_alive: Option<String>,
+}
+
+/// Embedding response of `/api/embed`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbedResponse {
+    pub model: String,
+    /// One embedding per input, in order
+    pub embeddings: Vec<Embedding>,
+
+    #[serde(flatten)]
+    pub stats: GenerationStats,
 }
@@ -1,4 +1,7 @@
 #[derive(Debug)]
 pub enum ParsingError {
+    InvalidDigest,
+    /// Bytes are not an encoded [`Embedding`](super::embedding::Embedding)
+    InvalidEmbedding,
     InvalidStr,
 }
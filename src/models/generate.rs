@@ -3,7 +3,7 @@ use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
-use super::RequestFormat;
+use super::{model::Capability, stats::GenerationStats, RequestFormat};
 
 /// Completion JSON request
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,6 +24,23 @@ pub struct GenerationRequest {
     pub keep_alive: Option<String>,
 }
 
+impl GenerationRequest {
+    /// Capabilities the model needs to serve this request
+    pub fn required_capabilities(&self) -> Vec<Capability> {
+        let mut capabilities = vec![Capability::Completion];
+
+        if self.suffix.is_some() {
+            capabilities.push(Capability::Insert);
+        }
+
+        if self.images.as_ref().is_some_and(|images| !images.is_empty()) {
+            capabilities.push(Capability::Vision);
+        }
+
+        capabilities
+    }
+}
+
 /// Completion JSON response
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GenerationResponse {
@@ -37,12 +54,9 @@ pub struct GenerationResponse {
     pub response: String,
     pub done: bool,
     
-    pub total_duration: Option<u64>,
-    pub load_duration: Option<u64>,
-    pub prompt_eval_count: Option<usize>,
-    pub prompt_eval_duration: Option<u64>,
-    pub eval_count: Option<usize>,
-    pub eval_duration: Option<u64>,
+    /// Performance stats, sent with the final chunk
+    #[serde(flatten)]
+    pub stats: GenerationStats,
 
     pub context: Option<Vec<u32>>,
 }
@@ -44,10 +44,10 @@ mod tests {
 
     #[test]
     fn def_function_schema() {
-        const FUNC_NAME: &'static str = "query_weather";
-        const FUNC_DESC: &'static str = "Get current weather in a specified location.";
+        const FUNC_NAME: &str = "query_weather";
+        const FUNC_DESC: &str = "Get current weather in a specified location.";
 
-        const LOC_DESC: &'static str = "Keywords of the location.";
+        const LOC_DESC: &str = "Keywords of the location.";
 
         let obj = serde_json::from_value::<JsonSchema>(serde_json::json!({
             "type": "function",
@@ -72,10 +72,10 @@ mod tests {
         if let JsonSchema::Function { function } = obj {
             assert_eq!(function.name, FUNC_NAME);
 
-            assert!(matches!(function.description, Some(_)));
+            assert!(function.description.is_some());
             assert_eq!(function.description.unwrap(), FUNC_DESC);
 
-            assert!(matches!(function.parameters, Some(_)));
+            assert!(function.parameters.is_some());
 
             if let Some(boxed_schema) = function.parameters {
                 let param_schema = *boxed_schema;
@@ -83,20 +83,20 @@ mod tests {
                 assert!(matches!(param_schema, JsonSchema::Object { .. }));
                 if let JsonSchema::Object { properties, required } = param_schema {
                     let location_schema = properties.get("location");
-                    assert!(matches!(location_schema, Some(_)));
+                    assert!(location_schema.is_some());
                     if let Some(location_schema) = location_schema {
                         assert!(matches!(location_schema, JsonSchema::String { .. }));
                         if let JsonSchema::String { description, enumeration } = location_schema {
-                            assert!(matches!(description, Some(_)));
+                            assert!(description.is_some());
                             if let Some(description) = description {
                                 assert_eq!(description, LOC_DESC);
                             }
 
-                            assert!(matches!(enumeration, None));
+                            assert!(enumeration.is_none());
                         }
                     }
 
-                    assert!(matches!(required, Some(_)));
+                    assert!(required.is_some());
                     if let Some(required_fields) = required {
                         assert_eq!(required_fields.len(), 1);
                         assert_eq!(required_fields[0], "location");
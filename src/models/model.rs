@@ -1,8 +1,12 @@
+use std::{fmt::Display, str::FromStr};
+
 #[cfg(feature = "chrono")]
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 use serde_json::Map;
 
+use super::{digest::Digest, errors::ParsingError, model_info::ModelInfo, options::ModelOptions};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ModelDetails {
     pub parent_model: Option<String>,
@@ -23,7 +27,7 @@ pub struct Model {
     pub modified_at: String,
 
     pub size: usize,
-    pub digest: String,
+    pub digest: Digest,
     pub details: ModelDetails,
 }
 
@@ -38,15 +42,121 @@ pub struct ModelShowRequest {
     pub verbose: Option<bool>,
 }
 
+/// Model capability
+///
+/// Since Ollama 0.6.4
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Capability {
+    Completion,
+    Tools,
+    /// Fill-in-the-middle (`suffix` in generation requests)
+    Insert,
+    /// Image input
+    Vision,
+    Embedding,
+    Thinking,
+    /// Capability unknown to this library
+    #[serde(other)]
+    Unknown,
+}
+
+impl Capability {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Completion => "completion",
+            Self::Tools => "tools",
+            Self::Insert => "insert",
+            Self::Vision => "vision",
+            Self::Embedding => "embedding",
+            Self::Thinking => "thinking",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+impl AsRef<str> for Capability {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Capability {
+    type Err = ParsingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "completion" => Self::Completion,
+            "tools" => Self::Tools,
+            "insert" => Self::Insert,
+            "vision" => Self::Vision,
+            "embedding" => Self::Embedding,
+            "thinking" => Self::Thinking,
+            // Same as deserializing, so that `to_string().parse()` round-trips
+            _ => Self::Unknown,
+        })
+    }
+}
+
+/// Tensor information (verbose `/api/show` only)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TensorInfo {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub tensor_type: String,
+    pub shape: Vec<u64>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ModelShowResponse {
     pub modelfile: Option<String>,
     pub parameters: Option<String>,
     pub template: Option<String>,
+    pub license: Option<String>,
+    pub system: Option<String>,
 
     pub details: Option<ModelDetails>,
 
     pub model_info: Option<Map<String, serde_json::Value>>,
+    /// Vision projector information of multimodal models
+    pub projector_info: Option<Map<String, serde_json::Value>>,
+    pub tensors: Option<Vec<TensorInfo>>,
+
+    /// Since Ollama 0.6.4
+    pub capabilities: Option<Vec<Capability>>,
+
+    #[cfg(feature = "chrono")]
+    pub modified_at: Option<DateTime<Local>>,
+    #[cfg(not(feature = "chrono"))]
+    pub modified_at: Option<String>,
+}
+
+impl ModelShowResponse {
+    /// Parse `parameters` into typed options
+    ///
+    /// Returns default options if the model has no parameters.
+    pub fn options(&self) -> Result<ModelOptions, ParsingError> {
+        self.parameters.as_deref().map_or_else(|| Ok(ModelOptions::default()), str::parse)
+    }
+
+    /// Typed view over `model_info`
+    pub fn info(&self) -> Option<ModelInfo<'_>> {
+        self.model_info.as_ref().map(ModelInfo::new)
+    }
+
+    /// Check if the model has a capability
+    ///
+    /// Always `false` for servers not reporting capabilities.
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.capabilities.as_ref().is_some_and(|capabilities| capabilities.contains(&capability))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -69,7 +179,7 @@ pub struct ModelSyncRequest {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ModelDownloadStatus {
-    pub digest: String,
+    pub digest: Digest,
     pub total: usize,
     pub completed: Option<usize>,
 }
@@ -83,7 +193,7 @@ pub struct ModelPullStatus {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ModelUploadStatus {
-    pub digest: String,
+    pub digest: Digest,
     pub total: usize,
 }
 
@@ -99,7 +209,7 @@ pub struct RunningModel {
     pub name: String,
     pub model: String,
     pub size: usize,
-    pub digest: String,
+    pub digest: Digest,
 
     pub details: ModelDetails,
 
@@ -115,3 +225,38 @@ pub struct RunningModel {
 pub struct RunningModelResponse {
     pub models: Vec<RunningModel>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn show_response_with_capabilities() {
+        let res = serde_json::from_value::<ModelShowResponse>(serde_json::json!({
+            "modelfile": "FROM llava",
+            "license": "Apache License",
+            "capabilities": ["completion", "vision", "teleportation"],
+            "modified_at": "2025-04-29T18:00:52.164466473+08:00",
+            "projector_info": {
+                "clip.has_vision_encoder": true,
+            },
+            "tensors": [
+                { "name": "token_embd.weight", "type": "Q4_K", "shape": [4096, 32000] },
+            ],
+        })).unwrap();
+
+        assert_eq!(res.capabilities.as_deref(), Some(&[Capability::Completion, Capability::Vision, Capability::Unknown][..]));
+        assert!(res.supports(Capability::Vision));
+        assert!(!res.supports(Capability::Tools));
+        assert_eq!(res.tensors.unwrap()[0].shape, vec![4096, 32000]);
+    }
+
+    #[test]
+    fn capability_round_trip() {
+        for capability in [Capability::Completion, Capability::Tools, Capability::Insert, Capability::Vision, Capability::Embedding, Capability::Thinking, Capability::Unknown] {
+            assert_eq!(capability.to_string().parse::<Capability>().unwrap(), capability);
+        }
+
+        assert_eq!("teleportation".parse::<Capability>().unwrap(), Capability::Unknown);
+    }
+}
@@ -0,0 +1,167 @@
+use serde_json::{Map, Value};
+
+/// Typed view over the `model_info` map returned by `/api/show`
+///
+/// Architecture-specific keys (e.g. `llama.context_length`,
+/// `qwen2.context_length`) are looked up regardless of the prefix.
+#[derive(Debug, Clone, Copy)]
+pub struct ModelInfo<'a> {
+    map: &'a Map<String, Value>,
+}
+
+/// Tokenizer information in `model_info`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenizerInfo {
+    /// Tokenizer model (e.g. `gpt2`, `llama`)
+    pub model: Option<String>,
+    /// Pre-tokenizer type
+    pub pre: Option<String>,
+    pub bos_token_id: Option<u64>,
+    pub eos_token_id: Option<u64>,
+    pub padding_token_id: Option<u64>,
+    /// Only available with verbose `/api/show` requests
+    pub vocab_size: Option<usize>,
+}
+
+impl<'a> ModelInfo<'a> {
+    pub fn new(map: &'a Map<String, Value>) -> Self {
+        Self { map }
+    }
+
+    /// Raw value of a key
+    pub fn get(&self, key: &str) -> Option<&'a Value> {
+        self.map.get(key)
+    }
+
+    /// Model architecture (`general.architecture`)
+    pub fn architecture(&self) -> Option<&'a str> {
+        self.get("general.architecture")?.as_str()
+    }
+
+    /// Model file type (`general.file_type`)
+    pub fn file_type(&self) -> Option<u64> {
+        self.get("general.file_type")?.as_u64()
+    }
+
+    /// Number of parameters (`general.parameter_count`)
+    pub fn parameter_count(&self) -> Option<u64> {
+        self.get("general.parameter_count")?.as_u64()
+    }
+
+    /// Quantization version (`general.quantization_version`)
+    pub fn quantization_version(&self) -> Option<u64> {
+        self.get("general.quantization_version")?.as_u64()
+    }
+
+    /// Value of an architecture-specific key, e.g. `context_length` for
+    /// `llama.context_length`
+    pub fn arch_value(&self, suffix: &str) -> Option<&'a Value> {
+        if let Some(value) = self.architecture().and_then(|arch| self.get(&format!("{arch}.{suffix}"))) {
+            return Some(value);
+        }
+
+        self.map.iter()
+            .find(|(key, _)| {
+                key.split_once('.').is_some_and(|(prefix, rest)| {
+                    rest == suffix && prefix != "general" && prefix != "tokenizer"
+                })
+            })
+            .map(|(_, value)| value)
+    }
+
+    /// Maximum context length
+    pub fn context_length(&self) -> Option<u64> {
+        self.arch_u64("context_length")
+    }
+
+    /// Embedding length
+    pub fn embedding_length(&self) -> Option<u64> {
+        self.arch_u64("embedding_length")
+    }
+
+    /// Number of blocks (layers)
+    pub fn block_count(&self) -> Option<u64> {
+        self.arch_u64("block_count")
+    }
+
+    /// Feed forward length
+    pub fn feed_forward_length(&self) -> Option<u64> {
+        self.arch_u64("feed_forward_length")
+    }
+
+    /// Number of attention heads
+    pub fn head_count(&self) -> Option<u64> {
+        self.arch_u64("attention.head_count")
+    }
+
+    /// Number of key/value attention heads
+    pub fn head_count_kv(&self) -> Option<u64> {
+        self.arch_u64("attention.head_count_kv")
+    }
+
+    /// Tokenizer information
+    pub fn tokenizer(&self) -> TokenizerInfo {
+        TokenizerInfo {
+            model: self.get("tokenizer.ggml.model").and_then(Value::as_str).map(str::to_string),
+            pre: self.get("tokenizer.ggml.pre").and_then(Value::as_str).map(str::to_string),
+            bos_token_id: self.get("tokenizer.ggml.bos_token_id").and_then(Value::as_u64),
+            eos_token_id: self.get("tokenizer.ggml.eos_token_id").and_then(Value::as_u64),
+            padding_token_id: self.get("tokenizer.ggml.padding_token_id").and_then(Value::as_u64),
+            vocab_size: self.get("tokenizer.ggml.tokens").and_then(Value::as_array).map(Vec::len),
+        }
+    }
+
+    /// Some architectures store per-layer values as arrays; the maximum is taken
+    fn arch_u64(&self, suffix: &str) -> Option<u64> {
+        match self.arch_value(suffix)? {
+            Value::Array(values) => values.iter().filter_map(Value::as_u64).max(),
+            value => value.as_u64(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_model_info() {
+        let map = serde_json::from_value::<Map<String, Value>>(serde_json::json!({
+            "general.architecture": "qwen2",
+            "general.parameter_count": 494032768,
+            "qwen2.context_length": 32768,
+            "qwen2.embedding_length": 896,
+            "qwen2.attention.head_count": 14,
+            "qwen2.attention.head_count_kv": 2,
+            "tokenizer.ggml.model": "gpt2",
+            "tokenizer.ggml.eos_token_id": 151645,
+            "tokenizer.ggml.tokens": ["a", "b", "c"],
+        })).unwrap();
+
+        let info = ModelInfo::new(&map);
+
+        assert_eq!(info.architecture(), Some("qwen2"));
+        assert_eq!(info.parameter_count(), Some(494032768));
+        assert_eq!(info.context_length(), Some(32768));
+        assert_eq!(info.embedding_length(), Some(896));
+        assert_eq!(info.head_count(), Some(14));
+        assert_eq!(info.head_count_kv(), Some(2));
+        assert_eq!(info.tokenizer().model.as_deref(), Some("gpt2"));
+        assert_eq!(info.tokenizer().eos_token_id, Some(151645));
+        assert_eq!(info.tokenizer().vocab_size, Some(3));
+    }
+
+    #[test]
+    fn fall_back_without_architecture() {
+        let map = serde_json::from_value::<Map<String, Value>>(serde_json::json!({
+            "openelm.context_length": 2048,
+            "openelm.attention.head_count": [12, 12, 16, 20],
+        })).unwrap();
+
+        let info = ModelInfo::new(&map);
+
+        assert_eq!(info.context_length(), Some(2048));
+        assert_eq!(info.head_count(), Some(20));
+        assert_eq!(info.block_count(), None);
+    }
+}
@@ -0,0 +1,145 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::errors::ParsingError;
+
+/// Model options
+///
+/// Known [model parameters](https://github.com/ollama/ollama/blob/main/docs/modelfile.md#valid-parameters-and-values)
+/// are typed; anything else ends up in `extra`.
+///
+/// Can be parsed from the `parameters` text returned by `/api/show`
+/// (see [`super::model::ModelShowResponse::options()`]).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelOptions {
+    pub num_ctx: Option<u32>,
+    pub num_keep: Option<i32>,
+    pub num_predict: Option<i32>,
+    pub num_batch: Option<u32>,
+    pub num_gpu: Option<i32>,
+    pub main_gpu: Option<u32>,
+    pub num_thread: Option<u32>,
+    pub use_mmap: Option<bool>,
+    pub seed: Option<i64>,
+
+    pub temperature: Option<f64>,
+    pub top_k: Option<u32>,
+    pub top_p: Option<f64>,
+    pub min_p: Option<f64>,
+    pub typical_p: Option<f64>,
+
+    pub repeat_last_n: Option<i32>,
+    pub repeat_penalty: Option<f64>,
+    pub presence_penalty: Option<f64>,
+    pub frequency_penalty: Option<f64>,
+    pub penalize_newline: Option<bool>,
+
+    pub mirostat: Option<u8>,
+    pub mirostat_tau: Option<f64>,
+    pub mirostat_eta: Option<f64>,
+
+    /// Stop sequences
+    ///
+    /// A parameter that may be given multiple times.
+    pub stop: Option<Vec<String>>,
+
+    /// Other options
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl ModelOptions {
+    /// Parameters which may be given multiple times
+    pub const MULTI_VALUED: &'static [&'static str] = &["stop"];
+
+    /// Convert into the JSON map used by the `options` field of requests
+    pub fn into_map(self) -> Map<String, Value> {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(map)) => map.into_iter().filter(|(_, value)| !value.is_null()).collect(),
+            _ => Map::new(),
+        }
+    }
+}
+
+impl FromStr for ModelOptions {
+    type Err = ParsingError;
+
+    /// Parse the `parameters` text returned by `/api/show`
+    ///
+    /// Each line holds a parameter name and a value separated by whitespace.
+    /// Quoted values are unquoted, and numbers and booleans are typed.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut map = Map::new();
+
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (name, raw) = line.split_once(char::is_whitespace).ok_or(ParsingError::InvalidStr)?;
+            let raw = raw.trim();
+
+            let value = serde_json::from_str::<Value>(raw)
+                .ok()
+                .filter(|value| value.is_string() || value.is_number() || value.is_boolean());
+
+            if Self::MULTI_VALUED.contains(&name) {
+                // Multi-valued parameters are lists of strings, even when a value looks like a number
+                let value = match value {
+                    Some(Value::String(value)) => value,
+                    _ => raw.to_string(),
+                };
+
+                match map.entry(name.to_string()).or_insert_with(|| Value::Array(Vec::new())) {
+                    Value::Array(values) => values.push(Value::String(value)),
+                    _ => return Err(ParsingError::InvalidStr),
+                }
+            } else {
+                let value = value.unwrap_or_else(|| Value::String(raw.to_string()));
+                map.insert(name.to_string(), value);
+            }
+        }
+
+        serde_json::from_value(Value::Object(map)).map_err(|_| ParsingError::InvalidStr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_shown_parameters() {
+        let options: ModelOptions = r#"num_ctx                        4096
+stop                           "<|start_header_id|>"
+stop                           "<|end_header_id|>"
+stop                           "<|eot_id|>"
+temperature                    1
+penalize_newline               false
+custom_thing                   foo bar"#.parse().unwrap();
+
+        assert_eq!(options.num_ctx, Some(4096));
+        assert_eq!(options.temperature, Some(1.0));
+        assert_eq!(options.penalize_newline, Some(false));
+        assert_eq!(
+            options.stop.as_deref(),
+            Some(&["<|start_header_id|>".to_string(), "<|end_header_id|>".to_string(), "<|eot_id|>".to_string()][..]),
+        );
+        assert_eq!(options.extra.get("custom_thing"), Some(&Value::String("foo bar".to_string())));
+
+        let map = options.into_map();
+        assert_eq!(map.get("num_ctx"), Some(&Value::from(4096)));
+        assert!(!map.contains_key("seed"));
+    }
+
+    #[test]
+    fn reject_mistyped_parameters() {
+        assert!("num_ctx lots".parse::<ModelOptions>().is_err());
+        assert!("num_ctx".parse::<ModelOptions>().is_err());
+    }
+
+    #[test]
+    fn keep_stop_sequences_as_strings() {
+        let options: ModelOptions = "stop 123\nstop \"true\"\nstop ###".parse().unwrap();
+
+        assert_eq!(options.stop, Some(vec!["123".to_string(), "true".to_string(), "###".to_string()]));
+    }
+}
@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Performance stats of a response
+///
+/// Sent by Ollama with the final chunk of generations and chats, and with
+/// embeddings. Durations are in nanoseconds on the wire; use the methods of the
+/// same name to get them as [`Duration`]s.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenerationStats {
+    /// Time spent on the whole request, in nanoseconds
+    pub total_duration: Option<u64>,
+    /// Time spent loading the model, in nanoseconds
+    pub load_duration: Option<u64>,
+    /// Number of tokens in the prompt
+    pub prompt_eval_count: Option<usize>,
+    /// Time spent evaluating the prompt, in nanoseconds
+    pub prompt_eval_duration: Option<u64>,
+    /// Number of generated tokens
+    pub eval_count: Option<usize>,
+    /// Time spent generating, in nanoseconds
+    pub eval_duration: Option<u64>,
+}
+
+impl GenerationStats {
+    pub fn total_duration(&self) -> Option<Duration> {
+        self.total_duration.map(Duration::from_nanos)
+    }
+
+    pub fn load_duration(&self) -> Option<Duration> {
+        self.load_duration.map(Duration::from_nanos)
+    }
+
+    pub fn prompt_eval_duration(&self) -> Option<Duration> {
+        self.prompt_eval_duration.map(Duration::from_nanos)
+    }
+
+    pub fn eval_duration(&self) -> Option<Duration> {
+        self.eval_duration.map(Duration::from_nanos)
+    }
+
+    /// Time spent evaluating the prompt and generating
+    pub fn evaluation_duration(&self) -> Option<Duration> {
+        match (self.prompt_eval_duration(), self.eval_duration()) {
+            (None, None) => None,
+            (prompt, eval) => Some(prompt.unwrap_or_default() + eval.unwrap_or_default()),
+        }
+    }
+
+    /// Share of the total duration spent loading the model, from 0 to 1
+    pub fn load_ratio(&self) -> Option<f64> {
+        ratio(self.load_duration?, self.total_duration?)
+    }
+
+    /// Share of the total duration spent evaluating the prompt and generating, from 0 to 1
+    pub fn evaluation_ratio(&self) -> Option<f64> {
+        ratio(self.evaluation_duration()?.as_nanos() as u64, self.total_duration?)
+    }
+
+    /// Prompt tokens evaluated per second
+    pub fn prompt_tokens_per_second(&self) -> Option<f64> {
+        rate(self.prompt_eval_count?, self.prompt_eval_duration()?)
+    }
+
+    /// Tokens generated per second
+    pub fn tokens_per_second(&self) -> Option<f64> {
+        rate(self.eval_count?, self.eval_duration()?)
+    }
+}
+
+fn ratio(part: u64, total: u64) -> Option<f64> {
+    match total {
+        0 => None,
+        total => Some(part as f64 / total as f64),
+    }
+}
+
+pub(crate) fn rate(count: usize, duration: Duration) -> Option<f64> {
+    match duration.is_zero() {
+        true => None,
+        false => Some(count as f64 / duration.as_secs_f64()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn derived_stats() {
+        let stats = serde_json::from_value::<GenerationStats>(json!({
+            "total_duration": 4_000_000_000u64,
+            "load_duration": 1_000_000_000u64,
+            "prompt_eval_count": 26,
+            "prompt_eval_duration": 500_000_000u64,
+            "eval_count": 250,
+            "eval_duration": 2_500_000_000u64,
+        })).unwrap();
+
+        assert_eq!(stats.load_duration(), Some(Duration::from_secs(1)));
+        assert_eq!(stats.evaluation_duration(), Some(Duration::from_secs(3)));
+        assert_eq!(stats.load_ratio(), Some(0.25));
+        assert_eq!(stats.evaluation_ratio(), Some(0.75));
+        assert_eq!(stats.prompt_tokens_per_second(), Some(52.0));
+        assert_eq!(stats.tokens_per_second(), Some(100.0));
+
+        // Streamed chunks carry no stats
+        let stats = GenerationStats::default();
+        assert_eq!(stats.evaluation_duration(), None);
+        assert_eq!(stats.tokens_per_second(), None);
+    }
+}
@@ -1,6 +1,319 @@
-use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
 
-#[derive(Debug, Serialize, Deserialize)]
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::errors::Error;
+
+use super::errors::ParsingError;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VersionResponse {
-    version: String,
+    pub version: Version,
+}
+
+/// Semver-like Ollama server version
+///
+/// Accepts `MAJOR.MINOR[.PATCH][-PRE][+BUILD]`, with an optional leading `v`.
+/// Pre-release versions (e.g. `0.6.0-rc1`) order before the release, and
+/// numbers within pre-release tags compare numerically (`rc2` < `rc10`).
+/// `git describe` builds (e.g. `0.3.12-6-g1234abc`) order after the version
+/// they were built on top of.
+///
+/// ## Examples
+///
+/// ```rust
+/// use ollama_rest::models::version::Version;
+///
+/// let version: Version = "0.6.0-rc1".parse().unwrap();
+///
+/// assert!(version < Version::new(0, 6, 0));
+/// assert!(version > Version::new(0, 5, 13));
+/// assert!("0.3.12-6-g1234abc".parse::<Version>().unwrap() > Version::new(0, 3, 12));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub pre: Option<String>,
+}
+
+impl Version {
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self { major, minor, patch, pre: None }
+    }
+
+    /// Development builds report `0.0.0`
+    pub fn is_dev(&self) -> bool {
+        self.major == 0 && self.minor == 0 && self.patch == 0
+    }
+
+    /// Split the pre-release part into its tag and the number of commits
+    /// after it, from a `git describe` suffix (`-N-g<sha>`)
+    fn describe(&self) -> (Option<&str>, Option<u64>) {
+        let Some(pre) = self.pre.as_deref() else {
+            return (None, None);
+        };
+
+        let mut parts = pre.rsplitn(3, '-');
+        let sha = parts.next().and_then(|sha| sha.strip_prefix('g'));
+        let commits = parts.next().and_then(|commits| commits.parse::<u64>().ok());
+
+        match (sha, commits) {
+            (Some(sha), Some(commits)) if !sha.is_empty() && sha.chars().all(|c| c.is_ascii_hexdigit()) => {
+                (parts.next(), Some(commits))
+            }
+            _ => (Some(pre), None),
+        }
+    }
+}
+
+/// Compare pre-release tags, with runs of digits compared as numbers
+fn cmp_pre(a: &str, b: &str) -> Ordering {
+    fn chunks(s: &str) -> impl Iterator<Item = &str> {
+        let mut rest = s;
+
+        std::iter::from_fn(move || {
+            let first = rest.chars().next()?;
+            let len = rest.find(|c: char| c.is_ascii_digit() != first.is_ascii_digit()).unwrap_or(rest.len());
+
+            let (chunk, tail) = rest.split_at(len);
+            rest = tail;
+            Some(chunk)
+        })
+    }
+
+    let mut a_chunks = chunks(a);
+    let mut b_chunks = chunks(b);
+
+    loop {
+        let ordering = match (a_chunks.next(), b_chunks.next()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => match (x.parse::<u64>(), y.parse::<u64>()) {
+                (Ok(x), Ok(y)) => x.cmp(&y),
+                _ => x.cmp(y),
+            },
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| {
+                let (tag, commits) = self.describe();
+                let (other_tag, other_commits) = other.describe();
+
+                match (tag, other_tag) {
+                    (None, None) => Ordering::Equal,
+                    (None, Some(_)) => Ordering::Greater,
+                    (Some(_), None) => Ordering::Less,
+                    (Some(a), Some(b)) => cmp_pre(a, b),
+                }
+                .then_with(|| commits.cmp(&other_commits))
+            })
+            // Builds of different commits are distinct, as for `Eq`
+            .then_with(|| self.pre.cmp(&other.pre))
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for Version {
+    type Err = ParsingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = s.strip_prefix('v').unwrap_or(s);
+        let s = s.split_once('+').map_or(s, |(version, _build)| version);
+
+        let (core, pre) = match s.split_once('-') {
+            Some((core, pre)) if !pre.is_empty() => (core, Some(pre.to_string())),
+            Some(_) => Err(ParsingError::InvalidStr)?,
+            None => (s, None),
+        };
+
+        let mut parts = core.split('.').map(|part| part.parse::<u64>().map_err(|_| ParsingError::InvalidStr));
+
+        let major = parts.next().ok_or(ParsingError::InvalidStr)??;
+        let minor = parts.next().ok_or(ParsingError::InvalidStr)??;
+        let patch = parts.next().transpose()?.unwrap_or(0);
+
+        if parts.next().is_some() {
+            Err(ParsingError::InvalidStr)?;
+        }
+
+        Ok(Self { major, minor, patch, pre })
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+
+        if let Some(pre) = &self.pre {
+            write!(f, "-{pre}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Serialize for Version {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(|_| D::Error::custom(format!("invalid version: {s}")))
+    }
+}
+
+/// Server feature which depends on the Ollama version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ServerFeature {
+    /// Tool calling in chat requests
+    Tools,
+    /// `/api/embed` endpoint (batch embeddings)
+    Embed,
+    /// JSON schemas in `format`
+    StructuredOutputs,
+    /// `model`/`from`/`files` form of `/api/create`
+    NewCreateApi,
+    /// `capabilities` in `/api/show` responses
+    Capabilities,
+    /// `think` in chat requests
+    Thinking,
+}
+
+impl ServerFeature {
+    /// First server version supporting the feature
+    pub fn min_version(&self) -> Version {
+        match self {
+            Self::Tools => Version::new(0, 3, 0),
+            Self::Embed => Version::new(0, 3, 4),
+            Self::StructuredOutputs => Version::new(0, 5, 0),
+            Self::NewCreateApi => Version::new(0, 5, 5),
+            Self::Capabilities => Version::new(0, 6, 4),
+            Self::Thinking => Version::new(0, 9, 0),
+        }
+    }
+}
+
+impl Display for ServerFeature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Self::Tools => "tools",
+            Self::Embed => "/api/embed",
+            Self::StructuredOutputs => "structured outputs",
+            Self::NewCreateApi => "new /api/create",
+            Self::Capabilities => "capabilities",
+            Self::Thinking => "thinking",
+        })
+    }
+}
+
+/// Features supported by a server, derived from its version
+///
+/// Development builds (`0.0.0`) are assumed to support everything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerFeatures {
+    version: Version,
+}
+
+impl ServerFeatures {
+    pub fn new(version: Version) -> Self {
+        Self { version }
+    }
+
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    pub fn supports(&self, feature: ServerFeature) -> bool {
+        if self.version.is_dev() {
+            return true;
+        }
+
+        // A pre-release of the minimum version is good enough
+        let min = feature.min_version();
+        (self.version.major, self.version.minor, self.version.patch) >= (min.major, min.minor, min.patch)
+    }
+
+    /// Fail fast if the feature is not supported
+    ///
+    /// ## Returns
+    /// - `Ok(())`: Feature supported
+    /// - `Err(Error::UnsupportedFeature { .. })`: Server is too old
+    pub fn require(&self, feature: ServerFeature) -> Result<(), Error> {
+        if self.supports(feature) {
+            Ok(())
+        } else {
+            Err(Error::UnsupportedFeature { feature, version: self.version.clone() })
+        }
+    }
+}
+
+impl From<VersionResponse> for ServerFeatures {
+    fn from(value: VersionResponse) -> Self {
+        Self::new(value.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_versions() {
+        assert_eq!("0.5.7".parse::<Version>().unwrap(), Version::new(0, 5, 7));
+        assert_eq!("v0.6".parse::<Version>().unwrap(), Version::new(0, 6, 0));
+        assert_eq!("0.3.12-6-g1234abc".parse::<Version>().unwrap().pre.as_deref(), Some("6-g1234abc"));
+        assert_eq!("0.1.0+build".parse::<Version>().unwrap(), Version::new(0, 1, 0));
+
+        assert!("".parse::<Version>().is_err());
+        assert!("0".parse::<Version>().is_err());
+        assert!("0.1.2.3".parse::<Version>().is_err());
+        assert!("0.x.1".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn order_versions() {
+        let ordered = ["0.3.11", "0.3.12-rc1", "0.3.12-rc2", "0.3.12-rc10", "0.3.12-rc10-2-gabc1234", "0.3.12", "0.3.12-6-g1234abc", "0.3.12-10-g1234abc", "0.3.13"];
+
+        for pair in ordered.windows(2) {
+            let (a, b) = (pair[0].parse::<Version>().unwrap(), pair[1].parse::<Version>().unwrap());
+            assert!(a < b, "{a} < {b}");
+        }
+
+        assert_ne!("0.5.0-rc01".parse::<Version>().unwrap().cmp(&"0.5.0-rc1".parse().unwrap()), Ordering::Equal);
+        assert_ne!("0.3.12-6-gaaaaaaa".parse::<Version>().unwrap().cmp(&"0.3.12-6-gbbbbbbb".parse().unwrap()), Ordering::Equal);
+    }
+
+    #[test]
+    fn check_features() {
+        let old = ServerFeatures::new(Version::new(0, 2, 8));
+        assert!(!old.supports(ServerFeature::Tools));
+        assert!(!old.supports(ServerFeature::Embed));
+
+        let rc = ServerFeatures::new("0.9.0-rc0".parse().unwrap());
+        assert!(rc.supports(ServerFeature::Thinking));
+        assert!(rc.supports(ServerFeature::NewCreateApi));
+
+        assert!(ServerFeatures::new(Version::new(0, 0, 0)).supports(ServerFeature::Thinking));
+    }
 }
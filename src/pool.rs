@@ -0,0 +1,540 @@
+//! Multi-host client pool
+//!
+//! [`OllamaPool`] spreads requests over several [`Ollama`] instances, skips
+//! hosts failing their health checks, fails over to the next host on
+//! connection errors and stops sending to a host whose circuit breaker is open.
+//!
+//! ## Examples
+//!
+//! ```rust,no_run
+//! use std::str::FromStr;
+//!
+//! use ollama_rest::{models::generate::GenerationRequest, pool::{OllamaPool, Strategy}, Ollama};
+//! use serde_json::json;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), ollama_rest::errors::Error> {
+//! let pool = OllamaPool::new([
+//!     Ollama::from_str("http://gpu-1:11434")?,
+//!     Ollama::from_str("http://gpu-2:11434")?,
+//! ]).with_strategy(Strategy::PreferLoaded);
+//!
+//! pool.check_health().await;
+//!
+//! let request = serde_json::from_value::<GenerationRequest>(json!({
+//!     "model": "llama3.2:1b",
+//!     "prompt": "Why is the sky blue?",
+//!     "stream": false,
+//! })).unwrap();
+//!
+//! let res = pool.generate(&request, None::<fn(&_)>).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    cell::RefCell,
+    collections::BTreeSet,
+    fmt::Display,
+    future::Future,
+    str::FromStr,
+    sync::{atomic::{AtomicUsize, Ordering}, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::{Stream, StreamExt};
+
+use crate::{
+    errors::Error,
+    models::{
+        chat::{ChatRequest, ChatResponse},
+        embeddings::{EmbedRequest, EmbedResponse, EmbeddingGenerationRequest, EmbeddingGenerationResponse},
+        errors::ParsingError,
+        generate::{GenerationRequest, GenerationResponse},
+    },
+    Ollama,
+};
+
+/// How the pool picks a host
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// Take turns
+    #[default]
+    RoundRobin,
+    /// Host with the fewest requests in flight
+    LeastInFlight,
+    /// Host that already has the model loaded (per `running_models`), then fewest in flight
+    PreferLoaded,
+}
+
+impl Strategy {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::RoundRobin => "round_robin",
+            Self::LeastInFlight => "least_in_flight",
+            Self::PreferLoaded => "prefer_loaded",
+        }
+    }
+}
+
+impl AsRef<str> for Strategy {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = ParsingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "round_robin" => Ok(Self::RoundRobin),
+            "least_in_flight" => Ok(Self::LeastInFlight),
+            "prefer_loaded" => Ok(Self::PreferLoaded),
+            _ => Err(ParsingError::InvalidStr),
+        }
+    }
+}
+
+/// Per-host circuit breaker settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive connection failures opening the circuit
+    pub failure_threshold: u32,
+    /// How long an open circuit rejects requests before letting one through again
+    pub cooldown: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// State of a host's circuit breaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests flow normally
+    Closed,
+    /// Requests are rejected until the cooldown ends
+    Open,
+    /// Cooldown ended; the next request decides whether the circuit closes again
+    HalfOpen,
+}
+
+/// Snapshot of a pooled host
+#[derive(Debug, Clone)]
+pub struct HostStatus {
+    pub host: String,
+    /// Result of the last health check (hosts start out healthy)
+    pub healthy: bool,
+    pub circuit: CircuitState,
+    pub in_flight: usize,
+    /// Models loaded as of the last health check or request
+    pub loaded_models: Vec<String>,
+}
+
+#[derive(Debug)]
+struct HostState {
+    healthy: bool,
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    /// A half-open circuit lets a single trial request through
+    trial_in_flight: bool,
+    loaded_models: BTreeSet<String>,
+}
+
+struct PoolHost {
+    index: usize,
+    client: Ollama,
+    in_flight: AtomicUsize,
+    state: Mutex<HostState>,
+}
+
+impl PoolHost {
+    fn circuit(state: &HostState, now: Instant) -> CircuitState {
+        match state.open_until {
+            None => CircuitState::Closed,
+            Some(until) if now < until => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Reserve the host for a request, if its circuit allows it
+    fn acquire(&self, now: Instant) -> Option<InFlight<'_>> {
+        let mut state = self.state.lock().unwrap();
+
+        let trial = match Self::circuit(&state, now) {
+            CircuitState::Closed => false,
+            CircuitState::Open => return None,
+            CircuitState::HalfOpen if state.trial_in_flight => return None,
+            CircuitState::HalfOpen => true,
+        };
+
+        state.trial_in_flight |= trial;
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        Some(InFlight { host: self, trial })
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures = 0;
+        state.open_until = None;
+        state.trial_in_flight = false;
+    }
+
+    fn record_failure(&self, config: &CircuitBreakerConfig) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+        state.trial_in_flight = false;
+
+        if state.open_until.is_some() || state.consecutive_failures >= config.failure_threshold {
+            state.open_until = Some(Instant::now() + config.cooldown);
+        }
+    }
+}
+
+/// Request in flight on a host
+pub(crate) struct InFlight<'a> {
+    host: &'a PoolHost,
+    trial: bool,
+}
+
+impl InFlight<'_> {
+    /// Position of the host in the pool
+    pub fn index(&self) -> usize {
+        self.host.index
+    }
+
+    pub fn client(&self) -> &Ollama {
+        &self.host.client
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.host.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        // A cancelled trial must not keep the circuit half-open forever
+        if self.trial {
+            self.host.state.lock().unwrap().trial_in_flight = false;
+        }
+    }
+}
+
+/// Model names as listed by `running_models`, where the tag defaults to `latest`
+pub(crate) fn canonical_model(name: &str) -> String {
+    match name.rsplit_once('/').map_or(name, |(_, last)| last).contains(':') {
+        true => name.to_string(),
+        false => format!("{name}:latest"),
+    }
+}
+
+/// Errors worth retrying on another host
+fn is_connection_error(err: &Error) -> bool {
+    match err {
+        Error::ClientCreation(err) => err.is_connect() || err.is_timeout(),
+        _ => false,
+    }
+}
+
+/// Pool of Ollama hosts
+pub struct OllamaPool {
+    hosts: Vec<PoolHost>,
+    strategy: Strategy,
+    circuit_breaker: CircuitBreakerConfig,
+    health_check_timeout: Duration,
+    next: AtomicUsize,
+}
+
+impl OllamaPool {
+    pub fn new<I: IntoIterator<Item = Ollama>>(hosts: I) -> Self {
+        Self {
+            hosts: hosts.into_iter()
+                .enumerate()
+                .map(|(index, client)| PoolHost {
+                    index,
+                    client,
+                    in_flight: AtomicUsize::new(0),
+                    state: Mutex::new(HostState {
+                        healthy: true,
+                        consecutive_failures: 0,
+                        open_until: None,
+                        trial_in_flight: false,
+                        loaded_models: BTreeSet::new(),
+                    }),
+                })
+                .collect(),
+            strategy: Strategy::default(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            health_check_timeout: Duration::from_secs(5),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = config;
+        self
+    }
+
+    /// Time a host has to answer a health check
+    pub fn with_health_check_timeout(mut self, timeout: Duration) -> Self {
+        self.health_check_timeout = timeout;
+        self
+    }
+
+    /// Pooled clients, in the order they were given
+    pub fn hosts(&self) -> impl Iterator<Item = &Ollama> {
+        self.hosts.iter().map(|host| &host.client)
+    }
+
+    /// Current state of every host
+    pub fn status(&self) -> Vec<HostStatus> {
+        let now = Instant::now();
+
+        self.hosts.iter()
+            .map(|host| {
+                let state = host.state.lock().unwrap();
+                HostStatus {
+                    host: host.client.host().to_string(),
+                    healthy: state.healthy,
+                    circuit: PoolHost::circuit(&state, now),
+                    in_flight: host.in_flight.load(Ordering::SeqCst),
+                    loaded_models: state.loaded_models.iter().cloned().collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Check every host with `version()` and refresh its loaded models
+    ///
+    /// Hosts failing the check are skipped until a later check succeeds,
+    /// unless no healthy host is left.
+    pub async fn check_health(&self) {
+        futures::future::join_all(self.hosts.iter().map(|host| async move {
+            let check = async {
+                host.client.version().await?;
+
+                match host.client.running_models().await {
+                    Ok(running) => Ok(Some(running)),
+                    // Reachable, but `/api/ps` may be unsupported
+                    Err(Error::JsonDecoding(_)) => Ok(None),
+                    Err(err) => Err(err),
+                }
+            };
+
+            let running = tokio::time::timeout(self.health_check_timeout, check).await;
+
+            let mut state = host.state.lock().unwrap();
+            state.healthy = match running {
+                Ok(Ok(running)) => {
+                    if let Some(running) = running {
+                        state.loaded_models = running.models.into_iter().map(|model| model.name).collect();
+                    }
+                    true
+                },
+                _ => false,
+            };
+        })).await;
+    }
+
+    /// Run [`OllamaPool::check_health`] forever, every `interval`
+    ///
+    /// Meant to be spawned alongside the pool, e.g. with an `Arc<OllamaPool>`.
+    pub async fn run_health_checks(&self, interval: Duration) {
+        loop {
+            self.check_health().await;
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Hosts in the order they should be tried
+    fn candidates(&self, model: Option<&str>, eligible: &dyn Fn(usize) -> bool) -> Vec<&PoolHost> {
+        let len = self.hosts.len();
+        if len == 0 {
+            return Vec::new();
+        }
+
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % len;
+        let mut hosts: Vec<&PoolHost> = self.hosts[start..].iter()
+            .chain(&self.hosts[..start])
+            .filter(|host| eligible(host.index))
+            .collect();
+
+        let model = model.map(canonical_model);
+        let in_flight = |host: &PoolHost| host.in_flight.load(Ordering::SeqCst);
+        let loaded = |host: &PoolHost| model.as_ref().is_some_and(|model| host.state.lock().unwrap().loaded_models.contains(model));
+
+        // Stable sorts keep the round-robin rotation as a tie breaker
+        match self.strategy {
+            Strategy::RoundRobin => {},
+            Strategy::LeastInFlight => hosts.sort_by_key(|host| in_flight(host)),
+            Strategy::PreferLoaded => hosts.sort_by_key(|host| (!loaded(host), in_flight(host))),
+        }
+
+        // Unhealthy hosts are a last resort
+        hosts.sort_by_key(|host| !host.state.lock().unwrap().healthy);
+        hosts
+    }
+
+    /// Run `f` against the chosen host among the `eligible` ones, failing over on connection errors
+    pub(crate) async fn dispatch<'a, F, Fut, T>(&'a self, model: Option<&str>, eligible: &dyn Fn(usize) -> bool, mut f: F) -> Result<(T, InFlight<'a>), Error>
+    where
+        F: FnMut(&'a Ollama) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let mut last_err = None;
+
+        for host in self.candidates(model, eligible) {
+            let Some(guard) = host.acquire(Instant::now()) else {
+                continue;
+            };
+
+            match f(&host.client).await {
+                Err(err) if is_connection_error(&err) => {
+                    host.record_failure(&self.circuit_breaker);
+                    last_err = Some(err);
+                },
+                res => {
+                    // The host answered, even if with an error
+                    host.record_success();
+                    if let (Ok(_), Some(model)) = (&res, model) {
+                        host.state.lock().unwrap().loaded_models.insert(canonical_model(model));
+                    }
+
+                    return res.map(|res| (res, guard));
+                },
+            }
+        }
+
+        Err(last_err.unwrap_or(Error::NoAvailableHost))
+    }
+
+    /// Run a request against the pool
+    ///
+    /// ## Parameters
+    /// - `model`: Model the request is for, used by [`Strategy::PreferLoaded`]
+    /// - `f`: Request to run, called again with the next host on connection errors
+    ///
+    /// ## Returns
+    /// The first response, or the last connection error if every host failed
+    pub async fn execute<'a, F, Fut, T>(&'a self, model: Option<&str>, f: F) -> Result<T, Error>
+    where
+        F: FnMut(&'a Ollama) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        Ok(self.dispatch(model, &|_| true, f).await?.0)
+    }
+
+    /// Generate completion response for one single prompt (Callback API)
+    pub async fn generate<T>(&self, request: &GenerationRequest, on_stream: Option<T>) -> Result<GenerationResponse, Error>
+    where
+        T: FnMut(&GenerationResponse),
+    {
+        // Shared by every attempt
+        let on_stream = on_stream.map(RefCell::new);
+        let on_stream = &on_stream;
+
+        self.execute(Some(&request.model), |ollama| {
+            ollama.generate(request, on_stream.as_ref().map(|f| move |res: &GenerationResponse| f.borrow_mut()(res)))
+        }).await
+    }
+
+    /// Generate completion response for one single prompt (Stream API)
+    pub async fn generate_streamed(&self, request: &GenerationRequest) -> Result<impl Stream<Item = Result<GenerationResponse, Error>> + '_, Error> {
+        let (stream, guard) = self.dispatch(Some(&request.model), &|_| true, |ollama| ollama.generate_streamed(request)).await?;
+
+        Ok(stream.map(move |res| {
+            let _ = &guard;
+            res
+        }))
+    }
+
+    /// Generate chat completion response (Callback API)
+    pub async fn chat<T>(&self, request: &ChatRequest, on_stream: Option<T>) -> Result<ChatResponse, Error>
+    where
+        T: FnMut(&ChatResponse),
+    {
+        // Shared by every attempt
+        let on_stream = on_stream.map(RefCell::new);
+        let on_stream = &on_stream;
+
+        self.execute(Some(&request.model), |ollama| {
+            ollama.chat(request, on_stream.as_ref().map(|f| move |res: &ChatResponse| f.borrow_mut()(res)))
+        }).await
+    }
+
+    /// Generate chat completion response (Stream API)
+    pub async fn chat_streamed(&self, request: &ChatRequest) -> Result<impl Stream<Item = Result<ChatResponse, Error>> + '_, Error> {
+        let (stream, guard) = self.dispatch(Some(&request.model), &|_| true, |ollama| ollama.chat_streamed(request)).await?;
+
+        Ok(stream.map(move |res| {
+            let _ = &guard;
+            res
+        }))
+    }
+
+    /// Generate embeddings
+    pub async fn generate_embeddings(&self, request: &EmbeddingGenerationRequest) -> Result<EmbeddingGenerationResponse, Error> {
+        self.execute(Some(&request.model), |ollama| ollama.generate_embeddings(request)).await
+    }
+
+    /// Generate embeddings for one or more inputs
+    pub async fn embed(&self, request: &EmbedRequest) -> Result<EmbedResponse, Error> {
+        self.execute(Some(&request.model), |ollama| ollama.embed(request)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_model_names() {
+        assert_eq!(canonical_model("llama3.2"), "llama3.2:latest");
+        assert_eq!(canonical_model("llama3.2:1b"), "llama3.2:1b");
+        assert_eq!(canonical_model("registry.local:5000/library/llama3.2"), "registry.local:5000/library/llama3.2:latest");
+    }
+
+    #[test]
+    fn circuit_breaker() {
+        let pool = OllamaPool::new([Ollama::default()])
+            .with_circuit_breaker(CircuitBreakerConfig { failure_threshold: 2, cooldown: Duration::from_millis(50) });
+        let host = &pool.hosts[0];
+        let now = Instant::now();
+
+        host.record_failure(&pool.circuit_breaker);
+        assert!(host.acquire(now).is_some());
+
+        host.record_failure(&pool.circuit_breaker);
+        assert_eq!(pool.status()[0].circuit, CircuitState::Open);
+        assert!(host.acquire(Instant::now()).is_none());
+
+        // Half-open lets one trial through, whose failure reopens the circuit
+        let later = Instant::now() + Duration::from_millis(60);
+        let trial = host.acquire(later).unwrap();
+        assert!(host.acquire(later).is_none());
+        host.record_failure(&pool.circuit_breaker);
+        drop(trial);
+        assert!(host.acquire(Instant::now()).is_none());
+
+        host.record_success();
+        assert_eq!(pool.status()[0].circuit, CircuitState::Closed);
+        assert_eq!(pool.status()[0].in_flight, 0);
+    }
+}
@@ -0,0 +1,741 @@
+//! Retrieval-augmented generation
+//!
+//! [`RagPipeline`] splits documents with a [`Chunker`], embeds the chunks with
+//! an embedding model and keeps them in a [`VectorStore`]. Questions are
+//! embedded the same way; the closest chunks are numbered and handed to a chat
+//! model, which is asked to cite them with markers like `[1]`. The answer comes
+//! back with the chunks it was given and the ones it cited.
+//!
+//! Requires the `rag` feature.
+//!
+//! ## Examples
+//!
+//! ```rust,no_run
+//! use ollama_rest::{
+//!     rag::{Chunker, RagPipeline},
+//!     vector::Metadata,
+//!     Ollama,
+//! };
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), ollama_rest::errors::Error> {
+//! let mut rag = RagPipeline::new(Ollama::default(), "nomic-embed-text", "llama3.2")
+//!     .with_chunker(Chunker::markdown(1000, 1))
+//!     .with_top_k(3);
+//!
+//! let readme = std::fs::read_to_string("README.md")?;
+//! rag.add_document("README.md", &readme, Metadata::new()).await?;
+//!
+//! let answer = rag.ask("How do I pull a model?", None).await?;
+//! println!("{}", answer.answer);
+//!
+//! for chunk in answer.cited() {
+//!     println!("- {} ({:.3})", chunk.id, chunk.score);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ops::Range;
+
+use serde_json::{Map, Value};
+
+use crate::{
+    errors::Error,
+    models::{
+        chat::{ChatRequest, ChatResponse, Message, Role},
+        embedding::Embedding,
+        embeddings::{EmbedInput, EmbedRequest},
+    },
+    vector::{Document, Filter, Metadata, VectorStore},
+    Ollama,
+};
+
+/// Chunks embedded per request
+const EMBED_BATCH: usize = 32;
+
+const DEFAULT_INSTRUCTIONS: &str = "Answer the question using only the numbered context passages below. \
+Cite every passage you use with its number in square brackets, like [1]. \
+If the context does not contain the answer, say so.";
+
+/// Piece of a chunked text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    /// Text of the chunk, without leading and trailing whitespace
+    pub text: String,
+    /// Byte offset of the chunk in the original text
+    pub start: usize,
+    /// Byte offset of the end of the chunk in the original text
+    pub end: usize,
+    /// Headings of the Markdown section the chunk belongs to, joined with ` > `
+    pub heading: Option<String>,
+}
+
+/// Text splitting strategy
+///
+/// Sizes are counted in characters. Chunks never contain leading or trailing
+/// whitespace, and whitespace-only chunks are dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chunker {
+    /// Fixed windows of `size` characters, the last `overlap` of which start the next window
+    Characters { size: usize, overlap: usize },
+    /// Runs of whole sentences up to `max_chars`, the last `overlap` sentences of which start the next run
+    ///
+    /// Paragraph breaks also end sentences. Sentences longer than `max_chars`
+    /// are split into windows of characters; closing punctuation that would be
+    /// left on its own stays with the last window instead.
+    Sentences { max_chars: usize, overlap: usize },
+    /// Markdown sections, split at headings
+    ///
+    /// Sections longer than `max_chars` are split like [`Chunker::Sentences`].
+    /// Headings inside fenced code blocks are ignored.
+    Markdown { max_chars: usize, overlap: usize },
+}
+
+impl Default for Chunker {
+    fn default() -> Self {
+        Self::Sentences { max_chars: 1000, overlap: 1 }
+    }
+}
+
+impl Chunker {
+    pub fn characters(size: usize, overlap: usize) -> Self {
+        Self::Characters { size, overlap }
+    }
+
+    pub fn sentences(max_chars: usize, overlap: usize) -> Self {
+        Self::Sentences { max_chars, overlap }
+    }
+
+    pub fn markdown(max_chars: usize, overlap: usize) -> Self {
+        Self::Markdown { max_chars, overlap }
+    }
+
+    /// Split `text` into chunks, in order
+    pub fn chunk(&self, text: &str) -> Vec<TextChunk> {
+        let mut chunks = Vec::new();
+
+        match *self {
+            Self::Characters { size, overlap } => characters(text, 0, size, overlap, None, &mut chunks),
+            Self::Sentences { max_chars, overlap } => sentences(text, 0, max_chars, overlap, None, &mut chunks),
+            Self::Markdown { max_chars, overlap } => {
+                for (section, heading) in sections(text) {
+                    let section_text = &text[section.clone()];
+
+                    if section_text.chars().count() <= max_chars.max(1) {
+                        push(section_text, section.start, 0..section_text.len(), heading.as_deref(), &mut chunks);
+                    } else {
+                        sentences(section_text, section.start, max_chars, overlap, heading.as_deref(), &mut chunks);
+                    }
+                }
+            }
+        }
+
+        chunks
+    }
+}
+
+/// Push `text[range]`, trimmed, unless it is blank
+fn push(text: &str, offset: usize, range: Range<usize>, heading: Option<&str>, chunks: &mut Vec<TextChunk>) {
+    let range = trim(text, range);
+    if range.is_empty() {
+        return;
+    }
+
+    chunks.push(TextChunk {
+        text: text[range.clone()].to_owned(),
+        start: offset + range.start,
+        end: offset + range.end,
+        heading: heading.map(str::to_owned),
+    });
+}
+
+fn trim(text: &str, range: Range<usize>) -> Range<usize> {
+    let piece = &text[range.clone()];
+    let start = range.start + (piece.len() - piece.trim_start().len());
+    let end = range.end - (piece.len() - piece.trim_end().len());
+
+    start..end.max(start)
+}
+
+fn characters(text: &str, offset: usize, size: usize, overlap: usize, heading: Option<&str>, chunks: &mut Vec<TextChunk>) {
+    let size = size.max(1);
+    let step = size - overlap.min(size - 1);
+
+    let bounds = text.char_indices().map(|(i, _)| i).chain([text.len()]).collect::<Vec<_>>();
+    let count = bounds.len() - 1;
+
+    let mut first = 0;
+    while first < count {
+        let last = (first + size).min(count);
+        push(text, offset, bounds[first]..bounds[last], heading, chunks);
+
+        if last == count {
+            break;
+        }
+        first += step;
+    }
+}
+
+fn sentences(text: &str, offset: usize, max_chars: usize, overlap: usize, heading: Option<&str>, chunks: &mut Vec<TextChunk>) {
+    let max_chars = max_chars.max(1);
+    let spans = split_sentences(text);
+    let chars = |range: Range<usize>| text[range].chars().count();
+
+    let mut first = 0;
+    while first < spans.len() {
+        let start = spans[first].start;
+
+        if chars(spans[first].clone()) > max_chars {
+            let before = chunks.len();
+            characters(&text[spans[first].clone()], offset + start, max_chars, 0, heading, chunks);
+
+            // Keep closing punctuation with the window before it rather than on its own
+            if chunks.len() > before + 1 && !chunks[chunks.len() - 1].text.chars().any(char::is_alphanumeric) {
+                let tail = chunks.pop().unwrap();
+                let last = chunks.last_mut().unwrap();
+                last.end = tail.end;
+                last.text = text[last.start - offset..last.end - offset].to_owned();
+            }
+
+            first += 1;
+            continue;
+        }
+
+        let mut last = first;
+        while last + 1 < spans.len() && chars(start..spans[last + 1].end) <= max_chars {
+            last += 1;
+        }
+
+        push(text, offset, start..spans[last].end, heading, chunks);
+
+        if last + 1 == spans.len() {
+            break;
+        }
+        first = (last + 1).saturating_sub(overlap).max(first + 1);
+    }
+}
+
+/// Trimmed, non-blank sentences of `text`
+fn split_sentences(text: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let end = match c {
+            '.' | '!' | '?' => {
+                let mut end = i + c.len_utf8();
+
+                // Keep repeated punctuation and closing quotes with the sentence
+                while let Some(&(j, next)) = chars.peek() {
+                    if !matches!(next, '.' | '!' | '?' | '"' | '\'' | ')' | ']' | '”' | '’') {
+                        break;
+                    }
+                    end = j + next.len_utf8();
+                    chars.next();
+                }
+
+                match chars.peek() {
+                    Some(&(_, next)) if !next.is_whitespace() => None,
+                    _ => Some(end),
+                }
+            }
+            '\n' if matches!(chars.peek(), Some(&(_, '\n' | '\r'))) => Some(i),
+            _ => None,
+        };
+
+        if let Some(end) = end {
+            spans.push(trim(text, start..end));
+            start = end;
+        }
+    }
+
+    spans.push(trim(text, start..text.len()));
+    spans.retain(|span| !span.is_empty());
+    spans
+}
+
+/// Markdown sections of `text` with their heading path
+///
+/// Sections holding nothing but their heading are skipped.
+fn sections(text: &str) -> Vec<(Range<usize>, Option<String>)> {
+    let mut sections = Vec::new();
+    let mut path: Vec<(usize, String)> = Vec::new();
+
+    let mut start = 0;
+    let mut body = 0;
+    let mut heading = None;
+    let mut fence: Option<&str> = None;
+    let mut offset = 0;
+
+    let mut close = |start: usize, body: usize, end: usize, heading: Option<String>| {
+        if !text[body..end].trim().is_empty() {
+            sections.push((start..end, heading));
+        }
+    };
+
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let trimmed = line.trim();
+
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            continue;
+        }
+
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker)) {
+            fence = Some(marker);
+            continue;
+        }
+
+        let Some((level, title)) = parse_heading(line) else {
+            continue;
+        };
+
+        close(start, body, line_start, heading.take());
+
+        while path.last().is_some_and(|(parent, _)| *parent >= level) {
+            path.pop();
+        }
+        path.push((level, title.to_owned()));
+
+        start = line_start;
+        body = offset;
+        heading = Some(path.iter().map(|(_, title)| title.as_str()).collect::<Vec<_>>().join(" > "));
+    }
+
+    close(start, body, text.len(), heading);
+    sections
+}
+
+/// Level and title of an ATX heading line
+fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+
+    let line = line[indent..].trim_end();
+    let level = line.len() - line.trim_start_matches('#').len();
+    let rest = &line[level..];
+
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+
+    Some((level, rest.trim().trim_end_matches('#').trim_end()))
+}
+
+/// Indices of the context passages cited in `answer`, in order of first citation
+///
+/// Citation markers are numbers in square brackets, starting at 1, like `[2]`
+/// or `[1, 3]`. Numbers outside `1..=count` are ignored.
+///
+/// ## Parameters
+/// - `answer`: Answer of the model
+/// - `count`: Number of context passages
+///
+/// ## Returns
+/// Zero-based indices of the cited passages
+pub fn citations(answer: &str, count: usize) -> Vec<usize> {
+    let mut cited = Vec::new();
+    let mut rest = answer;
+
+    while let Some(open) = rest.find('[') {
+        rest = &rest[open + 1..];
+
+        let Some(close) = rest.find([']', '[']) else {
+            break;
+        };
+        if rest[close..].starts_with('[') {
+            continue;
+        }
+
+        let numbers = rest[..close]
+            .split(',')
+            .map(|number| number.trim().parse::<usize>())
+            .collect::<Result<Vec<_>, _>>();
+
+        for number in numbers.into_iter().flatten() {
+            if (1..=count).contains(&number) && !cited.contains(&(number - 1)) {
+                cited.push(number - 1);
+            }
+        }
+
+        rest = &rest[close + 1..];
+    }
+
+    cited
+}
+
+/// Chunk retrieved for a question
+#[derive(Debug, Clone, PartialEq)]
+pub struct Retrieved {
+    /// ID of the chunk in the store
+    pub id: String,
+    /// Source the chunk was added with
+    pub source: String,
+    pub text: String,
+    /// Markdown headings of the chunk, if any
+    pub heading: Option<String>,
+    /// Cosine similarity to the question
+    pub score: f64,
+    pub metadata: Metadata,
+}
+
+impl Retrieved {
+    fn new(document: &Document, score: f64) -> Self {
+        let field = |key: &str| document.metadata.get(key).and_then(Value::as_str).map(str::to_owned);
+
+        Self {
+            id: document.id.clone(),
+            source: field("source").unwrap_or_default(),
+            text: field("text").unwrap_or_default(),
+            heading: field("heading"),
+            score,
+            metadata: document.metadata.clone(),
+        }
+    }
+}
+
+/// Answer of [`RagPipeline::ask`]
+#[derive(Debug)]
+pub struct RagAnswer {
+    pub answer: String,
+    /// Chunks given to the model, numbered from 1 in the prompt
+    pub context: Vec<Retrieved>,
+    /// Indices in `context` of the chunks cited in the answer, in order of first citation
+    pub citations: Vec<usize>,
+    /// Full chat response
+    pub response: ChatResponse,
+}
+
+impl RagAnswer {
+    /// Chunks cited in the answer
+    pub fn cited(&self) -> impl Iterator<Item = &Retrieved> {
+        self.citations.iter().map(|&i| &self.context[i])
+    }
+}
+
+/// Chunk, embed, retrieve and answer
+///
+/// Every chunk is stored under the ID `{source}#{n}` with these metadata
+/// fields on top of the document's own: `source`, `chunk` (`n`), `text`,
+/// `start` and `end` (byte offsets in the document) and `heading` (Markdown
+/// chunks only).
+#[derive(Clone)]
+pub struct RagPipeline {
+    ollama: Ollama,
+    embedding_model: String,
+    chat_model: String,
+    chunker: Chunker,
+    top_k: usize,
+    instructions: String,
+    options: Option<Map<String, Value>>,
+    store: VectorStore,
+}
+
+impl RagPipeline {
+    /// Create a pipeline with an empty store
+    ///
+    /// ## Parameters
+    /// - `ollama`: Client for embeddings and chats
+    /// - `embedding_model`: Model embedding chunks and questions
+    /// - `chat_model`: Model answering questions
+    pub fn new<E: Into<String>, C: Into<String>>(ollama: Ollama, embedding_model: E, chat_model: C) -> Self {
+        Self {
+            ollama,
+            embedding_model: embedding_model.into(),
+            chat_model: chat_model.into(),
+            chunker: Chunker::default(),
+            top_k: 4,
+            instructions: DEFAULT_INSTRUCTIONS.to_owned(),
+            options: None,
+            store: VectorStore::new(),
+        }
+    }
+
+    /// Split documents with `chunker` (default: [`Chunker::Sentences`] of up to 1000 characters, overlapping by 1)
+    pub fn with_chunker(mut self, chunker: Chunker) -> Self {
+        self.chunker = chunker;
+        self
+    }
+
+    /// Give the `top_k` closest chunks to the chat model (default: 4)
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    /// Replace the instructions preceding the context in the system message
+    pub fn with_instructions<S: Into<String>>(mut self, instructions: S) -> Self {
+        self.instructions = instructions.into();
+        self
+    }
+
+    /// Model options of chat requests, like `temperature`
+    pub fn with_options(mut self, options: Map<String, Value>) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    /// Start from an existing store, like one saved with [`VectorStore::save`]
+    pub fn with_store(mut self, store: VectorStore) -> Self {
+        self.store = store;
+        self
+    }
+
+    pub fn store(&self) -> &VectorStore {
+        &self.store
+    }
+
+    pub fn store_mut(&mut self) -> &mut VectorStore {
+        &mut self.store
+    }
+
+    pub fn into_store(self) -> VectorStore {
+        self.store
+    }
+
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Embedding>, Error> {
+        let request = EmbedRequest {
+            model: self.embedding_model.clone(),
+            input: EmbedInput::Multiple(texts),
+            truncate: None,
+            options: None,
+            keep_alive: None,
+        };
+
+        Ok(self.ollama.embed(&request).await?.embeddings)
+    }
+
+    /// Chunk, embed and store a document
+    ///
+    /// Chunks previously added from the same `source` are replaced.
+    ///
+    /// ## Parameters
+    /// - `source`: Name of the document, like a path or URL
+    /// - `text`: Content of the document
+    /// - `metadata`: Fields added to every chunk
+    ///
+    /// ## Returns
+    /// - `Ok(n)`: Number of chunks stored
+    /// - `Err(_)`: Error occurred; the store is left unchanged
+    pub async fn add_document(&mut self, source: &str, text: &str, metadata: Metadata) -> Result<usize, Error> {
+        let chunks = self.chunker.chunk(text);
+        let mut documents: Vec<Document> = Vec::with_capacity(chunks.len());
+
+        // Everything is embedded before touching the store, so a failure keeps the previous chunks
+        for (batch, chunks) in chunks.chunks(EMBED_BATCH).enumerate() {
+            let embeddings = self.embed(chunks.iter().map(|chunk| chunk.text.clone()).collect()).await?;
+            if embeddings.len() != chunks.len() {
+                return Err(Error::EmptyResponse);
+            }
+
+            for (i, (chunk, vector)) in chunks.iter().zip(embeddings).enumerate() {
+                let expected = self.store.dimensions().or_else(|| documents.first().map(|document| document.vector.len()));
+                match expected {
+                    Some(expected) if expected != vector.len() => return Err(Error::DimensionMismatch { expected, actual: vector.len() }),
+                    _ => {},
+                }
+
+                let n = batch * EMBED_BATCH + i;
+                let mut document = Document::new(format!("{source}#{n}"), vector)
+                    .with_metadata(metadata.clone())
+                    .with_field("source", source)
+                    .with_field("chunk", n)
+                    .with_field("text", chunk.text.as_str())
+                    .with_field("start", chunk.start)
+                    .with_field("end", chunk.end);
+
+                if let Some(heading) = &chunk.heading {
+                    document = document.with_field("heading", heading.as_str());
+                }
+
+                documents.push(document);
+            }
+        }
+
+        let stale = self.store.documents()
+            .filter(|document| document.metadata.get("source").and_then(Value::as_str) == Some(source))
+            .map(|document| document.id.clone())
+            .collect::<Vec<_>>();
+        for id in stale {
+            self.store.remove(&id);
+        }
+
+        self.store.extend(documents)?;
+
+        Ok(chunks.len())
+    }
+
+    /// Closest chunks to `question`, most similar first
+    ///
+    /// ## Parameters
+    /// - `question`: Question to embed
+    /// - `filter`: Only consider chunks whose metadata match
+    pub async fn retrieve(&self, question: &str, filter: Option<&Filter>) -> Result<Vec<Retrieved>, Error> {
+        if self.store.is_empty() || self.top_k == 0 {
+            return Ok(Vec::new());
+        }
+
+        let query = self.embed(vec![question.to_owned()]).await?
+            .into_iter()
+            .next()
+            .ok_or(Error::EmptyResponse)?;
+
+        let results = match filter {
+            Some(filter) => self.store.search_filtered(&query, self.top_k, filter)?,
+            None => self.store.search(&query, self.top_k)?,
+        };
+
+        Ok(results.into_iter().map(|result| Retrieved::new(result.document, result.score)).collect())
+    }
+
+    /// Chat request answering `question` from `context`
+    ///
+    /// The system message holds the instructions and the context passages,
+    /// numbered from 1; the user message holds the question.
+    pub fn request(&self, question: &str, context: &[Retrieved]) -> ChatRequest {
+        let mut system = format!("{}\n\nContext:\n", self.instructions);
+
+        if context.is_empty() {
+            system.push_str("(no passages found)\n");
+        }
+
+        for (i, chunk) in context.iter().enumerate() {
+            system.push_str(&format!("\n[{}] Source: {}", i + 1, chunk.source));
+            if let Some(heading) = &chunk.heading {
+                system.push_str(&format!(" ({heading})"));
+            }
+            system.push_str(&format!("\n{}\n", chunk.text));
+        }
+
+        let message = |role, content| Message {
+            role,
+            content,
+            images: None,
+            tool_calls: None,
+            thinking: None,
+        };
+
+        ChatRequest {
+            model: self.chat_model.clone(),
+            messages: vec![
+                message(Role::System, system),
+                message(Role::User, question.to_owned()),
+            ],
+            format: None,
+            options: self.options.clone(),
+            stream: Some(false),
+            keep_alive: None,
+            tools: None,
+            think: None,
+        }
+    }
+
+    /// Retrieve context for `question` and answer it
+    ///
+    /// ## Parameters
+    /// - `question`: Question to answer
+    /// - `filter`: Only consider chunks whose metadata match
+    pub async fn ask(&self, question: &str, filter: Option<&Filter>) -> Result<RagAnswer, Error> {
+        let context = self.retrieve(question, filter).await?;
+        let response = self.ollama.chat(&self.request(question, &context), None::<fn(&_)>).await?;
+
+        let answer = response.message.as_ref()
+            .map(|message| message.content.clone())
+            .unwrap_or_default();
+
+        Ok(RagAnswer {
+            citations: citations(&answer, context.len()),
+            answer,
+            context,
+            response,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(chunks: &[TextChunk]) -> Vec<&str> {
+        chunks.iter().map(|chunk| chunk.text.as_str()).collect()
+    }
+
+    #[test]
+    fn characters_with_overlap() {
+        let text = "abcdefghij";
+        let chunks = Chunker::characters(4, 1).chunk(text);
+
+        assert_eq!(texts(&chunks), ["abcd", "defg", "ghij"]);
+        assert_eq!((chunks[1].start, chunks[1].end), (3, 7));
+
+        // Offsets are in bytes, windows in characters
+        let chunks = Chunker::characters(2, 0).chunk("héllo");
+        assert_eq!(texts(&chunks), ["hé", "ll", "o"]);
+        assert_eq!(chunks[1].start, 3);
+    }
+
+    #[test]
+    fn sentences_with_overlap() {
+        let text = "The sky is blue. Grass is green!  Is snow white? \"Yes.\" Done";
+        let chunks = Chunker::sentences(40, 1).chunk(text);
+
+        assert_eq!(texts(&chunks), [
+            "The sky is blue. Grass is green!",
+            "Grass is green!  Is snow white? \"Yes.\"",
+            "\"Yes.\" Done",
+        ]);
+
+        for chunk in &chunks {
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+        }
+
+        // Decimals and paragraphs
+        let chunks = Chunker::sentences(10, 0).chunk("Pi is 3.14\n\nNext");
+        assert_eq!(texts(&chunks), ["Pi is 3.14", "Next"]);
+
+        // Long sentences fall back to characters
+        let chunks = Chunker::sentences(5, 0).chunk("Short. Much longer");
+        assert_eq!(texts(&chunks), ["Short.", "Much", "longe", "r"]);
+        assert_eq!(texts(&Chunker::sentences(4, 0).chunk("Why?!? Ok")), ["Why?!?", "Ok"]);
+
+        for chunk in &chunks {
+            assert_eq!(&"Short. Much longer"[chunk.start..chunk.end], chunk.text);
+        }
+    }
+
+    #[test]
+    fn markdown_sections() {
+        let text = "Intro.\n\n# Sky\n\nThe sky is blue.\n\n## Night\n\nIt is dark.\n\n```\n# not a heading\n```\n\n# Empty\n\n# Grass ##\n\nGrass is green.";
+        let chunks = Chunker::markdown(100, 0).chunk(text);
+
+        assert_eq!(texts(&chunks), [
+            "Intro.",
+            "# Sky\n\nThe sky is blue.",
+            "## Night\n\nIt is dark.\n\n```\n# not a heading\n```",
+            "# Grass ##\n\nGrass is green.",
+        ]);
+
+        let headings = chunks.iter().map(|chunk| chunk.heading.as_deref()).collect::<Vec<_>>();
+        assert_eq!(headings, [None, Some("Sky"), Some("Sky > Night"), Some("Grass")]);
+
+        // Long sections are split by sentences and keep their heading
+        let chunks = Chunker::markdown(20, 0).chunk("# Sky\n\nThe sky is blue. It is big.");
+        assert_eq!(texts(&chunks), ["# Sky", "The sky is blue.", "It is big."]);
+        assert!(chunks.iter().all(|chunk| chunk.heading.as_deref() == Some("Sky")));
+    }
+
+    #[test]
+    fn citation_markers() {
+        assert_eq!(citations("Blue [2], because of scattering [1, 2]. See [3] and [x].", 2), [1, 0]);
+        assert_eq!(citations("No citations [here", 2), Vec::<usize>::new());
+        assert_eq!(citations("Nested [[1]]", 1), [0]);
+    }
+}
@@ -0,0 +1,322 @@
+//! Model-aware routing across a fleet
+//!
+//! [`ModelRouter`] sends each request to a pooled host whose `local_models`
+//! includes the requested model, optionally pulling the model onto a host when
+//! none has it. Host inventories are cached and refreshed once they get older
+//! than the refresh interval.
+//!
+//! ## Examples
+//!
+//! ```rust,no_run
+//! use std::str::FromStr;
+//!
+//! use ollama_rest::{models::generate::GenerationRequest, pool::OllamaPool, router::ModelRouter, Ollama};
+//! use serde_json::json;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), ollama_rest::errors::Error> {
+//! let router = ModelRouter::new(OllamaPool::new([
+//!     Ollama::from_str("http://gpu-1:11434")?,
+//!     Ollama::from_str("http://gpu-2:11434")?,
+//! ])).with_pull_on_miss(true);
+//!
+//! let request = serde_json::from_value::<GenerationRequest>(json!({
+//!     "model": "llama3.2:1b",
+//!     "prompt": "Why is the sky blue?",
+//!     "stream": false,
+//! })).unwrap();
+//!
+//! let res = router.generate(&request, None::<fn(&_)>).await?;
+//! println!("{} answered: {}", res.host, res.response.response);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    cell::RefCell,
+    collections::BTreeSet,
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use futures::{Stream, StreamExt};
+
+use crate::{
+    errors::Error,
+    models::{
+        chat::{ChatRequest, ChatResponse},
+        embeddings::{EmbedRequest, EmbedResponse, EmbeddingGenerationRequest, EmbeddingGenerationResponse},
+        generate::{GenerationRequest, GenerationResponse},
+        model::{ModelPullStatus, ModelSyncRequest},
+    },
+    pool::{canonical_model, InFlight, OllamaPool},
+    Ollama,
+};
+
+/// Response along with the host that served it
+#[derive(Debug, Clone)]
+pub struct Routed<T> {
+    /// See [`Ollama::host`]
+    pub host: String,
+    pub response: T,
+}
+
+/// Cached models of a host
+#[derive(Debug, Clone)]
+pub struct HostInventory {
+    pub host: String,
+    pub models: Vec<String>,
+}
+
+struct Inventory {
+    /// Models of each host, by position in the pool
+    models: Vec<BTreeSet<String>>,
+    refreshed_at: Option<Instant>,
+}
+
+/// Router of requests to the hosts having their model
+pub struct ModelRouter {
+    pool: OllamaPool,
+    inventory: Mutex<Inventory>,
+    refresh_interval: Duration,
+    pull_on_miss: bool,
+}
+
+impl ModelRouter {
+    pub fn new(pool: OllamaPool) -> Self {
+        let hosts = pool.hosts().count();
+
+        Self {
+            pool,
+            inventory: Mutex::new(Inventory {
+                models: vec![BTreeSet::new(); hosts],
+                refreshed_at: None,
+            }),
+            refresh_interval: Duration::from_secs(60),
+            pull_on_miss: false,
+        }
+    }
+
+    /// Age after which the cached inventory is refreshed before routing
+    pub fn with_refresh_interval(mut self, interval: Duration) -> Self {
+        self.refresh_interval = interval;
+        self
+    }
+
+    /// Pull a missing model onto a host chosen by the pool, instead of failing with [`Error::NotExists`]
+    pub fn with_pull_on_miss(mut self, pull_on_miss: bool) -> Self {
+        self.pull_on_miss = pull_on_miss;
+        self
+    }
+
+    pub fn pool(&self) -> &OllamaPool {
+        &self.pool
+    }
+
+    /// Cached models of every host
+    pub fn inventory(&self) -> Vec<HostInventory> {
+        let inventory = self.inventory.lock().unwrap();
+
+        self.pool.hosts()
+            .zip(&inventory.models)
+            .map(|(client, models)| HostInventory {
+                host: client.host().to_string(),
+                models: models.iter().cloned().collect(),
+            })
+            .collect()
+    }
+
+    /// Hosts having `model`, as of the cached inventory
+    pub fn hosts_with(&self, model: &str) -> Vec<String> {
+        let model = canonical_model(model);
+
+        self.inventory()
+            .into_iter()
+            .filter(|inventory| inventory.models.contains(&model))
+            .map(|inventory| inventory.host)
+            .collect()
+    }
+
+    /// Fetch `local_models` of every host
+    ///
+    /// Hosts that cannot be reached keep their previous inventory.
+    pub async fn refresh_inventory(&self) {
+        let listed = futures::future::join_all(self.pool.hosts().map(Ollama::local_models)).await;
+
+        let mut inventory = self.inventory.lock().unwrap();
+        for (models, listed) in inventory.models.iter_mut().zip(listed) {
+            if let Ok(listed) = listed {
+                *models = listed.models.into_iter().map(|model| canonical_model(&model.name)).collect();
+            }
+        }
+
+        inventory.refreshed_at = Some(Instant::now());
+    }
+
+    /// Run [`ModelRouter::refresh_inventory`] forever, every refresh interval
+    pub async fn run_inventory_refresh(&self) {
+        loop {
+            self.refresh_inventory().await;
+            tokio::time::sleep(self.refresh_interval).await;
+        }
+    }
+
+    fn lookup(&self, model: &str) -> Vec<usize> {
+        self.inventory.lock().unwrap()
+            .models
+            .iter()
+            .enumerate()
+            .filter(|(_, models)| models.contains(model))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn is_stale(&self) -> bool {
+        match self.inventory.lock().unwrap().refreshed_at {
+            Some(at) => at.elapsed() >= self.refresh_interval,
+            None => true,
+        }
+    }
+
+    /// Positions of the hosts able to serve `model`
+    async fn hosts_for(&self, model: &str) -> Result<Vec<usize>, Error> {
+        let model = canonical_model(model);
+
+        let refreshed = self.is_stale();
+        if refreshed {
+            self.refresh_inventory().await;
+        }
+
+        let mut hosts = self.lookup(&model);
+
+        // The model may have been added since the last refresh
+        if hosts.is_empty() && !refreshed {
+            self.refresh_inventory().await;
+            hosts = self.lookup(&model);
+        }
+
+        if !hosts.is_empty() {
+            return Ok(hosts);
+        }
+
+        if !self.pull_on_miss {
+            return Err(Error::NotExists);
+        }
+
+        Ok(vec![self.pull(&model).await?])
+    }
+
+    /// Pull `model` onto a host picked by the pool
+    async fn pull(&self, model: &str) -> Result<usize, Error> {
+        let request = ModelSyncRequest {
+            name: model.to_string(),
+            insecure: None,
+            stream: Some(false),
+        };
+
+        let (_, guard) = self.pool.dispatch(None, &|_| true, |ollama| {
+            ollama.pull_model(&request, Some(|_: &ModelPullStatus| {}))
+        }).await?;
+
+        let index = guard.index();
+        self.inventory.lock().unwrap().models[index].insert(model.to_string());
+
+        Ok(index)
+    }
+
+    async fn dispatch<'a, F, Fut, T>(&'a self, model: &str, f: F) -> Result<(T, InFlight<'a>), Error>
+    where
+        F: FnMut(&'a Ollama) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let hosts = self.hosts_for(model).await?;
+
+        self.pool.dispatch(Some(model), &|index| hosts.contains(&index), f).await
+    }
+
+    /// Run a request on a host having `model`
+    ///
+    /// ## Parameters
+    /// - `model`: Model the request is for
+    /// - `f`: Request to run, called again with the next host on connection errors
+    ///
+    /// ## Returns
+    /// The response and the host that served it
+    pub async fn execute<'a, F, Fut, T>(&'a self, model: &str, f: F) -> Result<Routed<T>, Error>
+    where
+        F: FnMut(&'a Ollama) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let (response, guard) = self.dispatch(model, f).await?;
+
+        Ok(Routed {
+            host: guard.client().host().to_string(),
+            response,
+        })
+    }
+
+    /// Generate completion response for one single prompt (Callback API)
+    pub async fn generate<T>(&self, request: &GenerationRequest, on_stream: Option<T>) -> Result<Routed<GenerationResponse>, Error>
+    where
+        T: FnMut(&GenerationResponse),
+    {
+        // Shared by every attempt
+        let on_stream = on_stream.map(RefCell::new);
+        let on_stream = &on_stream;
+
+        self.execute(&request.model, |ollama| {
+            ollama.generate(request, on_stream.as_ref().map(|f| move |res: &GenerationResponse| f.borrow_mut()(res)))
+        }).await
+    }
+
+    /// Generate completion response for one single prompt (Stream API)
+    pub async fn generate_streamed(&self, request: &GenerationRequest) -> Result<Routed<impl Stream<Item = Result<GenerationResponse, Error>> + '_>, Error> {
+        let (stream, guard) = self.dispatch(&request.model, |ollama| ollama.generate_streamed(request)).await?;
+
+        Ok(Routed {
+            host: guard.client().host().to_string(),
+            response: stream.map(move |res| {
+                let _ = &guard;
+                res
+            }),
+        })
+    }
+
+    /// Generate chat completion response (Callback API)
+    pub async fn chat<T>(&self, request: &ChatRequest, on_stream: Option<T>) -> Result<Routed<ChatResponse>, Error>
+    where
+        T: FnMut(&ChatResponse),
+    {
+        // Shared by every attempt
+        let on_stream = on_stream.map(RefCell::new);
+        let on_stream = &on_stream;
+
+        self.execute(&request.model, |ollama| {
+            ollama.chat(request, on_stream.as_ref().map(|f| move |res: &ChatResponse| f.borrow_mut()(res)))
+        }).await
+    }
+
+    /// Generate chat completion response (Stream API)
+    pub async fn chat_streamed(&self, request: &ChatRequest) -> Result<Routed<impl Stream<Item = Result<ChatResponse, Error>> + '_>, Error> {
+        let (stream, guard) = self.dispatch(&request.model, |ollama| ollama.chat_streamed(request)).await?;
+
+        Ok(Routed {
+            host: guard.client().host().to_string(),
+            response: stream.map(move |res| {
+                let _ = &guard;
+                res
+            }),
+        })
+    }
+
+    /// Generate embeddings
+    pub async fn generate_embeddings(&self, request: &EmbeddingGenerationRequest) -> Result<Routed<EmbeddingGenerationResponse>, Error> {
+        self.execute(&request.model, |ollama| ollama.generate_embeddings(request)).await
+    }
+
+    /// Generate embeddings for one or more inputs
+    pub async fn embed(&self, request: &EmbedRequest) -> Result<Routed<EmbedResponse>, Error> {
+        self.execute(&request.model, |ollama| ollama.embed(request)).await
+    }
+}
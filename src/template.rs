@@ -0,0 +1,1382 @@
+//! Prompt template renderer
+//!
+//! Renders the [Go `text/template`](https://pkg.go.dev/text/template) subset
+//! used by Ollama prompt templates, such as `TEMPLATE` in Modelfiles and
+//! `template` in [`ModelShowResponse`](crate::models::model::ModelShowResponse):
+//!
+//! - Fields (`.System`, `$.Messages`, `(index .Messages 0).Content`), variables
+//!   (`$i := 0`, `$i = 1`), literals and pipelines (`.Tools | json`)
+//! - `if`/`else if`/`else`, `with`, `range` (with `$i, $m :=`, `break` and
+//!   `continue`), and comments
+//! - Whitespace trimming with `{{-` and `-}}`
+//! - `and`, `or`, `not`, `eq`, `ne`, `lt`, `le`, `gt`, `ge`, `len`, `index`,
+//!   `slice`, `print`, `printf`, `println`, plus Ollama's `json`,
+//!   `currentDate` and `yesterdayDate`
+//!
+//! `define`, `template` and `block` are not supported, nor are `printf`
+//! verbs other than `%v`, `%s`, `%d`, `%f`, `%t` and `%q` with no flags,
+//! width or precision. Objects and arrays print as JSON, like tool
+//! definitions and call arguments in Ollama.
+//!
+//! [`Template::render_chat`] and [`Template::render_generate`] build the
+//! prompt the way Ollama does for a request, so it can be previewed or sent
+//! as a `raw` generation. [`format`](mod@self::format) has ready-made prompt formats of common
+//! model families.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use ollama_rest::{models::chat::ChatRequest, template::Template};
+//! use serde_json::json;
+//!
+//! let template: Template = "{{- range .Messages }}<|{{ .Role }}|>\n{{ .Content }}\n{{ end }}<|assistant|>\n".parse().unwrap();
+//!
+//! let request = serde_json::from_value::<ChatRequest>(json!({
+//!     "model": "llama3.2",
+//!     "messages": [
+//!         { "role": "system", "content": "Be brief." },
+//!         { "role": "user", "content": "Why is the sky blue?" },
+//!     ],
+//! })).unwrap();
+//!
+//! assert_eq!(
+//!     template.render_chat(&request).unwrap(),
+//!     "<|system|>\nBe brief.\n<|user|>\nWhy is the sky blue?\n<|assistant|>\n",
+//! );
+//! ```
+
+use std::{fmt::Display, str::FromStr};
+
+use serde_json::{json, Value};
+
+use crate::models::{chat::ChatRequest, generate::GenerationRequest};
+
+pub mod format;
+
+/// Functions available to templates
+const FUNCTIONS: [&str; 18] = [
+    "and", "currentDate", "eq", "ge", "gt", "index", "json", "le", "len", "lt", "ne", "not", "or",
+    "print", "printf", "println", "slice", "yesterdayDate",
+];
+
+/// Template parsing or rendering error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    /// Malformed template
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// Call of a function this renderer does not provide
+    UnknownFunction {
+        line: usize,
+        name: String,
+    },
+    /// Failure while rendering, like a comparison of a string with a number
+    Execution {
+        line: usize,
+        message: String,
+    },
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Syntax { line, message } => write!(f, "line {line}: {message}"),
+            Self::UnknownFunction { line, name } => write!(f, "line {line}: function \"{name}\" not defined"),
+            Self::Execution { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+fn syntax(line: usize, message: impl Into<String>) -> TemplateError {
+    TemplateError::Syntax { line, message: message.into() }
+}
+
+fn execution(line: usize, message: impl Into<String>) -> TemplateError {
+    TemplateError::Execution { line, message: message.into() }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Dot,
+    /// Field chain, and whether it directly follows a `)`
+    Field(Vec<String>, bool),
+    /// Variable, empty for `$`, and its field chain
+    Variable(String, Vec<String>),
+    Identifier(String),
+    Literal(Value),
+    LeftParen,
+    RightParen,
+    Pipe,
+    Comma,
+    Declare,
+    Assign,
+}
+
+/// Text or action of the template source
+enum Item {
+    Text(String),
+    Action {
+        line: usize,
+        tokens: Vec<Token>,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Dot,
+    Field(Vec<String>),
+    Variable(String, Vec<String>),
+    Function(String),
+    Literal(Value),
+    /// Parenthesized pipeline and its field chain
+    Pipeline(Box<Pipeline>, Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+struct Declaration {
+    names: Vec<String>,
+    /// `:=` rather than `=`
+    declare: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Pipeline {
+    declaration: Option<Declaration>,
+    commands: Vec<Vec<Operand>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BranchKind {
+    If,
+    With,
+    Range,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Action {
+        line: usize,
+        pipeline: Pipeline,
+    },
+    Branch {
+        kind: BranchKind,
+        line: usize,
+        pipeline: Pipeline,
+        body: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    Break,
+    Continue,
+}
+
+/// Parsed prompt template
+#[derive(Debug, Clone)]
+pub struct Template {
+    source: String,
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    /// Parse template text
+    pub fn parse(text: &str) -> Result<Self, TemplateError> {
+        let items = scan(text)?;
+        let mut parser = Parser { items: items.into_iter(), ranges: 0 };
+
+        let nodes = match parser.list()? {
+            (nodes, None) => nodes,
+            (_, Some(Stop::End(line))) => return Err(syntax(line, "unexpected {{end}}")),
+            (_, Some(Stop::Else(_, line))) => return Err(syntax(line, "unexpected {{else}}")),
+        };
+
+        Ok(Self { source: text.to_string(), nodes })
+    }
+
+    /// Render with `data` as `.`
+    ///
+    /// Fields are looked up by name, then by their snake case name, so
+    /// `.ToolCalls` finds the `tool_calls` field of a serialized message.
+    pub fn render(&self, data: &Value) -> Result<String, TemplateError> {
+        render_nodes(&self.nodes, data)
+    }
+
+    /// Prompt Ollama builds from a chat request with this template
+    ///
+    /// Templates using `.Messages` get the whole conversation, along with
+    /// `.System` (all system messages), `.Tools` and `.Think`. Other templates
+    /// are rendered once per turn with `.System`, `.Prompt` and `.Response`,
+    /// the last turn stopping right after `.Response`.
+    pub fn render_chat(&self, request: &ChatRequest) -> Result<String, TemplateError> {
+        let messages = request.messages.iter().map(|message| json!(message)).collect();
+        let tools = request.tools.as_ref().map_or(Value::Null, |tools| json!(tools));
+
+        self.render_messages(messages, tools, request.think)
+    }
+
+    /// Prompt Ollama builds from a generation request with this template
+    ///
+    /// Raw requests are returned as is, and requests with a suffix fill
+    /// `.Prompt` and `.Suffix`. Others are rendered like a chat of the system
+    /// message and the prompt. The model's default system message is not
+    /// known here: set it in the request to see it in the prompt.
+    pub fn render_generate(&self, request: &GenerationRequest) -> Result<String, TemplateError> {
+        if request.raw == Some(true) {
+            return Ok(request.prompt.clone());
+        }
+
+        if let Some(suffix) = request.suffix.as_deref().filter(|suffix| !suffix.is_empty() && !request.prompt.is_empty()) {
+            return self.render(&json!({ "Prompt": request.prompt, "Suffix": suffix, "Response": "" }));
+        }
+
+        let mut messages = Vec::new();
+        if let Some(system) = &request.system {
+            messages.push(json!({ "role": "system", "content": system }));
+        }
+        if !request.prompt.is_empty() {
+            messages.push(json!({ "role": "user", "content": request.prompt, "images": request.images }));
+        }
+
+        self.render_messages(messages, Value::Null, None)
+    }
+
+    fn render_messages(&self, messages: Vec<Value>, tools: Value, think: Option<bool>) -> Result<String, TemplateError> {
+        let content = |message: &Value| message["content"].as_str().unwrap_or_default().to_string();
+        let data = |system: &str, prompt: &str, response: &str| json!({
+            "System": system,
+            "Prompt": prompt,
+            "Response": response,
+            "Think": think.unwrap_or_default(),
+            "ThinkLevel": "",
+            "IsThinkSet": think.is_some(),
+        });
+
+        let mut system = messages.iter()
+            .filter(|message| message["role"] == "system")
+            .map(content)
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        if self.uses("Messages") {
+            let mut data = data(&system, "", "");
+            data["Messages"] = Value::Array(messages);
+            data["Tools"] = tools;
+            return self.render(&data);
+        }
+
+        let mut out = String::new();
+        let (mut prompt, mut response) = (String::new(), String::new());
+
+        for message in &messages {
+            let flush = match message["role"].as_str() {
+                Some("system") => !prompt.is_empty() || !response.is_empty(),
+                Some("user") => !response.is_empty(),
+                _ => false,
+            };
+
+            if flush {
+                out.push_str(&self.render(&data(&system, &prompt, &response))?);
+                system.clear();
+                prompt.clear();
+                response.clear();
+            }
+
+            match message["role"].as_str() {
+                Some("system") => system = content(message),
+                Some("user") => prompt = content(message),
+                Some("assistant") => response = content(message),
+                _ => {}
+            }
+        }
+
+        let nodes = cut_after_response(&self.nodes, &mut false);
+        out.push_str(&render_nodes(&nodes, &data(&system, &prompt, &response))?);
+
+        Ok(out)
+    }
+
+    /// Whether a field chain anywhere in the template has a field called `name`
+    fn uses(&self, name: &str) -> bool {
+        fn in_pipeline(pipeline: &Pipeline, name: &str) -> bool {
+            pipeline.commands.iter().flatten().any(|operand| match operand {
+                Operand::Field(fields) | Operand::Variable(_, fields) => fields.iter().any(|field| field.eq_ignore_ascii_case(name)),
+                Operand::Pipeline(pipeline, fields) => {
+                    fields.iter().any(|field| field.eq_ignore_ascii_case(name)) || in_pipeline(pipeline, name)
+                }
+                _ => false,
+            })
+        }
+
+        fn in_nodes(nodes: &[Node], name: &str) -> bool {
+            nodes.iter().any(|node| match node {
+                Node::Action { pipeline, .. } => in_pipeline(pipeline, name),
+                Node::Branch { pipeline, body, otherwise, .. } => {
+                    in_pipeline(pipeline, name) || in_nodes(body, name) || in_nodes(otherwise, name)
+                }
+                _ => false,
+            })
+        }
+
+        in_nodes(&self.nodes, name)
+    }
+}
+
+impl FromStr for Template {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl Display for Template {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// Drop everything after the first `.Response` field, like Ollama does for the last turn
+fn cut_after_response(nodes: &[Node], cut: &mut bool) -> Vec<Node> {
+    fn mentions(pipeline: &Pipeline) -> bool {
+        pipeline.commands.iter().flatten().any(|operand| match operand {
+            Operand::Field(fields) => fields.iter().any(|field| field == "Response"),
+            Operand::Pipeline(pipeline, _) => mentions(pipeline),
+            _ => false,
+        })
+    }
+
+    let mut kept = Vec::new();
+
+    for node in nodes {
+        if *cut {
+            break;
+        }
+
+        match node {
+            Node::Action { pipeline, .. } => {
+                *cut = mentions(pipeline);
+                kept.push(node.clone());
+            }
+            Node::Branch { kind, line, pipeline, body, otherwise } => {
+                let body = cut_after_response(body, cut);
+                let otherwise = if *cut { Vec::new() } else { cut_after_response(otherwise, cut) };
+
+                kept.push(Node::Branch { kind: *kind, line: *line, pipeline: pipeline.clone(), body, otherwise });
+            }
+            node => kept.push(node.clone()),
+        }
+    }
+
+    kept
+}
+
+fn is_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn line_at(text: &str, pos: usize) -> usize {
+    text[..pos].matches('\n').count() + 1
+}
+
+/// Split the source into text and tokenized actions
+fn scan(text: &str) -> Result<Vec<Item>, TemplateError> {
+    let mut items = Vec::new();
+    let mut pos = 0;
+    let mut trim_next = false;
+
+    loop {
+        let open = text[pos..].find("{{").map(|i| pos + i);
+        let mut literal = &text[pos..open.unwrap_or(text.len())];
+        if trim_next {
+            literal = literal.trim_start_matches(is_space);
+        }
+
+        let Some(open) = open else {
+            if !literal.is_empty() {
+                items.push(Item::Text(literal.to_string()));
+            }
+            return Ok(items);
+        };
+
+        let mut start = open + 2;
+        if text[start..].starts_with('-') && text[start + 1..].starts_with(is_space) {
+            literal = literal.trim_end_matches(is_space);
+            start += 1;
+        }
+
+        if !literal.is_empty() {
+            items.push(Item::Text(literal.to_string()));
+        }
+
+        let line = line_at(text, open);
+        let (tokens, end, trim) = Lexer { text, pos: start, line }.action()?;
+
+        if let Some(tokens) = tokens {
+            items.push(Item::Action { line, tokens });
+        }
+
+        pos = end;
+        trim_next = trim;
+    }
+}
+
+struct Lexer<'a> {
+    text: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl Lexer<'_> {
+    fn rest(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_spaces(&mut self) -> bool {
+        let start = self.pos;
+        self.pos = self.text.len() - self.rest().trim_start_matches(is_space).len();
+        self.pos > start
+    }
+
+    /// Closing delimiter at the current position, and whether it trims
+    fn close(&mut self, after_space: bool) -> Option<bool> {
+        if after_space && self.rest().starts_with("-}}") {
+            self.pos += 3;
+            Some(true)
+        } else if self.rest().starts_with("}}") {
+            self.pos += 2;
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Tokens of the action, `None` for comments, with the position after it and whether it trims
+    fn action(mut self) -> Result<(Option<Vec<Token>>, usize, bool), TemplateError> {
+        let mut space = self.skip_spaces();
+
+        if self.rest().starts_with("/*") {
+            let end = self.rest().find("*/").ok_or_else(|| syntax(self.line, "unclosed comment"))?;
+            self.pos += end + 2;
+            space = self.skip_spaces();
+
+            let trim = self.close(space).ok_or_else(|| syntax(self.line, "comment ends before closing delimiter"))?;
+            return Ok((None, self.pos, trim));
+        }
+
+        let mut tokens = Vec::new();
+
+        loop {
+            if let Some(trim) = self.close(space) {
+                return Ok((Some(tokens), self.pos, trim));
+            }
+
+            let Some(c) = self.rest().chars().next() else {
+                return Err(syntax(self.line, "unclosed action"));
+            };
+
+            let token = match c {
+                '"' => self.string()?,
+                '`' => {
+                    let end = self.rest()[1..].find('`').ok_or_else(|| syntax(self.line, "unterminated raw quoted string"))?;
+                    let value = self.rest()[1..end + 1].to_string();
+                    self.pos += end + 2;
+                    Token::Literal(Value::String(value))
+                }
+                '(' | ')' | '|' | ',' | '=' => {
+                    self.pos += 1;
+                    match c {
+                        '(' => Token::LeftParen,
+                        ')' => Token::RightParen,
+                        '|' => Token::Pipe,
+                        ',' => Token::Comma,
+                        _ => Token::Assign,
+                    }
+                }
+                ':' if self.rest().starts_with(":=") => {
+                    self.pos += 2;
+                    Token::Declare
+                }
+                '.' if self.rest()[1..].starts_with(|c: char| c.is_alphabetic() || c == '_') => {
+                    let attached = self.text[..self.pos].ends_with(')');
+                    Token::Field(self.fields(), attached)
+                }
+                '.' if self.rest()[1..].starts_with(|c: char| c.is_ascii_digit()) => self.number()?,
+                '.' => {
+                    self.pos += 1;
+                    Token::Dot
+                }
+                '$' => {
+                    self.pos += 1;
+                    let name = self.identifier();
+                    Token::Variable(name, self.fields())
+                }
+                '-' | '+' | '0'..='9' => self.number()?,
+                c if c.is_alphabetic() || c == '_' => match self.identifier().as_str() {
+                    "true" => Token::Literal(Value::Bool(true)),
+                    "false" => Token::Literal(Value::Bool(false)),
+                    "nil" => Token::Literal(Value::Null),
+                    identifier => Token::Identifier(identifier.to_string()),
+                },
+                c => return Err(syntax(self.line, format!("unexpected {c:?} in action"))),
+            };
+
+            tokens.push(token);
+            space = self.skip_spaces();
+        }
+    }
+
+    fn identifier(&mut self) -> String {
+        let identifier = self.rest().chars().take_while(|&c| is_identifier(c)).collect::<String>();
+        self.pos += identifier.len();
+        identifier
+    }
+
+    /// Chain of `.Field`s
+    fn fields(&mut self) -> Vec<String> {
+        let mut fields = Vec::new();
+
+        while self.rest().starts_with('.') && self.rest()[1..].starts_with(|c: char| c.is_alphabetic() || c == '_') {
+            self.pos += 1;
+            fields.push(self.identifier());
+        }
+
+        fields
+    }
+
+    fn number(&mut self) -> Result<Token, TemplateError> {
+        let mut len = 0;
+        for (i, c) in self.rest().char_indices() {
+            let sign = matches!(c, '+' | '-') && (i == 0 || self.rest()[..i].ends_with(['e', 'E']));
+            if !(sign || c.is_ascii_alphanumeric() || c == '.' || c == '_') {
+                break;
+            }
+            len = i + c.len_utf8();
+        }
+
+        let literal = self.rest()[..len].replace('_', "");
+        self.pos += len;
+
+        let (negative, digits) = match literal.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, literal.trim_start_matches('+')),
+        };
+
+        let integer = match digits.get(..2) {
+            Some("0x" | "0X") => i64::from_str_radix(&digits[2..], 16).ok(),
+            Some("0o" | "0O") => i64::from_str_radix(&digits[2..], 8).ok(),
+            Some("0b" | "0B") => i64::from_str_radix(&digits[2..], 2).ok(),
+            _ => digits.parse::<i64>().ok(),
+        };
+
+        let value = match integer {
+            Some(integer) => json!(if negative { -integer } else { integer }),
+            None => match literal.parse::<f64>() {
+                Ok(float) if float.is_finite() => json!(float),
+                Ok(_) => return Err(execution(self.line, format!("number out of range: {literal:?}"))),
+                Err(_) => return Err(syntax(self.line, format!("bad number syntax: {literal:?}"))),
+            },
+        };
+
+        Ok(Token::Literal(value))
+    }
+
+    fn string(&mut self) -> Result<Token, TemplateError> {
+        let mut value = String::new();
+        let mut chars = self.rest()[1..].char_indices();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 2;
+                    return Ok(Token::Literal(Value::String(value)));
+                }
+                '\n' => break,
+                '\\' => {
+                    let Some((_, escaped)) = chars.next() else {
+                        break;
+                    };
+
+                    match escaped {
+                        'n' => value.push('\n'),
+                        't' => value.push('\t'),
+                        'r' => value.push('\r'),
+                        'a' => value.push('\x07'),
+                        'b' => value.push('\x08'),
+                        'f' => value.push('\x0c'),
+                        'v' => value.push('\x0b'),
+                        '\\' | '"' | '\'' => value.push(escaped),
+                        'x' | 'u' | 'U' => {
+                            let len = match escaped {
+                                'x' => 2,
+                                'u' => 4,
+                                _ => 8,
+                            };
+                            let hex = chars.by_ref().take(len).map(|(_, c)| c).collect::<String>();
+                            let c = u32::from_str_radix(&hex, 16).ok()
+                                .filter(|_| hex.len() == len)
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| syntax(self.line, format!("invalid escape \\{escaped}{hex}")))?;
+                            value.push(c);
+                        }
+                        escaped => return Err(syntax(self.line, format!("unknown escape \\{escaped}"))),
+                    }
+                }
+                c => value.push(c),
+            }
+        }
+
+        Err(syntax(self.line, "unterminated quoted string"))
+    }
+}
+
+/// How a list of nodes ended, and on which line
+enum Stop {
+    End(usize),
+    /// `else`, with the tokens following it
+    Else(Vec<Token>, usize),
+}
+
+struct Parser {
+    items: std::vec::IntoIter<Item>,
+    /// Depth of enclosing `range`s
+    ranges: usize,
+}
+
+impl Parser {
+    /// Nodes up to the next `end` or `else` at this level
+    fn list(&mut self) -> Result<(Vec<Node>, Option<Stop>), TemplateError> {
+        let mut nodes = Vec::new();
+
+        while let Some(item) = self.items.next() {
+            let (line, tokens) = match item {
+                Item::Text(text) => {
+                    nodes.push(Node::Text(text));
+                    continue;
+                }
+                Item::Action { line, tokens } => (line, tokens),
+            };
+
+            let keyword = match tokens.first() {
+                Some(Token::Identifier(keyword)) => keyword.as_str(),
+                _ => "",
+            };
+
+            let node = match keyword {
+                "end" if tokens.len() == 1 => return Ok((nodes, Some(Stop::End(line)))),
+                "else" => return Ok((nodes, Some(Stop::Else(tokens[1..].to_vec(), line)))),
+                "if" => self.branch(BranchKind::If, &tokens[1..], line)?,
+                "with" => self.branch(BranchKind::With, &tokens[1..], line)?,
+                "range" => self.branch(BranchKind::Range, &tokens[1..], line)?,
+                "break" | "continue" if tokens.len() == 1 => {
+                    if self.ranges == 0 {
+                        return Err(syntax(line, format!("{{{{{keyword}}}}} outside {{{{range}}}}")));
+                    }
+
+                    match keyword {
+                        "break" => Node::Break,
+                        _ => Node::Continue,
+                    }
+                }
+                "end" | "break" | "continue" => return Err(syntax(line, format!("unexpected arguments after {keyword}"))),
+                "define" | "template" | "block" => return Err(syntax(line, format!("{{{{{keyword}}}}} is not supported"))),
+                _ => Node::Action { line, pipeline: pipeline(&tokens, line, false)? },
+            };
+
+            nodes.push(node);
+        }
+
+        Ok((nodes, None))
+    }
+
+    fn branch(&mut self, kind: BranchKind, tokens: &[Token], line: usize) -> Result<Node, TemplateError> {
+        let pipeline = pipeline(tokens, line, kind == BranchKind::Range)?;
+
+        if kind == BranchKind::Range {
+            self.ranges += 1;
+        }
+        let (body, stop) = self.list()?;
+        if kind == BranchKind::Range {
+            self.ranges -= 1;
+        }
+
+        let otherwise = match stop {
+            None => return Err(syntax(line, "unexpected EOF, missing {{end}}")),
+            Some(Stop::End(_)) => Vec::new(),
+            Some(Stop::Else(tokens, else_line)) => match tokens.first() {
+                None => match self.list()? {
+                    (otherwise, Some(Stop::End(_))) => otherwise,
+                    (_, Some(Stop::Else(_, line))) => return Err(syntax(line, "expected {{end}}, found {{else}}")),
+                    (_, None) => return Err(syntax(line, "unexpected EOF, missing {{end}}")),
+                },
+                Some(Token::Identifier(keyword)) if kind != BranchKind::Range && (keyword == "if" || keyword == "with") => {
+                    let kind = match keyword.as_str() {
+                        "if" => BranchKind::If,
+                        _ => BranchKind::With,
+                    };
+                    vec![self.branch(kind, &tokens[1..], else_line)?]
+                }
+                Some(_) => return Err(syntax(else_line, "unexpected arguments after else")),
+            },
+        };
+
+        Ok(Node::Branch { kind, line, pipeline, body, otherwise })
+    }
+}
+
+fn pipeline(tokens: &[Token], line: usize, range: bool) -> Result<Pipeline, TemplateError> {
+    let (declaration, rest) = match tokens {
+        [Token::Variable(index, f), Token::Comma, Token::Variable(element, g), op @ (Token::Declare | Token::Assign), rest @ ..]
+            if range && f.is_empty() && g.is_empty() && !index.is_empty() && !element.is_empty() =>
+        {
+            (Some(Declaration { names: vec![index.clone(), element.clone()], declare: *op == Token::Declare }), rest)
+        }
+        [Token::Variable(name, fields), op @ (Token::Declare | Token::Assign), rest @ ..] if fields.is_empty() && !name.is_empty() => {
+            (Some(Declaration { names: vec![name.clone()], declare: *op == Token::Declare }), rest)
+        }
+        _ => (None, tokens),
+    };
+
+    let mut commands = Vec::new();
+    let mut start = 0;
+    let mut depth = 0usize;
+
+    for (i, token) in rest.iter().enumerate() {
+        match token {
+            Token::LeftParen => depth += 1,
+            Token::RightParen => depth = depth.checked_sub(1).ok_or_else(|| syntax(line, "unexpected right paren"))?,
+            Token::Pipe if depth == 0 => {
+                commands.push(command(&rest[start..i], line, !commands.is_empty())?);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if depth > 0 {
+        return Err(syntax(line, "unclosed left paren"));
+    }
+
+    if start == 0 && rest.is_empty() {
+        return Err(syntax(line, "missing value for command"));
+    }
+    commands.push(command(&rest[start..], line, !commands.is_empty())?);
+
+    Ok(Pipeline { declaration, commands })
+}
+
+fn command(tokens: &[Token], line: usize, piped: bool) -> Result<Vec<Operand>, TemplateError> {
+    let mut operands = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let operand = match &tokens[i] {
+            Token::Dot => Operand::Dot,
+            Token::Field(fields, false) => Operand::Field(fields.clone()),
+            Token::Variable(name, fields) => Operand::Variable(name.clone(), fields.clone()),
+            Token::Literal(value) => Operand::Literal(value.clone()),
+            Token::Identifier(name) if FUNCTIONS.contains(&name.as_str()) => Operand::Function(name.clone()),
+            Token::Identifier(name) => return Err(TemplateError::UnknownFunction { line, name: name.clone() }),
+            Token::LeftParen => {
+                let mut depth = 0;
+                let close = tokens[i..].iter()
+                    .position(|token| {
+                        match token {
+                            Token::LeftParen => depth += 1,
+                            Token::RightParen => depth -= 1,
+                            _ => {}
+                        }
+                        depth == 0
+                    })
+                    .map(|close| i + close)
+                    .ok_or_else(|| syntax(line, "unclosed left paren"))?;
+
+                let inner = pipeline(&tokens[i + 1..close], line, false)?;
+                if inner.declaration.is_some() {
+                    return Err(syntax(line, "declaration inside parentheses"));
+                }
+
+                i = close;
+                let fields = match tokens.get(i + 1) {
+                    Some(Token::Field(fields, true)) => {
+                        i += 1;
+                        fields.clone()
+                    }
+                    _ => Vec::new(),
+                };
+
+                Operand::Pipeline(Box::new(inner), fields)
+            }
+            token => return Err(syntax(line, format!("unexpected {token:?} in command"))),
+        };
+
+        operands.push(operand);
+        i += 1;
+    }
+
+    match operands.first() {
+        None => Err(syntax(line, "missing value for command")),
+        Some(Operand::Function(_)) => Ok(operands),
+        Some(_) if piped || operands.len() > 1 => Err(syntax(line, "can't give argument to non-function")),
+        Some(_) => Ok(operands),
+    }
+}
+
+fn render_nodes(nodes: &[Node], data: &Value) -> Result<String, TemplateError> {
+    let mut exec = Exec { root: data, variables: Vec::new(), out: String::new() };
+    exec.run(nodes, data)?;
+    Ok(exec.out)
+}
+
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+}
+
+struct Exec<'a> {
+    root: &'a Value,
+    variables: Vec<(String, Value)>,
+    out: String,
+}
+
+impl Exec<'_> {
+    fn run(&mut self, nodes: &[Node], dot: &Value) -> Result<Flow, TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => self.out.push_str(text),
+                Node::Action { line, pipeline } => {
+                    let value = self.pipeline(pipeline, dot, *line)?;
+                    match &pipeline.declaration {
+                        Some(declaration) => self.declare(declaration, &[value], *line)?,
+                        None => self.out.push_str(&print(&value)),
+                    }
+                }
+                Node::Branch { kind, line, pipeline, body, otherwise } => {
+                    let mark = self.variables.len();
+                    let flow = self.branch(*kind, *line, pipeline, body, otherwise, dot);
+                    self.variables.truncate(mark);
+
+                    match flow? {
+                        Flow::Normal => {}
+                        flow => return Ok(flow),
+                    }
+                }
+                Node::Break => return Ok(Flow::Break),
+                Node::Continue => return Ok(Flow::Continue),
+            }
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    fn branch(&mut self, kind: BranchKind, line: usize, pipeline: &Pipeline, body: &[Node], otherwise: &[Node], dot: &Value) -> Result<Flow, TemplateError> {
+        let value = self.pipeline(pipeline, dot, line)?;
+
+        if kind != BranchKind::Range {
+            if let Some(declaration) = &pipeline.declaration {
+                self.declare(declaration, std::slice::from_ref(&value), line)?;
+            }
+
+            return match (truthy(&value), kind) {
+                (true, BranchKind::With) => self.run(body, &value),
+                (true, _) => self.run(body, dot),
+                (false, _) => self.run(otherwise, dot),
+            };
+        }
+
+        let items = match value {
+            Value::Array(items) => items.into_iter().enumerate().map(|(i, item)| (json!(i), item)).collect::<Vec<_>>(),
+            Value::Object(items) => items.into_iter().map(|(key, item)| (Value::String(key), item)).collect(),
+            Value::Number(count) if count.as_u64().is_some() => (0..count.as_u64().unwrap_or_default()).map(|i| (json!(i), json!(i))).collect(),
+            Value::Null => Vec::new(),
+            value => return Err(execution(line, format!("range can't iterate over {}", print(&value)))),
+        };
+
+        if items.is_empty() {
+            return self.run(otherwise, dot);
+        }
+
+        for (key, item) in items {
+            let mark = self.variables.len();
+
+            if let Some(declaration) = &pipeline.declaration {
+                match declaration.names.len() {
+                    1 => self.declare(declaration, std::slice::from_ref(&item), line)?,
+                    _ => self.declare(declaration, &[key, item.clone()], line)?,
+                }
+            }
+
+            let flow = self.run(body, &item);
+            self.variables.truncate(mark);
+
+            if let Flow::Break = flow? {
+                break;
+            }
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    fn declare(&mut self, declaration: &Declaration, values: &[Value], line: usize) -> Result<(), TemplateError> {
+        for (name, value) in declaration.names.iter().zip(values) {
+            if declaration.declare {
+                self.variables.push((name.clone(), value.clone()));
+                continue;
+            }
+
+            match self.variables.iter_mut().rev().find(|(variable, _)| variable == name) {
+                Some((_, variable)) => *variable = value.clone(),
+                None => return Err(execution(line, format!("undefined variable: ${name}"))),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn pipeline(&mut self, pipeline: &Pipeline, dot: &Value, line: usize) -> Result<Value, TemplateError> {
+        let mut value = None;
+
+        for command in &pipeline.commands {
+            value = Some(match &command[0] {
+                Operand::Function(name) => self.call(name, &command[1..], value, dot, line)?,
+                operand => self.operand(operand, dot, line)?,
+            });
+        }
+
+        Ok(value.unwrap_or_default())
+    }
+
+    fn operand(&mut self, operand: &Operand, dot: &Value, line: usize) -> Result<Value, TemplateError> {
+        match operand {
+            Operand::Dot => Ok(dot.clone()),
+            Operand::Field(fields) => walk(dot.clone(), fields, line),
+            Operand::Variable(name, fields) if name.is_empty() => walk(self.root.clone(), fields, line),
+            Operand::Variable(name, fields) => {
+                let value = self.variables.iter()
+                    .rev()
+                    .find(|(variable, _)| variable == name)
+                    .map(|(_, value)| value.clone())
+                    .ok_or_else(|| execution(line, format!("undefined variable: ${name}")))?;
+
+                walk(value, fields, line)
+            }
+            Operand::Function(name) => self.call(name, &[], None, dot, line),
+            Operand::Literal(value) => Ok(value.clone()),
+            Operand::Pipeline(pipeline, fields) => {
+                let value = self.pipeline(pipeline, dot, line)?;
+                walk(value, fields, line)
+            }
+        }
+    }
+
+    fn call(&mut self, name: &str, operands: &[Operand], piped: Option<Value>, dot: &Value, line: usize) -> Result<Value, TemplateError> {
+        // `and` and `or` stop at the first operand deciding the result
+        if name == "and" || name == "or" {
+            if operands.len() + piped.is_some() as usize == 0 {
+                return Err(execution(line, format!("wrong number of args for {name}: want at least 1 got 0")));
+            }
+
+            let mut value = Value::Null;
+            for operand in operands {
+                value = self.operand(operand, dot, line)?;
+                if truthy(&value) == (name == "or") {
+                    return Ok(value);
+                }
+            }
+
+            return Ok(piped.unwrap_or(value));
+        }
+
+        let mut args = operands.iter()
+            .map(|operand| self.operand(operand, dot, line))
+            .collect::<Result<Vec<_>, _>>()?;
+        args.extend(piped);
+
+        function(name, &args).map_err(|message| execution(line, format!("error calling {name}: {message}")))
+    }
+}
+
+/// Field of a value, by name or by snake case name
+fn walk(mut value: Value, fields: &[String], line: usize) -> Result<Value, TemplateError> {
+    for field in fields {
+        value = match value {
+            Value::Object(mut map) => match map.remove(field) {
+                Some(value) => value,
+                None => map.remove(&snake_case(field)).unwrap_or_default(),
+            },
+            Value::Null => Value::Null,
+            value => return Err(execution(line, format!("can't evaluate field {field} in {}", type_name(&value)))),
+        };
+    }
+
+    Ok(value)
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+
+    for (i, c) in name.char_indices() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+
+    snake
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "nil",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(value) => value.as_f64() != Some(0.0),
+        Value::String(value) => !value.is_empty(),
+        Value::Array(value) => !value.is_empty(),
+        Value::Object(value) => !value.is_empty(),
+    }
+}
+
+fn print(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn integer(value: &Value) -> Result<i64, String> {
+    value.as_i64().ok_or_else(|| format!("expected integer, got {}", type_name(value)))
+}
+
+fn compare(a: &Value, b: &Value) -> Result<std::cmp::Ordering, String> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => Ok(a.as_f64().unwrap_or_default().total_cmp(&b.as_f64().unwrap_or_default())),
+        (Value::String(a), Value::String(b)) => Ok(a.cmp(b)),
+        (a, b) => Err(format!("incompatible types for comparison: {} and {}", type_name(a), type_name(b))),
+    }
+}
+
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (a, b) => a == b,
+    }
+}
+
+fn function(name: &str, args: &[Value]) -> Result<Value, String> {
+    let arity = |count: usize| match args.len() == count {
+        true => Ok(()),
+        false => Err(format!("wrong number of args: want {count} got {}", args.len())),
+    };
+
+    Ok(match name {
+        "not" => {
+            arity(1)?;
+            Value::Bool(!truthy(&args[0]))
+        }
+        "eq" => {
+            if args.len() < 2 {
+                return Err("missing argument for comparison".to_string());
+            }
+            Value::Bool(args[1..].iter().any(|arg| equal(&args[0], arg)))
+        }
+        "ne" => {
+            arity(2)?;
+            Value::Bool(!equal(&args[0], &args[1]))
+        }
+        "lt" | "le" | "gt" | "ge" => {
+            arity(2)?;
+            let ordering = compare(&args[0], &args[1])?;
+            Value::Bool(match name {
+                "lt" => ordering.is_lt(),
+                "le" => ordering.is_le(),
+                "gt" => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            })
+        }
+        "len" => {
+            arity(1)?;
+            json!(match &args[0] {
+                Value::String(value) => value.len(),
+                Value::Array(value) => value.len(),
+                Value::Object(value) => value.len(),
+                value => return Err(format!("len of {}", type_name(value))),
+            })
+        }
+        "index" => {
+            let Some((item, keys)) = args.split_first() else {
+                return Err("wrong number of args: want at least 1 got 0".to_string());
+            };
+
+            let mut item = item.clone();
+            for key in keys {
+                item = match (item, key) {
+                    (Value::Array(mut items), key) => {
+                        let i = integer(key)?;
+                        match usize::try_from(i).ok().filter(|&i| i < items.len()) {
+                            Some(i) => items.swap_remove(i),
+                            None => return Err(format!("index out of range: {i}")),
+                        }
+                    }
+                    (Value::Object(mut map), Value::String(key)) => map.remove(key).unwrap_or_default(),
+                    (item, key) => return Err(format!("can't index {} with {}", type_name(&item), type_name(key))),
+                };
+            }
+            item
+        }
+        "slice" => {
+            let Some((item, bounds)) = args.split_first() else {
+                return Err("wrong number of args: want at least 1 got 0".to_string());
+            };
+
+            let len = match item {
+                Value::String(value) => value.len(),
+                Value::Array(value) => value.len(),
+                value => return Err(format!("can't slice {}", type_name(value))),
+            };
+
+            let bounds = bounds.iter().map(integer).collect::<Result<Vec<_>, _>>()?;
+            let (start, end) = match bounds[..] {
+                [] => (0, len as i64),
+                [start] => (start, len as i64),
+                [start, end] => (start, end),
+                _ => return Err("too many slice indexes".to_string()),
+            };
+
+            if start < 0 || start > end || end > len as i64 {
+                return Err(format!("slice index out of range: [{start}:{end}] with length {len}"));
+            }
+            let range = start as usize..end as usize;
+
+            match item {
+                Value::String(value) => json!(value.get(range).ok_or("slice is not at a character boundary")?),
+                Value::Array(value) => json!(value[range]),
+                _ => unreachable!(),
+            }
+        }
+        "print" | "println" => {
+            let mut out = String::new();
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 && (name == "println" || !(arg.is_string() || args[i - 1].is_string())) {
+                    out.push(' ');
+                }
+                out.push_str(&print(arg));
+            }
+            if name == "println" {
+                out.push('\n');
+            }
+            Value::String(out)
+        }
+        "printf" => {
+            let Some((Value::String(format), args)) = args.split_first() else {
+                return Err("format must be a string".to_string());
+            };
+            Value::String(printf(format, args)?)
+        }
+        "json" => {
+            arity(1)?;
+            Value::String(args[0].to_string())
+        }
+        "currentDate" => {
+            arity(0)?;
+            Value::String(date(0))
+        }
+        "yesterdayDate" => {
+            arity(0)?;
+            Value::String(date(1))
+        }
+        _ => return Err("function not defined".to_string()),
+    })
+}
+
+/// `fmt.Sprintf` for the `%v`, `%s`, `%d`, `%f`, `%t` and `%q` verbs
+fn printf(format: &str, args: &[Value]) -> Result<String, String> {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut chars = format.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+
+        let verb = chars.next().ok_or("missing verb")?;
+        match verb {
+            '%' => {
+                out.push('%');
+                continue;
+            }
+            'v' | 's' | 'd' | 'f' | 't' | 'q' => {}
+            // Flags, width and precision included
+            _ => return Err(format!("unsupported format %{verb}")),
+        }
+
+        let Some(arg) = args.next() else {
+            out.push_str(&format!("%!{verb}(MISSING)"));
+            continue;
+        };
+
+        match (verb, arg) {
+            ('v' | 's', arg) => out.push_str(&print(arg)),
+            ('d', Value::Number(number)) if number.is_i64() || number.is_u64() => out.push_str(&number.to_string()),
+            ('f', Value::Number(number)) => out.push_str(&format!("{:.6}", number.as_f64().unwrap_or_default())),
+            ('t', Value::Bool(value)) => out.push_str(&value.to_string()),
+            ('q', Value::String(value)) => out.push_str(&json!(value).to_string()),
+            (verb, arg) => out.push_str(&format!("%!{verb}({})", print(arg))),
+        }
+    }
+
+    Ok(out)
+}
+
+/// Local date `days` days ago, as `YYYY-MM-DD`
+#[cfg(feature = "chrono")]
+fn date(days: i64) -> String {
+    (chrono::Local::now() - chrono::Duration::days(days)).format("%Y-%m-%d").to_string()
+}
+
+/// UTC date `days` days ago, as `YYYY-MM-DD`
+#[cfg(not(feature = "chrono"))]
+fn date(days: i64) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+
+    // Civil date from days since the epoch, after Howard Hinnant
+    let z = now / 86_400 - days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, data: Value) -> String {
+        Template::parse(template).unwrap().render(&data).unwrap()
+    }
+
+    #[test]
+    fn actions() {
+        let data = json!({ "Name": "sky", "Colors": ["red", "blue"], "Empty": [], "Nested": { "tool_calls": [1] } });
+
+        assert_eq!(render("The {{ .Name }} is {{ index .Colors 1 }}.", data.clone()), "The sky is blue.");
+        assert_eq!(render("a  {{- /* comment */ -}}  b", data.clone()), "ab");
+        assert_eq!(render("{{ if .Empty }}x{{ else if .Name }}y{{ else }}z{{ end }}", data.clone()), "y");
+        assert_eq!(render("{{ with .Nested }}{{ len .ToolCalls }}{{ end }}", data.clone()), "1");
+        assert_eq!(render("{{ range $i, $c := .Colors }}{{ if $i }}, {{ end }}{{ $i }}={{ $c }}{{ end }}", data.clone()), "0=red, 1=blue");
+        assert_eq!(render("{{ range .Empty }}x{{ else }}none{{ end }}", data.clone()), "none");
+        assert_eq!(render("{{ range .Colors }}{{ if eq . \"red\" }}{{ continue }}{{ end }}{{ . }}{{ break }}{{ end }}", data.clone()), "blue");
+        assert_eq!(render("{{ $n := 0 }}{{ range .Colors }}{{ $n = len . }}{{ end }}{{ $n }}", data.clone()), "4");
+        assert_eq!(render("{{ $last := eq (len (slice $.Colors 1)) 1 }}{{ $last }}", data.clone()), "true");
+        assert_eq!(render("{{ .Colors | json }} {{ .Nested }}", data.clone()), r#"["red","blue"] {"tool_calls":[1]}"#);
+        assert_eq!(render("{{ and .Name .Empty 1 }}|{{ or .Empty .Name }}|{{ not .Missing }}", data.clone()), "[]|sky|true");
+        assert_eq!(render("{{ printf \"%s=%d %q\" \"n\" 3 `a\"b` }}|{{ print 1 2 \"x\" }}", data.clone()), "n=3 \"a\\\"b\"|1 2x");
+        assert_eq!(render("{{ lt 1 2.5 }} {{ ge \"b\" \"a\" }} {{ ne .Name \"sea\" }}", data), "true true true");
+        assert_eq!(render("{{ currentDate }}", Value::Null).len(), 10);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Template::parse("{{ .A }}\n{{ if .B }}").unwrap_err(), syntax(2, "unexpected EOF, missing {{end}}"));
+        assert_eq!(Template::parse("\n\n{{ toUpper .A }}").unwrap_err(), TemplateError::UnknownFunction { line: 3, name: "toUpper".to_string() });
+        assert_eq!(Template::parse("{{ end }}").unwrap_err(), syntax(1, "unexpected {{end}}"));
+        assert_eq!(Template::parse("{{ break }}").unwrap_err(), syntax(1, "{{break}} outside {{range}}"));
+        assert!(Template::parse("{{ .A").is_err());
+        assert!(Template::parse("{{ .A .B }}").is_err());
+
+        let template = Template::parse("{{ lt .A 1 }}").unwrap();
+        assert!(matches!(template.render(&json!({ "A": "x" })), Err(TemplateError::Execution { line: 1, .. })));
+        assert!(Template::parse("{{ $x }}").unwrap().render(&Value::Null).is_err());
+        assert!(matches!(Template::parse("{{ 1e400 }}"), Err(TemplateError::Execution { line: 1, .. })));
+
+        for format in ["%5d", "%.2f", "%-10s", "%x"] {
+            let template = Template::parse(&format!("{{{{ printf {format:?} 3 }}}}")).unwrap();
+            assert!(matches!(template.render(&Value::Null), Err(TemplateError::Execution { line: 1, .. })), "{format}");
+        }
+    }
+
+    const MESSAGES_TEMPLATE: &str = "\
+{{- if .System }}<|system|>{{ .System }}<|end|>{{ end }}
+{{- range $i, $_ := .Messages }}
+{{- $last := eq (len (slice $.Messages $i)) 1 }}
+{{- if eq .Role \"user\" }}<|user|>{{ .Content }}<|end|>
+{{- else if eq .Role \"assistant\" }}<|assistant|>{{ .Content }}
+{{- range .ToolCalls }}[{{ .Function.Name }} {{ .Function.Arguments }}]{{ end }}
+{{- if not $last }}<|end|>{{ end }}
+{{- end }}
+{{- if and $last (ne .Role \"assistant\") }}<|assistant|>{{ end }}
+{{- end }}";
+
+    const LEGACY_TEMPLATE: &str = "{{ if .System }}S:{{ .System }}\n{{ end }}{{ if .Prompt }}U:{{ .Prompt }}\n{{ end }}A:{{ .Response }}\n";
+
+    fn chat(messages: Value) -> ChatRequest {
+        serde_json::from_value(json!({ "model": "m", "messages": messages })).unwrap()
+    }
+
+    #[test]
+    fn chat_prompts() {
+        let request = chat(json!([
+            { "role": "system", "content": "Be brief." },
+            { "role": "user", "content": "Weather?" },
+            { "role": "assistant", "content": "", "tool_calls": [{ "function": { "name": "weather", "arguments": { "city": "Paris" } } }] },
+            { "role": "user", "content": "Thanks" },
+        ]));
+
+        let template = Template::parse(MESSAGES_TEMPLATE).unwrap();
+        assert_eq!(
+            template.render_chat(&request).unwrap(),
+            r#"<|system|>Be brief.<|end|><|user|>Weather?<|end|><|assistant|>[weather {"city":"Paris"}]<|end|><|user|>Thanks<|end|><|assistant|>"#,
+        );
+
+        // Without `.Messages`, one rendering per turn, the last one cut after `.Response`
+        let template = Template::parse(LEGACY_TEMPLATE).unwrap();
+        let request = chat(json!([
+            { "role": "system", "content": "Be brief." },
+            { "role": "user", "content": "Hi" },
+            { "role": "assistant", "content": "Hello" },
+            { "role": "user", "content": "Why?" },
+        ]));
+        assert_eq!(template.render_chat(&request).unwrap(), "S:Be brief.\nU:Hi\nA:Hello\nU:Why?\nA:");
+    }
+
+    #[test]
+    fn generate_prompts() {
+        let template = Template::parse(LEGACY_TEMPLATE).unwrap();
+        let request = |value: Value| serde_json::from_value::<GenerationRequest>(value).unwrap();
+
+        assert_eq!(template.render_generate(&request(json!({ "model": "m", "prompt": "Hi", "system": "Sys" }))).unwrap(), "S:Sys\nU:Hi\nA:");
+        assert_eq!(template.render_generate(&request(json!({ "model": "m", "prompt": "Hi", "raw": true }))).unwrap(), "Hi");
+
+        let template = Template::parse("<PRE>{{ .Prompt }}<SUF>{{ .Suffix }}<MID>").unwrap();
+        assert_eq!(template.render_generate(&request(json!({ "model": "m", "prompt": "fn a", "suffix": "}" }))).unwrap(), "<PRE>fn a<SUF>}<MID>");
+    }
+}
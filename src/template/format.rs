@@ -0,0 +1,552 @@
+//! Raw prompt formats
+//!
+//! [`PromptFormat`]s turn a conversation into the prompt a model family was
+//! trained on, for generation requests with `raw: true`, along with the stop
+//! sequences ending an assistant turn. Use [`Template`](super::Template)
+//! instead to reproduce the template of a specific model.
+//!
+//! The beginning-of-sequence token is left out of prompts: Ollama adds it when
+//! tokenizing, like for templated prompts.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use ollama_rest::{models::chat::Message, template::format::{ChatMl, PromptFormat}};
+//! use serde_json::json;
+//!
+//! let messages = serde_json::from_value::<Vec<Message>>(json!([
+//!     { "role": "system", "content": "Be brief." },
+//!     { "role": "user", "content": "Why is the sky blue?" },
+//! ])).unwrap();
+//!
+//! let request = ChatMl.generation_request("qwen2.5", &messages, &[]);
+//!
+//! assert_eq!(request.raw, Some(true));
+//! assert_eq!(
+//!     request.prompt,
+//!     "<|im_start|>system\nBe brief.<|im_end|>\n<|im_start|>user\nWhy is the sky blue?<|im_end|>\n<|im_start|>assistant\n",
+//! );
+//! ```
+
+use std::sync::Arc;
+
+use serde_json::{json, Map};
+
+use crate::models::{
+    chat::{Message, Role, ToolCall},
+    generate::GenerationRequest,
+    json_schema::JsonSchema,
+};
+
+/// Chat prompt format of a model family
+///
+/// Prompts end with the opening of an assistant turn. A final assistant
+/// message is left open instead, for the model to continue it.
+pub trait PromptFormat {
+    /// Raw prompt of a conversation
+    ///
+    /// ## Parameters
+    /// - `messages`: Conversation, including tool calls and tool results
+    /// - `tools`: Tools the model may call, empty for none
+    fn format(&self, messages: &[Message], tools: &[JsonSchema]) -> String;
+
+    /// Sequences ending an assistant turn
+    fn stop_sequences(&self) -> &'static [&'static str];
+
+    /// Raw generation request of a conversation, stopping at [`PromptFormat::stop_sequences`]
+    fn generation_request(&self, model: &str, messages: &[Message], tools: &[JsonSchema]) -> GenerationRequest {
+        let mut options = Map::new();
+        options.insert("stop".to_string(), json!(self.stop_sequences()));
+
+        GenerationRequest {
+            model: model.to_string(),
+            prompt: self.format(messages, tools),
+            suffix: None,
+            images: None,
+            format: None,
+            options: Some(options),
+            system: None,
+            template: None,
+            stream: None,
+            raw: Some(true),
+            keep_alive: None,
+        }
+    }
+}
+
+impl<T: PromptFormat + ?Sized> PromptFormat for Arc<T> {
+    fn format(&self, messages: &[Message], tools: &[JsonSchema]) -> String {
+        (**self).format(messages, tools)
+    }
+
+    fn stop_sequences(&self) -> &'static [&'static str] {
+        (**self).stop_sequences()
+    }
+}
+
+/// System messages joined by blank lines, and the other messages
+fn split_system(messages: &[Message]) -> (String, Vec<&Message>) {
+    let (system, rest): (Vec<&Message>, Vec<&Message>) = messages.iter().partition(|message| message.role == Role::System);
+    let system = system.iter().map(|message| message.content.as_str()).collect::<Vec<_>>().join("\n\n");
+
+    (system, rest)
+}
+
+/// Tool call as `{"name": ..., "<arguments>": {...}}`
+fn call(call: &ToolCall, arguments: &str) -> String {
+    let ToolCall::Function { name, arguments: values } = call;
+    format!("{{\"name\": {}, \"{arguments}\": {}}}", json!(name), json!(values))
+}
+
+/// One JSON tool definition per line
+fn tool_lines(tools: &[JsonSchema]) -> String {
+    tools.iter().map(|tool| json!(tool).to_string()).collect::<Vec<_>>().join("\n")
+}
+
+fn tool_calls(message: &Message) -> &[ToolCall] {
+    message.tool_calls.as_deref().unwrap_or_default()
+}
+
+/// Whether the turn at `i` is an unfinished final assistant message
+fn is_open(messages: &[&Message], i: usize) -> bool {
+    i + 1 == messages.len() && messages[i].role == Role::Assistant
+}
+
+/// ChatML, as used by many fine-tunes, with Hermes-style tool calls
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChatMl;
+
+/// Qwen 2.5 and 3: ChatML with Qwen's tool instructions
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Qwen;
+
+/// Llama 3.1 to 3.3, with JSON tool calls
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Llama3;
+
+/// Mistral instruct v3 and later, with `[TOOL_CALLS]`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mistral;
+
+/// Gemma 2 and 3, with `tool_code` blocks for tool calls
+///
+/// Gemma has no system role: system messages open the first user turn.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gemma;
+
+/// Phi 3 and 4, with `<|tool_call|>` tool calls
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Phi;
+
+const HERMES_TOOLS: &str = "# Tools\n\nYou may call one or more functions to assist with the user query.\n\n\
+You are provided with function signatures within <tools></tools> XML tags:\n<tools>\n";
+
+const HERMES_CALLS: &str = "\n</tools>\n\nFor each function call, return a json object with function name and arguments within <tool_call></tool_call> XML tags:\n\
+<tool_call>\n{\"name\": <function-name>, \"arguments\": <args-json-object>}\n</tool_call>";
+
+fn chatml(system: String, messages: &[&Message], tools: &[JsonSchema]) -> String {
+    let mut prompt = String::new();
+
+    let system = match tools.is_empty() {
+        true => system,
+        false if system.is_empty() => format!("{HERMES_TOOLS}{}{HERMES_CALLS}", tool_lines(tools)),
+        false => format!("{system}\n\n{HERMES_TOOLS}{}{HERMES_CALLS}", tool_lines(tools)),
+    };
+    if !system.is_empty() {
+        prompt.push_str(&format!("<|im_start|>system\n{system}<|im_end|>\n"));
+    }
+
+    for (i, message) in messages.iter().enumerate() {
+        match message.role {
+            Role::Tool => {
+                // Consecutive tool results share one user turn
+                if i == 0 || messages[i - 1].role != Role::Tool {
+                    prompt.push_str("<|im_start|>user");
+                }
+                prompt.push_str(&format!("\n<tool_response>\n{}\n</tool_response>", message.content));
+                if messages.get(i + 1).is_none_or(|next| next.role != Role::Tool) {
+                    prompt.push_str("<|im_end|>\n");
+                }
+            }
+            role => {
+                prompt.push_str(&format!("<|im_start|>{role}\n{}", message.content));
+
+                for (j, tool_call) in tool_calls(message).iter().enumerate() {
+                    if j > 0 || !message.content.is_empty() {
+                        prompt.push('\n');
+                    }
+                    prompt.push_str(&format!("<tool_call>\n{}\n</tool_call>", call(tool_call, "arguments")));
+                }
+
+                if !is_open(messages, i) {
+                    prompt.push_str("<|im_end|>\n");
+                }
+            }
+        }
+    }
+
+    if messages.last().is_none_or(|message| message.role != Role::Assistant) {
+        prompt.push_str("<|im_start|>assistant\n");
+    }
+
+    prompt
+}
+
+impl PromptFormat for ChatMl {
+    fn format(&self, messages: &[Message], tools: &[JsonSchema]) -> String {
+        let (system, messages) = split_system(messages);
+        chatml(system, &messages, tools)
+    }
+
+    fn stop_sequences(&self) -> &'static [&'static str] {
+        &["<|im_start|>", "<|im_end|>"]
+    }
+}
+
+impl PromptFormat for Qwen {
+    fn format(&self, messages: &[Message], tools: &[JsonSchema]) -> String {
+        let (mut system, messages) = split_system(messages);
+
+        if system.is_empty() && !tools.is_empty() {
+            system = "You are Qwen, created by Alibaba Cloud. You are a helpful assistant.".to_string();
+        }
+
+        chatml(system, &messages, tools)
+    }
+
+    fn stop_sequences(&self) -> &'static [&'static str] {
+        &["<|im_start|>", "<|im_end|>", "<|endoftext|>"]
+    }
+}
+
+impl PromptFormat for Llama3 {
+    fn format(&self, messages: &[Message], tools: &[JsonSchema]) -> String {
+        let (mut system, messages) = split_system(messages);
+        let mut prompt = String::new();
+
+        if !tools.is_empty() {
+            if !system.is_empty() {
+                system.push_str("\n\n");
+            }
+            system.push_str("When you receive a tool call response, use the output to format an answer to the original user question.\n\n\
+You are a helpful assistant with tool calling capabilities.");
+        }
+
+        if !system.is_empty() {
+            prompt.push_str(&format!("<|start_header_id|>system<|end_header_id|>\n\n{system}<|eot_id|>"));
+        }
+
+        let last_user = messages.iter().rposition(|message| message.role == Role::User);
+
+        for (i, message) in messages.iter().enumerate() {
+            let header = match message.role {
+                Role::Tool => "ipython",
+                _ => message.role.as_str(),
+            };
+            prompt.push_str(&format!("<|start_header_id|>{header}<|end_header_id|>\n\n"));
+
+            // Tool definitions go with the last question
+            if Some(i) == last_user && !tools.is_empty() {
+                prompt.push_str("Given the following functions, please respond with a JSON for a function call with its proper arguments that best answers the given prompt.\n\n\
+Respond in the format {\"name\": function name, \"parameters\": dictionary of argument name and its value}. Do not use variables.\n\n");
+                prompt.push_str(&tools.iter().map(|tool| json!(tool).to_string()).collect::<Vec<_>>().join("\n\n"));
+                prompt.push_str("\n\n");
+            }
+
+            prompt.push_str(&message.content);
+            for tool_call in tool_calls(message) {
+                prompt.push_str(&call(tool_call, "parameters"));
+            }
+
+            if !is_open(&messages, i) {
+                prompt.push_str("<|eot_id|>");
+            }
+        }
+
+        if messages.last().is_none_or(|message| message.role != Role::Assistant) {
+            prompt.push_str("<|start_header_id|>assistant<|end_header_id|>\n\n");
+        }
+
+        prompt
+    }
+
+    fn stop_sequences(&self) -> &'static [&'static str] {
+        &["<|start_header_id|>", "<|end_header_id|>", "<|eot_id|>", "<|eom_id|>"]
+    }
+}
+
+impl PromptFormat for Mistral {
+    fn format(&self, messages: &[Message], tools: &[JsonSchema]) -> String {
+        let (system, messages) = split_system(messages);
+        let mut prompt = String::new();
+        let last_user = messages.iter().rposition(|message| message.role == Role::User);
+        let available_tools = match tools.is_empty() {
+            true => String::new(),
+            false => format!("[AVAILABLE_TOOLS] {}[/AVAILABLE_TOOLS]", json!(tools)),
+        };
+
+        // Without a question to go with, tools and system text stand alone
+        if last_user.is_none() {
+            prompt.push_str(&available_tools);
+            if !system.is_empty() {
+                prompt.push_str(&format!("[INST] {system}[/INST]"));
+            }
+        }
+
+        for (i, message) in messages.iter().enumerate() {
+            match message.role {
+                Role::User => {
+                    if Some(i) == last_user {
+                        prompt.push_str(&available_tools);
+                    }
+
+                    prompt.push_str("[INST] ");
+                    if Some(i) == last_user && !system.is_empty() {
+                        prompt.push_str(&format!("{system}\n\n"));
+                    }
+                    prompt.push_str(&format!("{}[/INST]", message.content));
+                }
+                Role::Assistant => {
+                    if !message.content.is_empty() {
+                        prompt.push_str(&format!(" {}", message.content));
+                    }
+                    if !tool_calls(message).is_empty() {
+                        let calls = tool_calls(message).iter().map(|tool_call| call(tool_call, "arguments")).collect::<Vec<_>>();
+                        prompt.push_str(&format!("[TOOL_CALLS] [{}]", calls.join(", ")));
+                    }
+
+                    if !is_open(&messages, i) {
+                        prompt.push_str("</s>");
+                    }
+                }
+                Role::Tool => prompt.push_str(&format!("[TOOL_RESULTS] {}[/TOOL_RESULTS]", json!({ "content": message.content }))),
+                Role::System => {}
+            }
+        }
+
+        prompt
+    }
+
+    fn stop_sequences(&self) -> &'static [&'static str] {
+        &["[INST]", "[/INST]", "</s>"]
+    }
+}
+
+impl PromptFormat for Gemma {
+    fn format(&self, messages: &[Message], tools: &[JsonSchema]) -> String {
+        let (system, messages) = split_system(messages);
+        let mut prompt = String::new();
+
+        let mut preamble = system;
+        if !tools.is_empty() {
+            if !preamble.is_empty() {
+                preamble.push_str("\n\n");
+            }
+            preamble.push_str("You have access to functions. If you decide to invoke any of the function(s), put it in the format\n\
+```tool_code\n{\"name\": function name, \"arguments\": dictionary of argument name and its value}\n```\n\n");
+            preamble.push_str(&tool_lines(tools));
+        }
+
+        for (i, message) in messages.iter().enumerate() {
+            let continued = i > 0 && message.role == Role::Tool && messages[i - 1].role == Role::Tool;
+            if !continued {
+                let role = match message.role {
+                    Role::Assistant => "model",
+                    _ => "user",
+                };
+                prompt.push_str(&format!("<start_of_turn>{role}\n"));
+            }
+
+            if !preamble.is_empty() && message.role != Role::Assistant {
+                prompt.push_str(&format!("{}\n\n", std::mem::take(&mut preamble)));
+            }
+
+            match message.role {
+                Role::Tool => prompt.push_str(&format!("```tool_output\n{}\n```", message.content)),
+                _ => prompt.push_str(&message.content),
+            }
+
+            for (j, tool_call) in tool_calls(message).iter().enumerate() {
+                if j > 0 || !message.content.is_empty() {
+                    prompt.push('\n');
+                }
+                prompt.push_str(&format!("```tool_code\n{}\n```", call(tool_call, "arguments")));
+            }
+
+            let continues = message.role == Role::Tool && messages.get(i + 1).is_some_and(|next| next.role == Role::Tool);
+            if continues {
+                prompt.push('\n');
+            } else if !is_open(&messages, i) {
+                prompt.push_str("<end_of_turn>\n");
+            }
+        }
+
+        if messages.last().is_none_or(|message| message.role != Role::Assistant) {
+            prompt.push_str("<start_of_turn>model\n");
+        }
+
+        prompt
+    }
+
+    fn stop_sequences(&self) -> &'static [&'static str] {
+        &["<end_of_turn>"]
+    }
+}
+
+impl PromptFormat for Phi {
+    fn format(&self, messages: &[Message], tools: &[JsonSchema]) -> String {
+        let (system, messages) = split_system(messages);
+        let mut prompt = String::new();
+
+        if !system.is_empty() || !tools.is_empty() {
+            prompt.push_str("<|system|>\n");
+            match system.is_empty() {
+                true => prompt.push_str("You are a helpful assistant with some tools."),
+                false => prompt.push_str(&system),
+            }
+            if !tools.is_empty() {
+                prompt.push_str(&format!("<|tool|>{}<|/tool|>", json!(tools)));
+            }
+            prompt.push_str("<|end|>\n");
+        }
+
+        for (i, message) in messages.iter().enumerate() {
+            prompt.push_str(&format!("<|{}|>\n{}", message.role, message.content));
+
+            if !tool_calls(message).is_empty() {
+                let calls = tool_calls(message).iter().map(|tool_call| call(tool_call, "arguments")).collect::<Vec<_>>();
+                prompt.push_str(&format!("<|tool_call|>[{}]<|/tool_call|>", calls.join(", ")));
+            }
+
+            if !is_open(&messages, i) {
+                prompt.push_str("<|end|>\n");
+            }
+        }
+
+        if messages.last().is_none_or(|message| message.role != Role::Assistant) {
+            prompt.push_str("<|assistant|>\n");
+        }
+
+        prompt
+    }
+
+    fn stop_sequences(&self) -> &'static [&'static str] {
+        &["<|end|>", "<|user|>", "<|assistant|>", "<|endoftext|>"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn messages(value: Value) -> Vec<Message> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn tools() -> Vec<JsonSchema> {
+        serde_json::from_value(json!([{
+            "type": "function",
+            "function": { "name": "weather", "description": "Get the weather" },
+        }])).unwrap()
+    }
+
+    fn tool_conversation() -> Vec<Message> {
+        messages(json!([
+            { "role": "system", "content": "Be brief." },
+            { "role": "user", "content": "Weather in Paris?" },
+            { "role": "assistant", "content": "", "tool_calls": [{ "function": { "name": "weather", "arguments": { "city": "Paris" } } }] },
+            { "role": "tool", "content": "Sunny" },
+        ]))
+    }
+
+    #[test]
+    fn plain_conversations() {
+        let conversation = messages(json!([
+            { "role": "system", "content": "Be brief." },
+            { "role": "user", "content": "Hi" },
+            { "role": "assistant", "content": "Hello" },
+            { "role": "user", "content": "Why?" },
+        ]));
+
+        assert_eq!(
+            Llama3.format(&conversation, &[]),
+            "<|start_header_id|>system<|end_header_id|>\n\nBe brief.<|eot_id|>\
+<|start_header_id|>user<|end_header_id|>\n\nHi<|eot_id|>\
+<|start_header_id|>assistant<|end_header_id|>\n\nHello<|eot_id|>\
+<|start_header_id|>user<|end_header_id|>\n\nWhy?<|eot_id|>\
+<|start_header_id|>assistant<|end_header_id|>\n\n",
+        );
+        assert_eq!(Mistral.format(&conversation, &[]), "[INST] Hi[/INST] Hello</s>[INST] Be brief.\n\nWhy?[/INST]");
+        assert_eq!(
+            Gemma.format(&conversation, &[]),
+            "<start_of_turn>user\nBe brief.\n\nHi<end_of_turn>\n<start_of_turn>model\nHello<end_of_turn>\n<start_of_turn>user\nWhy?<end_of_turn>\n<start_of_turn>model\n",
+        );
+        assert_eq!(
+            Phi.format(&conversation, &[]),
+            "<|system|>\nBe brief.<|end|>\n<|user|>\nHi<|end|>\n<|assistant|>\nHello<|end|>\n<|user|>\nWhy?<|end|>\n<|assistant|>\n",
+        );
+
+        // A final assistant message is continued
+        let prefilled = messages(json!([{ "role": "user", "content": "Hi" }, { "role": "assistant", "content": "Hel" }]));
+        assert_eq!(ChatMl.format(&prefilled, &[]), "<|im_start|>user\nHi<|im_end|>\n<|im_start|>assistant\nHel");
+        assert_eq!(Phi.format(&prefilled, &[]), "<|user|>\nHi<|end|>\n<|assistant|>\nHel");
+    }
+
+    #[test]
+    fn tool_calls_and_results() {
+        let conversation = tool_conversation();
+
+        let prompt = Qwen.format(&conversation, &tools());
+        assert!(prompt.starts_with("<|im_start|>system\nBe brief.\n\n# Tools\n"));
+        assert!(prompt.contains(r#"<tools>
+{"function":{"description":"Get the weather","name":"weather","parameters":null},"type":"function"}
+</tools>"#));
+        assert!(prompt.ends_with("<|im_start|>user\nWeather in Paris?<|im_end|>\n\
+<|im_start|>assistant\n<tool_call>\n{\"name\": \"weather\", \"arguments\": {\"city\":\"Paris\"}}\n</tool_call><|im_end|>\n\
+<|im_start|>user\n<tool_response>\nSunny\n</tool_response><|im_end|>\n<|im_start|>assistant\n"));
+
+        let prompt = Llama3.format(&conversation, &tools());
+        assert!(prompt.contains("{\"name\": \"weather\", \"parameters\": {\"city\":\"Paris\"}}<|eot_id|>"));
+        assert!(prompt.ends_with("<|start_header_id|>ipython<|end_header_id|>\n\nSunny<|eot_id|><|start_header_id|>assistant<|end_header_id|>\n\n"));
+
+        assert_eq!(
+            Mistral.format(&conversation, &tools()),
+            "[AVAILABLE_TOOLS] [{\"function\":{\"description\":\"Get the weather\",\"name\":\"weather\",\"parameters\":null},\"type\":\"function\"}][/AVAILABLE_TOOLS]\
+[INST] Be brief.\n\nWeather in Paris?[/INST][TOOL_CALLS] [{\"name\": \"weather\", \"arguments\": {\"city\":\"Paris\"}}]</s>\
+[TOOL_RESULTS] {\"content\":\"Sunny\"}[/TOOL_RESULTS]",
+        );
+
+        // Calls follow any text, and tools are kept without a question
+        let answered = messages(json!([
+            { "role": "system", "content": "Be brief." },
+            { "role": "assistant", "content": "Let me check.", "tool_calls": [{ "function": { "name": "weather", "arguments": { "city": "Paris" } } }] },
+            { "role": "tool", "content": "Sunny" },
+        ]));
+        assert_eq!(
+            Mistral.format(&answered, &tools()),
+            "[AVAILABLE_TOOLS] [{\"function\":{\"description\":\"Get the weather\",\"name\":\"weather\",\"parameters\":null},\"type\":\"function\"}][/AVAILABLE_TOOLS]\
+[INST] Be brief.[/INST] Let me check.[TOOL_CALLS] [{\"name\": \"weather\", \"arguments\": {\"city\":\"Paris\"}}]</s>\
+[TOOL_RESULTS] {\"content\":\"Sunny\"}[/TOOL_RESULTS]",
+        );
+
+        let prompt = Gemma.format(&conversation, &tools());
+        assert!(prompt.contains("<start_of_turn>model\n```tool_code\n{\"name\": \"weather\", \"arguments\": {\"city\":\"Paris\"}}\n```<end_of_turn>\n"));
+        assert!(prompt.ends_with("<start_of_turn>user\n```tool_output\nSunny\n```<end_of_turn>\n<start_of_turn>model\n"));
+
+        let prompt = Phi.format(&conversation, &tools());
+        assert!(prompt.starts_with("<|system|>\nBe brief.<|tool|>[{"));
+        assert!(prompt.contains("<|assistant|>\n<|tool_call|>[{\"name\": \"weather\", \"arguments\": {\"city\":\"Paris\"}}]<|/tool_call|><|end|>\n<|tool|>\nSunny<|end|>\n"));
+    }
+
+    #[test]
+    fn raw_requests() {
+        let request = Gemma.generation_request("gemma3", &tool_conversation(), &[]);
+
+        assert_eq!(request.model, "gemma3");
+        assert_eq!(request.raw, Some(true));
+        assert_eq!(request.options.unwrap()["stop"], json!(["<end_of_turn>"]));
+        assert!(request.prompt.ends_with("<start_of_turn>model\n"));
+    }
+}
@@ -0,0 +1,594 @@
+//! Mock Ollama server for testing
+//!
+//! An in-process HTTP server implementing the Ollama REST API with canned,
+//! deterministic behaviour, so that code built on [`crate::Ollama`] can be
+//! tested without a real server or downloaded models.
+//!
+//! - Models are kept in memory: pulling, creating, copying and deleting
+//!   models affects what `/api/tags` and `/api/show` return.
+//! - Responses of any endpoint can be scripted, including streamed NDJSON
+//!   chunks, error statuses and latency.
+//! - Every request is recorded for assertions.
+//!
+//! Requires the `testing` feature.
+//!
+//! ## Examples
+//!
+//! ```rust
+//! use ollama_rest::{models::generate::GenerationRequest, testing::{MockModel, MockResponse, MockServer}};
+//! use reqwest::StatusCode;
+//! use serde_json::json;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let server = MockServer::start().await.unwrap();
+//! server.add_model(MockModel::new("llama3.2:1b"));
+//! server.set_completion("The sky is blue.");
+//!
+//! let ollama = server.client();
+//! let request = serde_json::from_value::<GenerationRequest>(json!({
+//!     "model": "llama3.2:1b",
+//!     "prompt": "Why is the sky blue?",
+//!     "stream": false,
+//! })).unwrap();
+//!
+//! let res = ollama.generate(&request, None::<fn(&_)>).await.unwrap();
+//! assert_eq!(res.response, "The sky is blue.");
+//!
+//! // Inject an error for the next call only
+//! server.mock_once("/api/generate", MockResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "out of memory"));
+//! assert!(ollama.generate(&request, None::<fn(&_)>).await.is_err());
+//!
+//! assert_eq!(server.requests_to("/api/generate").len(), 2);
+//! # }
+//! ```
+
+use std::{collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque}, convert::Infallible, net::SocketAddr, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use axum::{body::Body, extract::State, http::{HeaderMap, Method, StatusCode, Uri}, response::{IntoResponse, Response}, Router};
+use bytes::Bytes;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use tokio::{net::TcpListener, sync::oneshot};
+use url::Url;
+
+use crate::{errors::Error, models::{digest::Digest, model::Capability}, Ollama};
+
+const CREATED_AT: &str = "2024-01-01T00:00:00Z";
+const EMBEDDING_DIMENSIONS: usize = 8;
+
+/// Model known to the mock server
+#[derive(Debug, Clone)]
+pub struct MockModel {
+    pub name: String,
+    pub digest: Digest,
+    pub size: u64,
+    pub family: String,
+    pub parameter_size: String,
+    pub quantization_level: String,
+    pub capabilities: Vec<Capability>,
+    pub context_length: u64,
+}
+
+impl MockModel {
+    /// A small completion model with a digest derived from its name
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+
+        Self {
+            digest: Digest::from_bytes(name.as_bytes()),
+            name,
+            size: 1_321_098_329,
+            family: "llama".to_string(),
+            parameter_size: "1.2B".to_string(),
+            quantization_level: "Q8_0".to_string(),
+            capabilities: vec![Capability::Completion],
+            context_length: 131_072,
+        }
+    }
+
+    pub fn with_capabilities(mut self, capabilities: impl IntoIterator<Item = Capability>) -> Self {
+        self.capabilities = capabilities.into_iter().collect();
+        self
+    }
+
+    fn details(&self) -> Value {
+        json!({
+            "parent_model": "",
+            "format": "gguf",
+            "family": self.family,
+            "families": [self.family],
+            "parameter_size": self.parameter_size,
+            "quantization_level": self.quantization_level,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum MockBody {
+    Json(Value),
+    Stream {
+        chunks: Vec<Value>,
+        delay: Duration,
+    },
+    Empty,
+}
+
+/// Scripted response
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: StatusCode,
+    body: MockBody,
+    latency: Duration,
+}
+
+impl MockResponse {
+    /// JSON response with status 200
+    pub fn json(body: Value) -> Self {
+        Self { status: StatusCode::OK, body: MockBody::Json(body), latency: Duration::ZERO }
+    }
+
+    /// Streamed NDJSON response, one chunk per line
+    pub fn stream(chunks: impl IntoIterator<Item = Value>) -> Self {
+        Self {
+            status: StatusCode::OK,
+            body: MockBody::Stream { chunks: chunks.into_iter().collect(), delay: Duration::ZERO },
+            latency: Duration::ZERO,
+        }
+    }
+
+    /// Error response with Ollama's `{"error": "..."}` body
+    pub fn error(status: StatusCode, message: impl Into<String>) -> Self {
+        Self { status, body: MockBody::Json(json!({ "error": message.into() })), latency: Duration::ZERO }
+    }
+
+    /// Response with an empty body
+    pub fn status(status: StatusCode) -> Self {
+        Self { status, body: MockBody::Empty, latency: Duration::ZERO }
+    }
+
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Delay before the response starts
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Delay before each streamed chunk
+    pub fn with_chunk_delay(mut self, chunk_delay: Duration) -> Self {
+        if let MockBody::Stream { ref mut delay, .. } = self.body {
+            *delay = chunk_delay;
+        }
+        self
+    }
+
+    fn into_response(self) -> Response {
+        match self.body {
+            MockBody::Json(body) => (self.status, axum::Json(body)).into_response(),
+            MockBody::Empty => self.status.into_response(),
+            MockBody::Stream { chunks, delay } => {
+                let stream = futures::stream::iter(chunks).then(move |chunk| async move {
+                    if !delay.is_zero() {
+                        tokio::time::sleep(delay).await;
+                    }
+
+                    let mut line = serde_json::to_vec(&chunk).unwrap_or_default();
+                    line.push(b'\n');
+
+                    Ok::<_, Infallible>(Bytes::from(line))
+                });
+
+                Response::builder()
+                    .status(self.status)
+                    .header("content-type", "application/x-ndjson")
+                    .body(Body::from_stream(stream))
+                    .unwrap()
+            }
+        }
+    }
+}
+
+/// Request received by the mock server
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub path: String,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    pub received_at: Instant,
+}
+
+impl RecordedRequest {
+    /// Body parsed as JSON, if it is JSON
+    pub fn json(&self) -> Option<Value> {
+        serde_json::from_slice(&self.body).ok()
+    }
+}
+
+#[derive(Default)]
+struct Script {
+    once: VecDeque<MockResponse>,
+    always: Option<MockResponse>,
+}
+
+struct Shared {
+    models: Mutex<BTreeMap<String, MockModel>>,
+    running: Mutex<BTreeSet<String>>,
+    blobs: Mutex<HashSet<Digest>>,
+    scripts: Mutex<HashMap<String, Script>>,
+    requests: Mutex<Vec<RecordedRequest>>,
+    latency: Mutex<Duration>,
+    completion: Mutex<String>,
+    version: Mutex<String>,
+}
+
+/// In-process mock Ollama server
+///
+/// The server shuts down when dropped.
+pub struct MockServer {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockServer {
+    /// Start a server on a random local port
+    pub async fn start() -> Result<Self, Error> {
+        let shared = Arc::new(Shared {
+            models: Mutex::default(),
+            running: Mutex::default(),
+            blobs: Mutex::default(),
+            scripts: Mutex::default(),
+            requests: Mutex::default(),
+            latency: Mutex::default(),
+            completion: Mutex::new("Hello from the mock server!".to_string()),
+            version: Mutex::new("0.9.0".to_string()),
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+
+        let app = Router::new()
+            .fallback(handle)
+            .with_state(shared.clone());
+
+        let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app)
+                .with_graceful_shutdown(async {
+                    let _ = shutdown_rx.await;
+                })
+                .await;
+        });
+
+        Ok(Self { addr, shared, shutdown: Some(shutdown) })
+    }
+
+    /// Base URL of the server
+    pub fn url(&self) -> Url {
+        Url::parse(&format!("http://{}", self.addr)).unwrap()
+    }
+
+    /// Client connected to the server
+    pub fn client(&self) -> Ollama {
+        Ollama::new(self.url()).unwrap()
+    }
+
+    /// Make a model available
+    pub fn add_model(&self, model: MockModel) {
+        self.shared.models.lock().unwrap().insert(model.name.clone(), model);
+    }
+
+    /// Names of available models
+    pub fn model_names(&self) -> Vec<String> {
+        self.shared.models.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Set the text generated by `/api/generate` and `/api/chat`
+    ///
+    /// Streamed responses send it word by word.
+    pub fn set_completion(&self, text: impl Into<String>) {
+        *self.shared.completion.lock().unwrap() = text.into();
+    }
+
+    /// Set the version reported by `/api/version`
+    pub fn set_version(&self, version: impl Into<String>) {
+        *self.shared.version.lock().unwrap() = version.into();
+    }
+
+    /// Delay every response
+    pub fn set_latency(&self, latency: Duration) {
+        *self.shared.latency.lock().unwrap() = latency;
+    }
+
+    /// Always answer requests to `path` with `response`
+    pub fn mock(&self, path: &str, response: MockResponse) {
+        self.shared.scripts.lock().unwrap().entry(path.to_string()).or_default().always = Some(response);
+    }
+
+    /// Answer the next request to `path` with `response`
+    ///
+    /// Queued one-shot responses take precedence over [`MockServer::mock()`].
+    pub fn mock_once(&self, path: &str, response: MockResponse) {
+        self.shared.scripts.lock().unwrap().entry(path.to_string()).or_default().once.push_back(response);
+    }
+
+    /// Remove all scripted responses
+    pub fn reset_mocks(&self) {
+        self.shared.scripts.lock().unwrap().clear();
+    }
+
+    /// All recorded requests, in order
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.shared.requests.lock().unwrap().clone()
+    }
+
+    /// Recorded requests to `path`, in order
+    pub fn requests_to(&self, path: &str) -> Vec<RecordedRequest> {
+        self.shared.requests.lock().unwrap()
+            .iter()
+            .filter(|req| req.path == path)
+            .cloned()
+            .collect()
+    }
+
+    /// Forget recorded requests
+    pub fn clear_requests(&self) {
+        self.shared.requests.lock().unwrap().clear();
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+async fn handle(State(shared): State<Arc<Shared>>, method: Method, uri: Uri, headers: HeaderMap, body: Bytes) -> Response {
+    let path = uri.path().to_string();
+
+    shared.requests.lock().unwrap().push(RecordedRequest {
+        method: method.clone(),
+        path: path.clone(),
+        headers,
+        body: body.clone(),
+        received_at: Instant::now(),
+    });
+
+    let scripted = shared.scripts.lock().unwrap()
+        .get_mut(&path)
+        .and_then(|script| script.once.pop_front().or_else(|| script.always.clone()));
+
+    let response = match scripted {
+        Some(response) => response,
+        None => default_response(&shared, &method, &path, &body),
+    };
+
+    let latency = *shared.latency.lock().unwrap() + response.latency;
+    if !latency.is_zero() {
+        tokio::time::sleep(latency).await;
+    }
+
+    response.into_response()
+}
+
+fn not_found(model: &str) -> MockResponse {
+    MockResponse::error(StatusCode::NOT_FOUND, format!("model '{model}' not found"))
+}
+
+/// Deterministic unit vector derived from the input text
+fn embedding(input: &str) -> Vec<f64> {
+    let digest = Digest::from_bytes(input.as_bytes());
+    let values: Vec<f64> = digest.as_bytes()[..EMBEDDING_DIMENSIONS]
+        .iter()
+        .map(|&b| b as f64 / 127.5 - 1.0)
+        .collect();
+
+    let norm = values.iter().map(|v| v * v).sum::<f64>().sqrt().max(f64::EPSILON);
+    values.into_iter().map(|v| v / norm).collect()
+}
+
+fn default_response(shared: &Shared, method: &Method, path: &str, body: &Bytes) -> MockResponse {
+    let req: Value = serde_json::from_slice(body).unwrap_or(Value::Null);
+    let stream = req["stream"].as_bool().unwrap_or(true);
+    let model_name = req["model"].as_str().or_else(|| req["name"].as_str()).unwrap_or_default().to_string();
+    let model = shared.models.lock().unwrap().get(&model_name).cloned();
+
+    if let Some(digest) = path.strip_prefix("/api/blobs/") {
+        let Ok(digest) = digest.parse::<Digest>() else {
+            return MockResponse::error(StatusCode::BAD_REQUEST, "invalid digest format");
+        };
+
+        return match *method {
+            Method::HEAD if shared.blobs.lock().unwrap().contains(&digest) => MockResponse::status(StatusCode::OK),
+            Method::HEAD => MockResponse::status(StatusCode::NOT_FOUND),
+            Method::POST if Digest::from_bytes(body) == digest => {
+                shared.blobs.lock().unwrap().insert(digest);
+                MockResponse::status(StatusCode::CREATED)
+            }
+            Method::POST => MockResponse::error(StatusCode::BAD_REQUEST, "digest mismatch"),
+            _ => MockResponse::status(StatusCode::METHOD_NOT_ALLOWED),
+        };
+    }
+
+    match path {
+        "/api/version" => MockResponse::json(json!({ "version": *shared.version.lock().unwrap() })),
+
+        "/api/generate" | "/api/chat" => {
+            let Some(model) = model else {
+                return not_found(&model_name);
+            };
+
+            shared.running.lock().unwrap().insert(model.name.clone());
+
+            let completion = shared.completion.lock().unwrap().clone();
+            let chat = path == "/api/chat";
+
+            let chunk = |text: &str, done: bool| {
+                let mut chunk = json!({
+                    "model": model.name,
+                    "created_at": CREATED_AT,
+                    "done": done,
+                });
+
+                if chat {
+                    chunk["message"] = json!({ "role": "assistant", "content": text });
+                } else {
+                    chunk["response"] = json!(text);
+                }
+
+                if done {
+                    let eval_count = completion.split_inclusive(' ').count();
+                    chunk["done_reason"] = json!("stop");
+                    chunk["total_duration"] = json!(5_000_000_u64 + eval_count as u64 * 1_000_000);
+                    chunk["load_duration"] = json!(1_000_000_u64);
+                    chunk["prompt_eval_count"] = json!(req.to_string().split_whitespace().count());
+                    chunk["prompt_eval_duration"] = json!(2_000_000_u64);
+                    chunk["eval_count"] = json!(eval_count);
+                    chunk["eval_duration"] = json!(eval_count as u64 * 1_000_000);
+                    if !chat {
+                        chunk["context"] = json!([1, 2, 3]);
+                    }
+                }
+
+                chunk
+            };
+
+            if stream {
+                let mut chunks: Vec<Value> = completion.split_inclusive(' ').map(|word| chunk(word, false)).collect();
+                chunks.push(chunk("", true));
+                MockResponse::stream(chunks)
+            } else {
+                MockResponse::json(chunk(&completion, true))
+            }
+        }
+
+        "/api/embeddings" => match model {
+            Some(_) => MockResponse::json(json!({ "embedding": embedding(req["prompt"].as_str().unwrap_or_default()) })),
+            None => not_found(&model_name),
+        },
+
+        "/api/embed" => match model {
+            Some(model) => {
+                let inputs: Vec<String> = match &req["input"] {
+                    Value::String(input) => vec![input.clone()],
+                    Value::Array(inputs) => inputs.iter().filter_map(|input| input.as_str().map(str::to_string)).collect(),
+                    _ => Vec::new(),
+                };
+
+                MockResponse::json(json!({
+                    "model": model.name,
+                    "embeddings": inputs.iter().map(|input| embedding(input)).collect::<Vec<_>>(),
+                    "total_duration": 1_000_000_u64,
+                    "load_duration": 100_000_u64,
+                    "prompt_eval_count": inputs.iter().map(|input| input.split_whitespace().count()).sum::<usize>(),
+                }))
+            }
+            None => not_found(&model_name),
+        },
+
+        "/api/tags" => {
+            let models: Vec<Value> = shared.models.lock().unwrap().values().map(|model| json!({
+                "name": model.name,
+                "model": model.name,
+                "modified_at": CREATED_AT,
+                "size": model.size,
+                "digest": model.digest.to_hex(),
+                "details": model.details(),
+            })).collect();
+
+            MockResponse::json(json!({ "models": models }))
+        }
+
+        "/api/ps" => {
+            let models = shared.models.lock().unwrap();
+            let running: Vec<Value> = shared.running.lock().unwrap().iter()
+                .filter_map(|name| models.get(name))
+                .map(|model| json!({
+                    "name": model.name,
+                    "model": model.name,
+                    "size": model.size,
+                    "digest": model.digest.to_hex(),
+                    "details": model.details(),
+                    "expires_at": "2099-01-01T00:00:00Z",
+                    "size_vram": model.size,
+                }))
+                .collect();
+
+            MockResponse::json(json!({ "models": running }))
+        }
+
+        "/api/show" => match model {
+            Some(model) => MockResponse::json(json!({
+                "modelfile": format!("FROM {}\n", model.name),
+                "parameters": "",
+                "template": "{{ .Prompt }}",
+                "details": model.details(),
+                "model_info": {
+                    "general.architecture": model.family,
+                    format!("{}.context_length", model.family): model.context_length,
+                },
+                "capabilities": model.capabilities,
+                "modified_at": CREATED_AT,
+            })),
+            None => not_found(&model_name),
+        },
+
+        "/api/pull" | "/api/push" | "/api/create" => {
+            if path == "/api/push" && model.is_none() {
+                return not_found(&model_name);
+            }
+
+            if path != "/api/push" {
+                shared.models.lock().unwrap()
+                    .entry(model_name.clone())
+                    .or_insert_with(|| MockModel::new(model_name.clone()));
+            }
+
+            let digest = Digest::from_bytes(model_name.as_bytes());
+
+            if stream {
+                MockResponse::stream([
+                    json!({ "status": "pulling manifest" }),
+                    json!({ "status": format!("pulling {}", digest.to_hex()), "digest": digest, "total": 1024, "completed": 512 }),
+                    json!({ "status": format!("pulling {}", digest.to_hex()), "digest": digest, "total": 1024, "completed": 1024 }),
+                    json!({ "status": "writing manifest" }),
+                    json!({ "status": "success" }),
+                ])
+            } else {
+                MockResponse::json(json!({ "status": "success" }))
+            }
+        }
+
+        "/api/copy" => {
+            let mut models = shared.models.lock().unwrap();
+            let source = req["source"].as_str().unwrap_or_default();
+
+            match models.get(source).cloned() {
+                Some(model) => {
+                    let destination = req["destination"].as_str().unwrap_or_default().to_string();
+                    models.insert(destination.clone(), MockModel { name: destination, ..model });
+                    MockResponse::status(StatusCode::OK)
+                }
+                None => not_found(source),
+            }
+        }
+
+        "/api/delete" => match shared.models.lock().unwrap().remove(&model_name) {
+            Some(_) => {
+                shared.running.lock().unwrap().remove(&model_name);
+                MockResponse::status(StatusCode::OK)
+            }
+            None => not_found(&model_name),
+        },
+
+        _ => MockResponse::error(StatusCode::NOT_FOUND, "404 page not found"),
+    }
+}
@@ -0,0 +1,158 @@
+//! `tracing` instrumentation helpers
+//!
+//! Every [`crate::Ollama`] method opens a span carrying the endpoint, the model,
+//! the HTTP status and, for generations, the chunk count, time to first token
+//! and token counts of the final response. Prompts are recorded only when
+//! enabled with [`crate::Ollama::with_prompt_tracing`].
+
+use std::time::Instant;
+
+use reqwest::StatusCode;
+use tracing::Span;
+
+use crate::{
+    metrics::MeteredResponse,
+    models::{
+        chat::{ChatRequest, ChatResponse, Role},
+        create::{CreationProgress, CreationRequest},
+        embeddings::EmbedResponse,
+        generate::{GenerationRequest, GenerationResponse},
+        model::{ModelPullStatus, ModelPushStatus, ModelSyncRequest},
+        Status,
+    },
+};
+
+/// Request fields worth recording
+pub(crate) trait TracedRequest {
+    fn model(&self) -> Option<&str>;
+
+    /// Prompt content, only recorded on request
+    fn prompt(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl TracedRequest for GenerationRequest {
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn prompt(&self) -> Option<&str> {
+        Some(&self.prompt)
+    }
+}
+
+impl TracedRequest for ChatRequest {
+    fn model(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn prompt(&self) -> Option<&str> {
+        self.messages.iter()
+            .rev()
+            .find(|message| message.role == Role::User)
+            .map(|message| message.content.as_str())
+    }
+}
+
+impl TracedRequest for CreationRequest {
+    fn model(&self) -> Option<&str> {
+        self.model.as_deref().or(self.name.as_deref())
+    }
+}
+
+impl TracedRequest for ModelSyncRequest {
+    fn model(&self) -> Option<&str> {
+        Some(&self.name)
+    }
+}
+
+/// Response fields worth recording
+pub(crate) trait TracedResponse: MeteredResponse {
+    fn record(&self, _span: &Span) {}
+}
+
+impl TracedResponse for GenerationResponse {
+    fn record(&self, span: &Span) {
+        if let Some(count) = self.stats.prompt_eval_count {
+            span.record("prompt_eval_count", count);
+        }
+        if let Some(count) = self.stats.eval_count {
+            span.record("eval_count", count);
+        }
+    }
+}
+
+impl TracedResponse for ChatResponse {
+    fn record(&self, span: &Span) {
+        if let Some(count) = self.stats.prompt_eval_count {
+            span.record("prompt_eval_count", count);
+        }
+        if let Some(count) = self.stats.eval_count {
+            span.record("eval_count", count);
+        }
+    }
+}
+
+impl TracedResponse for EmbedResponse {
+    fn record(&self, span: &Span) {
+        if let Some(count) = self.stats.prompt_eval_count {
+            span.record("prompt_eval_count", count);
+        }
+    }
+}
+
+impl TracedResponse for Status {}
+impl TracedResponse for CreationProgress {}
+impl TracedResponse for ModelPullStatus {}
+impl TracedResponse for ModelPushStatus {}
+
+/// Record the prompt of `request` on the current span, if enabled
+pub(crate) fn record_request<R: TracedRequest>(request: &R, trace_prompts: bool) {
+    if let (true, Some(prompt)) = (trace_prompts, request.prompt()) {
+        Span::current().record("prompt", prompt);
+    }
+}
+
+pub(crate) fn record_status(status: StatusCode) {
+    Span::current().record("status", status.as_u16());
+}
+
+/// Record a whole response on the current span
+pub(crate) fn record_response<R: TracedResponse>(response: &R) {
+    Observer::new().observe(response);
+}
+
+/// Follows the chunks of a streamed response
+///
+/// Keeps the span open until the stream is dropped.
+pub(crate) struct Observer {
+    span: Span,
+    started: Instant,
+    chunks: u64,
+    first_token: bool,
+}
+
+impl Observer {
+    /// Observer of the current span, timing from now
+    pub fn new() -> Self {
+        Self {
+            span: Span::current(),
+            started: Instant::now(),
+            chunks: 0,
+            first_token: false,
+        }
+    }
+
+    pub fn observe<R: TracedResponse>(&mut self, response: &R) {
+        self.chunks += 1;
+        self.span.record("chunks", self.chunks);
+
+        if !self.first_token && response.has_token() {
+            self.first_token = true;
+            self.span.record("ttft_ms", self.started.elapsed().as_millis() as u64);
+        }
+
+        TracedResponse::record(response, &self.span);
+    }
+}
@@ -0,0 +1,289 @@
+//! HTTP transport underneath [`crate::Ollama`]
+//!
+//! [`ReqwestTransport`] is used by default. Other backends (another HTTP
+//! client, a Unix socket, an in-memory fake, ...) implement [`Transport`] and
+//! are plugged in with [`crate::Ollama::with_transport`].
+
+use bytes::{Bytes, BytesMut};
+use futures::{future::BoxFuture, stream::BoxStream, Stream, StreamExt, TryStreamExt};
+use reqwest::{header::{HeaderMap, HeaderValue, CONTENT_TYPE}, Body, Client, Method, StatusCode, Url};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::errors::Error;
+
+/// Boxed error of streamed request bodies
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Request body
+pub enum RequestBody {
+    Empty,
+    /// Serialized JSON
+    Json(Bytes),
+    /// Streamed bytes (e.g. blob uploads)
+    Stream(BoxStream<'static, Result<Bytes, BoxError>>),
+}
+
+/// Request handed to a [`Transport`]
+pub struct TransportRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub body: RequestBody,
+}
+
+impl TransportRequest {
+    pub fn new(method: Method, url: Url) -> Self {
+        Self {
+            method,
+            url,
+            headers: HeaderMap::new(),
+            body: RequestBody::Empty,
+        }
+    }
+
+    /// Set a JSON body
+    pub fn json<T: Serialize + ?Sized>(mut self, body: &T) -> Result<Self, Error> {
+        self.headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        self.body = RequestBody::Json(serde_json::to_vec(body)?.into());
+        Ok(self)
+    }
+
+    /// Set a streamed body
+    pub fn stream<S>(mut self, stream: S) -> Self
+    where
+        S: Stream<Item = Result<Bytes, BoxError>> + Send + 'static,
+    {
+        self.body = RequestBody::Stream(stream.boxed());
+        self
+    }
+
+    /// JSON body, if any
+    pub fn json_body(&self) -> Option<&[u8]> {
+        match &self.body {
+            RequestBody::Json(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+}
+
+/// Response returned by a [`Transport`]
+pub struct TransportResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: BoxStream<'static, Result<Bytes, Error>>,
+}
+
+impl TransportResponse {
+    /// Collect the whole body
+    pub async fn bytes(self) -> Result<Bytes, Error> {
+        let mut buf = BytesMut::new();
+        let mut body = self.body;
+
+        while let Some(chunk) = body.try_next().await? {
+            buf.extend_from_slice(&chunk);
+        }
+
+        Ok(buf.freeze())
+    }
+
+    /// Collect the body and decode it as JSON
+    pub async fn json<T: DeserializeOwned>(self) -> Result<T, Error> {
+        Ok(serde_json::from_slice(&self.bytes().await?)?)
+    }
+
+    /// Decode a newline-delimited JSON body item by item
+    pub fn json_lines<T: DeserializeOwned + Send + 'static>(self) -> BoxStream<'static, Result<T, Error>> {
+        lines(self.body)
+            .map(|line| serde_json::from_slice::<T>(&line?).map_err(Error::from))
+            .boxed()
+    }
+}
+
+/// Split a byte stream into non-empty lines, regardless of chunk boundaries
+pub(crate) fn lines<S>(body: S) -> impl Stream<Item = Result<Bytes, Error>>
+where
+    S: Stream<Item = Result<Bytes, Error>> + Unpin,
+{
+    futures::stream::try_unfold((body, BytesMut::new(), false), |(mut body, mut buf, mut eof)| async move {
+        loop {
+            if let Some(pos) = buf.iter().position(|&b| b == b'\n') {
+                let line = buf.split_to(pos + 1).freeze().slice(..pos);
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+
+                return Ok(Some((line, (body, buf, eof))));
+            }
+
+            if eof {
+                if buf.iter().all(u8::is_ascii_whitespace) {
+                    return Ok(None);
+                }
+
+                let rest = buf.split().freeze();
+                return Ok(Some((rest, (body, buf, eof))));
+            }
+
+            match body.try_next().await? {
+                Some(chunk) => buf.extend_from_slice(&chunk),
+                None => eof = true,
+            }
+        }
+    })
+}
+
+/// Sends requests to an Ollama server
+///
+/// ## Examples
+///
+/// ```rust
+/// use futures::{future::BoxFuture, FutureExt, StreamExt};
+/// use ollama_rest::{errors::Error, transport::{Transport, TransportRequest, TransportResponse}, Ollama};
+/// use reqwest::{header::HeaderMap, StatusCode};
+///
+/// /// Answers every request with the same version
+/// struct FixedVersion;
+///
+/// impl Transport for FixedVersion {
+///     fn send(&self, _request: TransportRequest) -> BoxFuture<'_, Result<TransportResponse, Error>> {
+///         async {
+///             Ok(TransportResponse {
+///                 status: StatusCode::OK,
+///                 headers: HeaderMap::new(),
+///                 body: futures::stream::once(async { Ok(r#"{"version":"0.9.0"}"#.into()) }).boxed(),
+///             })
+///         }.boxed()
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let ollama = Ollama::with_transport("http://localhost:11434".parse().unwrap(), FixedVersion);
+///
+/// assert_eq!(ollama.version().await.unwrap().version.to_string(), "0.9.0");
+/// # }
+/// ```
+pub trait Transport: Send + Sync {
+    /// Send a request and return the response head with a streamed body
+    fn send(&self, request: TransportRequest) -> BoxFuture<'_, Result<TransportResponse, Error>>;
+}
+
+impl<T: Transport + ?Sized> Transport for std::sync::Arc<T> {
+    fn send(&self, request: TransportRequest) -> BoxFuture<'_, Result<TransportResponse, Error>> {
+        (**self).send(request)
+    }
+}
+
+/// Default transport using reqwest
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn send(&self, request: TransportRequest) -> BoxFuture<'_, Result<TransportResponse, Error>> {
+        Box::pin(async move {
+            let builder = self.client.request(request.method, request.url).headers(request.headers);
+
+            let builder = match request.body {
+                RequestBody::Empty => builder,
+                RequestBody::Json(bytes) => builder.body(bytes),
+                RequestBody::Stream(stream) => builder.body(Body::wrap_stream(stream)),
+            };
+
+            let res = builder.send().await?;
+
+            Ok(TransportResponse {
+                status: res.status(),
+                headers: res.headers().clone(),
+                body: res.bytes_stream().map_err(Error::from).boxed(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures::FutureExt;
+
+    use crate::{models::generate::GenerationRequest, Ollama};
+
+    use super::*;
+
+    /// Replies with canned chunks and keeps what it was sent
+    struct InMemory {
+        chunks: Vec<&'static [u8]>,
+        sent: Mutex<Vec<(Method, String, Option<serde_json::Value>)>>,
+    }
+
+    impl Transport for InMemory {
+        fn send(&self, request: TransportRequest) -> BoxFuture<'_, Result<TransportResponse, Error>> {
+            let body = request.json_body().map(|body| serde_json::from_slice(body).unwrap());
+            self.sent.lock().unwrap().push((request.method, request.url.path().to_string(), body));
+
+            let chunks: Vec<Result<Bytes, Error>> = self.chunks.iter().map(|chunk| Ok(Bytes::from_static(chunk))).collect();
+
+            async move {
+                Ok(TransportResponse {
+                    status: StatusCode::OK,
+                    headers: HeaderMap::new(),
+                    body: futures::stream::iter(chunks).boxed(),
+                })
+            }.boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_transport() {
+        let transport = std::sync::Arc::new(InMemory {
+            chunks: vec![
+                b"{\"model\":\"m\",\"created_at\":\"2024-01-01T00:00:00Z\",\"response\":\"Hel",
+                b"lo\",\"done\":false}\n{\"model\":\"m\",\"created_at\":\"2024-01-01T00:00:00Z\",\"response\":\"!\",\"done\":true}\n",
+            ],
+            sent: Mutex::new(Vec::new()),
+        });
+
+        let ollama = Ollama::with_transport("http://ollama.invalid/".parse().unwrap(), transport.clone());
+        let request: GenerationRequest = serde_json::from_value(serde_json::json!({ "model": "m", "prompt": "Hi" })).unwrap();
+
+        let responses: Vec<_> = ollama.generate_streamed(&request).await.unwrap().try_collect().await.unwrap();
+
+        assert_eq!(responses.iter().map(|res| res.response.as_str()).collect::<String>(), "Hello!");
+
+        let sent = transport.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, Method::POST);
+        assert_eq!(sent[0].1, "/api/generate");
+        assert_eq!(sent[0].2.as_ref().unwrap()["prompt"], "Hi");
+    }
+
+    #[tokio::test]
+    async fn split_lines_across_chunks() {
+        let chunks: Vec<Result<Bytes, Error>> = vec![
+            Ok(Bytes::from_static(b"{\"a\":1}\n{\"a\"")),
+            Ok(Bytes::from_static(b":2}\n\n{\"a\":3}\n{\"a\":")),
+            Ok(Bytes::from_static(b"4}")),
+        ];
+
+        let lines: Vec<Bytes> = lines(futures::stream::iter(chunks)).try_collect().await.unwrap();
+
+        assert_eq!(lines, vec![
+            Bytes::from_static(b"{\"a\":1}"),
+            Bytes::from_static(b"{\"a\":2}"),
+            Bytes::from_static(b"{\"a\":3}"),
+            Bytes::from_static(b"{\"a\":4}"),
+        ]);
+    }
+}
@@ -0,0 +1,610 @@
+//! Local vector store
+//!
+//! [`VectorStore`] keeps documents along with their embeddings and metadata,
+//! answers top-k cosine similarity queries, optionally filtered by metadata,
+//! and saves to and loads from a JSON file. Queries scan every document by
+//! default; [`IndexKind::Hnsw`] trades exactness for speed on large stores.
+//!
+//! Requires the `vector` feature.
+//!
+//! ## Examples
+//!
+//! ```rust,no_run
+//! use ollama_rest::{
+//!     models::embeddings::EmbedRequest,
+//!     vector::{Document, Filter, HnswConfig, IndexKind, VectorStore},
+//!     Ollama,
+//! };
+//! use serde_json::json;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), ollama_rest::errors::Error> {
+//! let ollama = Ollama::default();
+//! let texts = ["The sky is blue", "Grass is green"];
+//!
+//! let request = serde_json::from_value::<EmbedRequest>(json!({
+//!     "model": "nomic-embed-text",
+//!     "input": texts,
+//! })).unwrap();
+//! let res = ollama.embed(&request).await?;
+//!
+//! let mut store = VectorStore::new().with_index(IndexKind::Hnsw(HnswConfig::default()));
+//! for (i, (text, vector)) in texts.iter().zip(res.embeddings).enumerate() {
+//!     store.insert(Document::new(format!("doc-{i}"), vector).with_field("text", *text))?;
+//! }
+//! store.save("store.json")?;
+//!
+//! let request = serde_json::from_value::<EmbedRequest>(json!({
+//!     "model": "nomic-embed-text",
+//!     "input": "What color is the sky?",
+//! })).unwrap();
+//! let query = &ollama.embed(&request).await?.embeddings[0];
+//!
+//! for result in store.search_filtered(query, 1, &Filter::exists("text"))? {
+//!     println!("{} ({:.3}): {}", result.document.id, result.score, result.document.metadata["text"]);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{collections::HashMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{errors::Error, models::embedding::{Embedding, Precision}};
+
+use hnsw::Hnsw;
+
+mod hnsw;
+
+/// Metadata of a document
+pub type Metadata = Map<String, Value>;
+
+/// Stored document
+///
+/// Vectors keep the precision they are inserted with; store them as
+/// [`Precision::F32`] or [`Precision::F16`] to save memory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    pub id: String,
+    pub vector: Embedding,
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+impl Document {
+    pub fn new<S: Into<String>, V: Into<Embedding>>(id: S, vector: V) -> Self {
+        Self {
+            id: id.into(),
+            vector: vector.into(),
+            metadata: Metadata::new(),
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Set one metadata field
+    pub fn with_field<K: Into<String>, V: Into<Value>>(mut self, key: K, value: V) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// Document matching a query
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult<'a> {
+    pub document: &'a Document,
+    /// Cosine similarity to the query, from -1 to 1
+    pub score: f64,
+}
+
+/// Parameters of an [`IndexKind::Hnsw`] index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HnswConfig {
+    /// Links per node and layer (twice as many on the bottom layer)
+    pub m: usize,
+    /// Candidates considered when inserting
+    pub ef_construction: usize,
+    /// Candidates considered when searching, at least `k`
+    pub ef_search: usize,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+        }
+    }
+}
+
+/// How a [`VectorStore`] finds the nearest documents
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IndexKind {
+    /// Exact search, comparing the query with every document
+    #[default]
+    BruteForce,
+    /// Approximate search in a hierarchical navigable small world graph
+    Hnsw(HnswConfig),
+}
+
+/// Condition on the metadata of a document
+///
+/// Fields are looked up at the top level of the metadata.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    /// Field equals the value
+    Eq(String, Value),
+    /// Field equals one of the values
+    In(String, Vec<Value>),
+    /// Field is set, to anything but null
+    Exists(String),
+    /// Field is a number within the bounds, inclusive
+    Range {
+        field: String,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    pub fn eq<K: Into<String>, V: Into<Value>>(field: K, value: V) -> Self {
+        Self::Eq(field.into(), value.into())
+    }
+
+    pub fn one_of<K, I, V>(field: K, values: I) -> Self
+    where
+        K: Into<String>,
+        I: IntoIterator<Item = V>,
+        V: Into<Value>,
+    {
+        Self::In(field.into(), values.into_iter().map(Into::into).collect())
+    }
+
+    pub fn exists<K: Into<String>>(field: K) -> Self {
+        Self::Exists(field.into())
+    }
+
+    pub fn range<K: Into<String>>(field: K, min: Option<f64>, max: Option<f64>) -> Self {
+        Self::Range { field: field.into(), min, max }
+    }
+
+    pub fn and(self, other: Filter) -> Self {
+        match self {
+            Self::And(mut filters) => {
+                filters.push(other);
+                Self::And(filters)
+            },
+            filter => Self::And(vec![filter, other]),
+        }
+    }
+
+    pub fn or(self, other: Filter) -> Self {
+        match self {
+            Self::Or(mut filters) => {
+                filters.push(other);
+                Self::Or(filters)
+            },
+            filter => Self::Or(vec![filter, other]),
+        }
+    }
+
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        match self {
+            Self::Eq(field, value) => metadata.get(field) == Some(value),
+            Self::In(field, values) => metadata.get(field).is_some_and(|value| values.contains(value)),
+            Self::Exists(field) => metadata.get(field).is_some_and(|value| !value.is_null()),
+            Self::Range { field, min, max } => metadata.get(field).and_then(Value::as_f64).is_some_and(|value| {
+                min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+            }),
+            Self::And(filters) => filters.iter().all(|filter| filter.matches(metadata)),
+            Self::Or(filters) => filters.iter().any(|filter| filter.matches(metadata)),
+            Self::Not(filter) => !filter.matches(metadata),
+        }
+    }
+}
+
+impl std::ops::Not for Filter {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self::Not(Box::new(self))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    document: Document,
+    norm: f64,
+    /// Removed or replaced, but still linked in the HNSW graph
+    deleted: bool,
+}
+
+fn cosine(a: &Embedding, a_norm: f64, b: &Embedding, b_norm: f64) -> f64 {
+    match a_norm * b_norm {
+        0.0 => 0.0,
+        norms => a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>() / norms,
+    }
+}
+
+/// File format of [`VectorStore::save`]
+#[derive(Serialize, Deserialize)]
+struct Saved {
+    index: IndexKind,
+    documents: Vec<SavedDocument>,
+}
+
+/// Document along with the precision its vector is restored in
+#[derive(Serialize, Deserialize)]
+struct SavedDocument {
+    #[serde(flatten)]
+    document: Document,
+    /// Files without it were saved in double precision
+    #[serde(default)]
+    precision: Precision,
+}
+
+/// Documents and their embeddings, searchable by cosine similarity
+#[derive(Debug, Clone, Default)]
+pub struct VectorStore {
+    index: IndexKind,
+    dimensions: Option<usize>,
+    entries: Vec<Entry>,
+    /// Position of the live entry of every document
+    ids: HashMap<String, usize>,
+    hnsw: Option<Hnsw>,
+}
+
+impl VectorStore {
+    /// Empty store with a brute-force index
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use another index, rebuilding it from the current documents
+    pub fn with_index(mut self, index: IndexKind) -> Self {
+        self.index = index;
+        self.rebuild();
+        self
+    }
+
+    fn rebuild(&mut self) {
+        let entries = std::mem::take(&mut self.entries);
+        self.ids.clear();
+        self.hnsw = match self.index {
+            IndexKind::BruteForce => None,
+            IndexKind::Hnsw(config) => Some(Hnsw::new(config)),
+        };
+
+        for entry in entries.into_iter().filter(|entry| !entry.deleted) {
+            self.push(entry.document, entry.norm);
+        }
+    }
+
+    pub fn index(&self) -> IndexKind {
+        self.index
+    }
+
+    /// Length of the vectors, set by the first document
+    pub fn dimensions(&self) -> Option<usize> {
+        self.dimensions
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Document> {
+        self.ids.get(id).map(|&i| &self.entries[i].document)
+    }
+
+    pub fn documents(&self) -> impl Iterator<Item = &Document> {
+        self.entries.iter().filter(|entry| !entry.deleted).map(|entry| &entry.document)
+    }
+
+    fn check_dimensions(&self, vector: &Embedding) -> Result<(), Error> {
+        match self.dimensions {
+            Some(expected) if expected != vector.len() => Err(Error::DimensionMismatch { expected, actual: vector.len() }),
+            _ => Ok(()),
+        }
+    }
+
+    fn push(&mut self, document: Document, norm: f64) {
+        let i = self.entries.len();
+
+        self.dimensions.get_or_insert(document.vector.len());
+        self.ids.insert(document.id.clone(), i);
+        self.entries.push(Entry { document, norm, deleted: false });
+
+        let Self { entries, hnsw, .. } = self;
+        if let Some(hnsw) = hnsw {
+            hnsw.insert(i, |a, b| {
+                let (a, b) = (&entries[a], &entries[b]);
+                1.0 - cosine(&a.document.vector, a.norm, &b.document.vector, b.norm)
+            });
+        }
+    }
+
+    /// Add a document, replacing any with the same ID
+    ///
+    /// ## Returns
+    /// [`Error::DimensionMismatch`] if its vector does not have the length of the others
+    pub fn insert(&mut self, document: Document) -> Result<(), Error> {
+        self.check_dimensions(&document.vector)?;
+        self.remove(&document.id);
+
+        let norm = document.vector.norm();
+        self.push(document, norm);
+
+        Ok(())
+    }
+
+    /// Add documents, stopping at the first one that cannot be added
+    pub fn extend<I: IntoIterator<Item = Document>>(&mut self, documents: I) -> Result<(), Error> {
+        documents.into_iter().try_for_each(|document| self.insert(document))
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<Document> {
+        let i = self.ids.remove(id)?;
+
+        if self.hnsw.is_none() {
+            let entry = self.entries.swap_remove(i);
+            if let Some(moved) = self.entries.get(i) {
+                self.ids.insert(moved.document.id.clone(), i);
+            }
+
+            return Some(entry.document);
+        }
+
+        let entry = &mut self.entries[i];
+        entry.deleted = true;
+        let document = entry.document.clone();
+
+        // Graph nodes cannot be unlinked, so the graph is rebuilt once most of them are deleted
+        if self.entries.len() - self.ids.len() > self.ids.len() {
+            self.rebuild();
+        }
+
+        Some(document)
+    }
+
+    /// `k` most similar documents to `query`, most similar first
+    pub fn search(&self, query: &Embedding, k: usize) -> Result<Vec<SearchResult<'_>>, Error> {
+        self.search_where(query, k, |_| true)
+    }
+
+    /// `k` most similar documents to `query` among those matching `filter`, most similar first
+    pub fn search_filtered(&self, query: &Embedding, k: usize, filter: &Filter) -> Result<Vec<SearchResult<'_>>, Error> {
+        self.search_where(query, k, |metadata| filter.matches(metadata))
+    }
+
+    fn search_where<F>(&self, query: &Embedding, k: usize, accept: F) -> Result<Vec<SearchResult<'_>>, Error>
+    where
+        F: Fn(&Metadata) -> bool,
+    {
+        self.check_dimensions(query)?;
+
+        let query_norm = query.norm();
+        let score = |entry: &Entry| cosine(query, query_norm, &entry.document.vector, entry.norm);
+        let eligible = |entry: &Entry| !entry.deleted && accept(&entry.document.metadata);
+
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+
+        if let (Some(hnsw), IndexKind::Hnsw(config)) = (&self.hnsw, self.index) {
+            let results = hnsw.search(|i| 1.0 - score(&self.entries[i]), config.ef_search.max(k))
+                .into_iter()
+                .map(|(i, distance)| (&self.entries[i], 1.0 - distance))
+                .filter(|(entry, _)| eligible(entry))
+                .take(k)
+                .map(|(entry, score)| SearchResult { document: &entry.document, score })
+                .collect::<Vec<_>>();
+
+            // Selective filters may leave too few candidates in the graph neighborhood
+            if results.len() == k || results.len() == self.len() {
+                return Ok(results);
+            }
+        }
+
+        let mut results = self.entries.iter()
+            .filter(|entry| eligible(entry))
+            .map(|entry| SearchResult { document: &entry.document, score: score(entry) })
+            .collect::<Vec<_>>();
+
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        results.truncate(k);
+
+        Ok(results)
+    }
+
+    /// Save the documents and index kind to a JSON file
+    ///
+    /// Vectors are loaded back in the precision they are saved in; HNSW
+    /// graphs are rebuilt on load.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let saved = Saved {
+            index: self.index,
+            documents: self.documents()
+                .map(|document| SavedDocument {
+                    precision: document.vector.precision(),
+                    document: document.clone(),
+                })
+                .collect(),
+        };
+        std::fs::write(path, serde_json::to_vec(&saved)?)?;
+
+        Ok(())
+    }
+
+    /// Load a store saved with [`VectorStore::save`]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let saved = serde_json::from_slice::<Saved>(&std::fs::read(path)?)?;
+
+        let mut store = Self::new().with_index(saved.index);
+        store.extend(saved.documents.into_iter().map(|SavedDocument { mut document, precision }| {
+            document.vector = document.vector.to_precision(precision);
+            document
+        }))?;
+
+        Ok(store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Deterministic pseudo-random vectors
+    fn vectors(count: usize, dimensions: usize) -> Vec<Embedding> {
+        let mut state = 42u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % 2000) as f64 / 1000.0 - 1.0
+        };
+
+        (0..count).map(|_| (0..dimensions).map(|_| next()).collect()).collect()
+    }
+
+    fn store(index: IndexKind) -> VectorStore {
+        let mut store = VectorStore::new().with_index(index);
+        store.extend(vectors(500, 16).into_iter().enumerate().map(|(i, vector)| {
+            Document::new(i.to_string(), vector).with_field("parity", i % 2).with_field("rank", i)
+        })).unwrap();
+
+        store
+    }
+
+    #[test]
+    fn exact_search() {
+        let mut store = VectorStore::new();
+        store.insert(Document::new("x", vec![1.0, 0.0])).unwrap();
+        store.insert(Document::new("y", vec![0.0, 1.0])).unwrap();
+        store.insert(Document::new("xy", vec![1.0, 1.0]).with_field("tag", "diagonal")).unwrap();
+
+        let results = store.search(&vec![1.0, 0.1].into(), 2).unwrap();
+        assert_eq!(results.iter().map(|r| r.document.id.as_str()).collect::<Vec<_>>(), ["x", "xy"]);
+        assert!(results[0].score > results[1].score);
+
+        let results = store.search_filtered(&vec![1.0, 0.1].into(), 2, &Filter::eq("tag", "diagonal")).unwrap();
+        assert_eq!(results.len(), 1);
+
+        assert!(matches!(store.insert(Document::new("z", vec![1.0])), Err(Error::DimensionMismatch { expected: 2, actual: 1 })));
+        assert!(store.search(&vec![1.0, 0.0, 0.0].into(), 1).is_err());
+
+        // Replacing and removing
+        store.insert(Document::new("x", vec![0.0, -1.0])).unwrap();
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.search(&vec![1.0, 0.1].into(), 1).unwrap()[0].document.id, "xy");
+
+        assert!(store.remove("xy").is_some());
+        assert!(store.get("xy").is_none());
+        assert_eq!(store.search(&vec![1.0, 0.1].into(), 3).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn compact_removed_documents() {
+        for index in [IndexKind::BruteForce, IndexKind::Hnsw(HnswConfig::default())] {
+            let mut store = VectorStore::new().with_index(index);
+
+            // Re-indexing the same documents over and over does not grow the store
+            for round in 0..4 {
+                for (i, vector) in vectors(100, 16).into_iter().enumerate() {
+                    store.insert(Document::new(i.to_string(), vector).with_field("round", round)).unwrap();
+                }
+            }
+
+            assert_eq!(store.len(), 100);
+            assert!(store.entries.len() <= 200);
+            assert!(store.ids.iter().all(|(id, &i)| store.entries[i].document.id == *id && !store.entries[i].deleted));
+
+            for i in 0..80 {
+                store.remove(&i.to_string());
+            }
+
+            assert_eq!(store.len(), 20);
+            assert!(store.entries.len() <= 40);
+            assert_eq!(store.search(&vectors(1, 16)[0], 40).unwrap().len(), 20);
+        }
+    }
+
+    #[test]
+    fn hnsw_recall() {
+        let exact = store(IndexKind::BruteForce);
+        let approximate = store(IndexKind::Hnsw(HnswConfig::default()));
+
+        let mut found = 0;
+        for query in vectors(520, 16).split_off(500) {
+            let expected = exact.search(&query, 10).unwrap();
+            let results = approximate.search(&query, 10).unwrap();
+
+            found += results.iter().filter(|r| expected.iter().any(|e| e.document.id == r.document.id)).count();
+        }
+
+        assert!(found >= 190, "recall of {found}/200");
+    }
+
+    #[test]
+    fn filters() {
+        let store = store(IndexKind::Hnsw(HnswConfig::default()));
+        let query = &vectors(1, 16)[0];
+
+        let filter = Filter::eq("parity", 1).and(Filter::range("rank", Some(100.0), Some(120.0)));
+        let results = store.search_filtered(query, 20, &filter).unwrap();
+
+        assert_eq!(results.len(), 10);
+        assert!(results.iter().all(|r| filter.matches(&r.document.metadata)));
+
+        let filter = !Filter::one_of("rank", [1, 2, 3]).or(Filter::exists("missing"));
+        assert!(!filter.matches(&store.get("2").unwrap().metadata));
+        assert!(filter.matches(json!({ "rank": 4 }).as_object().unwrap()));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("ollama-rest-vector-{}.json", std::process::id()));
+
+        let mut store = store(IndexKind::Hnsw(HnswConfig::default()));
+        store.remove("0");
+        for (id, precision) in [("1", Precision::F16), ("2", Precision::F32)] {
+            let mut document = store.get(id).unwrap().clone();
+            document.vector = document.vector.to_precision(precision);
+            store.insert(document).unwrap();
+        }
+        store.save(&path).unwrap();
+
+        let loaded = VectorStore::load(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(loaded.len(), 499);
+        assert_eq!(loaded.index(), store.index());
+        assert!(loaded.documents().eq(store.documents()));
+        assert_eq!(loaded.get("1").unwrap().vector.precision(), Precision::F16);
+        assert_eq!(loaded.get("2").unwrap().vector.precision(), Precision::F32);
+        assert_eq!(loaded.get("3").unwrap().vector.precision(), Precision::F64);
+        assert_eq!(loaded.search(&vectors(1, 16)[0], 5).unwrap().len(), 5);
+    }
+}
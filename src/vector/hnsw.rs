@@ -0,0 +1,196 @@
+//! Hierarchical navigable small world graph
+//!
+//! Approximate nearest neighbor search after Malkov and Yashunin, with the
+//! simple neighbor selection: every node links to its `m` closest candidates
+//! per layer (`2 * m` on the bottom layer).
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashSet},
+};
+
+use super::HnswConfig;
+
+/// Distance to a node, ordered for the heaps
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    distance: f64,
+    node: usize,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance.total_cmp(&other.distance).then(self.node.cmp(&other.node))
+    }
+}
+
+/// Deterministic generator of node levels
+#[derive(Debug, Clone)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_f64(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+
+        // 53 random bits in (0, 1]
+        ((z >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Hnsw {
+    config: HnswConfig,
+    /// Neighbors of every node, by layer
+    links: Vec<Vec<Vec<usize>>>,
+    entry: Option<usize>,
+    rng: SplitMix64,
+}
+
+impl Hnsw {
+    pub fn new(config: HnswConfig) -> Self {
+        Self {
+            config,
+            links: Vec::new(),
+            entry: None,
+            rng: SplitMix64(0x5eed),
+        }
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        match layer {
+            0 => self.config.m * 2,
+            _ => self.config.m,
+        }
+    }
+
+    fn top_layer(&self) -> usize {
+        self.entry.map_or(0, |entry| self.links[entry].len() - 1)
+    }
+
+    fn random_level(&mut self) -> usize {
+        let ml = 1.0 / (self.config.m.max(2) as f64).ln();
+        (-self.rng.next_f64().ln() * ml).floor() as usize
+    }
+
+    /// Closest nodes to the query on `layer`, nearest first
+    fn search_layer<D>(&self, distance: &D, entries: &[Candidate], ef: usize, layer: usize) -> Vec<Candidate>
+    where
+        D: Fn(usize) -> f64,
+    {
+        let mut visited = entries.iter().map(|c| c.node).collect::<HashSet<_>>();
+        let mut candidates = entries.iter().copied().map(Reverse).collect::<BinaryHeap<_>>();
+        let mut found = entries.iter().copied().collect::<BinaryHeap<_>>();
+
+        while let Some(Reverse(nearest)) = candidates.pop() {
+            let furthest = found.peek().map_or(f64::INFINITY, |c| c.distance);
+            if nearest.distance > furthest && found.len() >= ef {
+                break;
+            }
+
+            for &neighbor in self.links[nearest.node].get(layer).into_iter().flatten() {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+
+                let candidate = Candidate { distance: distance(neighbor), node: neighbor };
+                let furthest = found.peek().map_or(f64::INFINITY, |c| c.distance);
+
+                if found.len() < ef || candidate.distance < furthest {
+                    candidates.push(Reverse(candidate));
+                    found.push(candidate);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+
+        found.into_sorted_vec()
+    }
+
+    /// Greedy descent from the entry point to `layer`
+    fn descend<D>(&self, distance: &D, layer: usize) -> Option<Vec<Candidate>>
+    where
+        D: Fn(usize) -> f64,
+    {
+        let entry = self.entry?;
+        let mut nearest = vec![Candidate { distance: distance(entry), node: entry }];
+
+        for upper in (layer + 1..=self.top_layer()).rev() {
+            nearest = self.search_layer(distance, &nearest, 1, upper);
+        }
+
+        Some(nearest)
+    }
+
+    /// Add node `node`, the next one in order, with `distance` between any two nodes
+    pub fn insert<D>(&mut self, node: usize, distance: D)
+    where
+        D: Fn(usize, usize) -> f64,
+    {
+        debug_assert_eq!(node, self.links.len());
+
+        let level = self.random_level();
+        self.links.push(vec![Vec::new(); level + 1]);
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(node);
+            return;
+        };
+
+        let top = self.top_layer();
+        let to_node = |other: usize| distance(node, other);
+        let mut nearest = self.descend(&to_node, level).unwrap_or_else(|| vec![Candidate { distance: to_node(entry), node: entry }]);
+
+        for layer in (0..=level.min(top)).rev() {
+            nearest = self.search_layer(&to_node, &nearest, self.config.ef_construction, layer);
+
+            let max_links = self.max_links(layer);
+            let neighbors = nearest.iter().take(self.config.m).map(|c| c.node).collect::<Vec<_>>();
+
+            for &neighbor in &neighbors {
+                let links = &mut self.links[neighbor][layer];
+                links.push(node);
+
+                if links.len() > max_links {
+                    let mut ranked = links.iter().map(|&other| (distance(neighbor, other), other)).collect::<Vec<_>>();
+                    ranked.sort_by(|a, b| a.0.total_cmp(&b.0));
+                    *links = ranked.into_iter().take(max_links).map(|(_, other)| other).collect();
+                }
+            }
+
+            self.links[node][layer] = neighbors;
+        }
+
+        if level > top {
+            self.entry = Some(node);
+        }
+    }
+
+    /// Approximate `ef` nearest nodes, nearest first
+    pub fn search<D>(&self, distance: D, ef: usize) -> Vec<(usize, f64)>
+    where
+        D: Fn(usize) -> f64,
+    {
+        let Some(nearest) = self.descend(&distance, 0) else {
+            return Vec::new();
+        };
+
+        self.search_layer(&distance, &nearest, ef.max(1), 0)
+            .into_iter()
+            .map(|c| (c.node, c.distance))
+            .collect()
+    }
+}
@@ -0,0 +1,30 @@
+use std::{io::Cursor, sync::Arc};
+
+use ollama_rest::{blocking::Ollama, errors::Error, models::digest::Digest, testing::{MockResponse, MockServer}};
+use reqwest::StatusCode;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn verified_reader_uploads() {
+    let server = Arc::new(MockServer::start().await.unwrap());
+    let (url, mock) = (server.url(), server.clone());
+
+    tokio::task::spawn_blocking(move || {
+        let ollama = Ollama::new(url).unwrap();
+
+        let data = vec![7u8; 100_000];
+        let digest = Digest::from_bytes(&data);
+
+        // Sized bodies may not be read up to EOF, but are verified all the same
+        ollama.upload_blob_from_reader(&digest, Cursor::new(data.clone()), Some(data.len() as u64), None::<fn(&_)>).unwrap();
+        ollama.blob_exists(&digest).unwrap();
+
+        let wrong = Digest::from_bytes(b"something else");
+        let err = ollama.upload_blob_from_reader(&wrong, Cursor::new(data.clone()), None, None::<fn(&_)>).unwrap_err();
+        assert!(matches!(err, Error::ErrorStatus(StatusCode::BAD_REQUEST)));
+
+        // Servers that don't verify content are caught by the digest check
+        mock.mock(&format!("/api/blobs/{wrong}"), MockResponse::status(StatusCode::CREATED));
+        let err = ollama.upload_blob_from_reader(&wrong, Cursor::new(data), None, None::<fn(&_)>).unwrap_err();
+        assert!(matches!(err, Error::DigestMismatch { actual, .. } if actual == digest));
+    }).await.unwrap();
+}
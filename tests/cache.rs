@@ -0,0 +1,78 @@
+mod common;
+
+use futures::StreamExt;
+use ollama_rest::{
+    cache::{CacheStats, DiskCache, MemoryCache, ResponseCache},
+    models::{embeddings::EmbedRequest, generate::GenerationRequest},
+};
+use serde_json::json;
+
+use common::{server, MODEL};
+
+#[tokio::test]
+async fn memory_cache() {
+    let server = server().await;
+    let cache = ResponseCache::new(MemoryCache::new(16));
+    let ollama = server.client().with_cache(cache.clone());
+
+    let embed = serde_json::from_value::<EmbedRequest>(json!({ "model": MODEL, "input": "Hello" })).unwrap();
+    let first = ollama.embed(&embed).await.unwrap();
+    let second = ollama.embed(&embed).await.unwrap();
+    assert_eq!(first.embeddings, second.embeddings);
+    assert_eq!(server.requests_to("/api/embed").len(), 1);
+
+    // Only deterministic generations are cached
+    let random = serde_json::from_value::<GenerationRequest>(json!({ "model": MODEL, "prompt": "Why is the sky blue?", "stream": false })).unwrap();
+    ollama.generate(&random, None::<fn(&_)>).await.unwrap();
+    ollama.generate(&random, None::<fn(&_)>).await.unwrap();
+    assert_eq!(server.requests_to("/api/generate").len(), 2);
+
+    // Streamed calls are replayed chunk by chunk
+    let seeded = serde_json::from_value::<GenerationRequest>(json!({ "model": MODEL, "prompt": "Why is the sky blue?", "options": { "seed": 42 } })).unwrap();
+    let mut replays = Vec::new();
+    for _ in 0..2 {
+        let chunks = ollama.generate_streamed(&seeded).await.unwrap()
+            .map(|res| res.unwrap().response)
+            .collect::<Vec<_>>()
+            .await;
+        replays.push(chunks);
+    }
+
+    assert_eq!(replays[0], replays[1]);
+    assert!(replays[0].len() > 1);
+    assert_eq!(server.requests_to("/api/generate").len(), 3);
+
+    // Non-streamed calls are cached apart from streamed ones
+    let seeded = serde_json::from_value::<GenerationRequest>(json!({ "model": MODEL, "prompt": "Why is the sky blue?", "options": { "seed": 42 }, "stream": false })).unwrap();
+    let res = ollama.generate(&seeded, None::<fn(&_)>).await.unwrap();
+    assert_eq!(res.response, "Hello from the mock server!");
+    assert_eq!(ollama.generate(&seeded, None::<fn(&_)>).await.unwrap().response, res.response);
+    assert_eq!(server.requests_to("/api/generate").len(), 4);
+
+    assert_eq!(cache.stats(), CacheStats { hits: 3, misses: 3 });
+}
+
+#[tokio::test]
+async fn disk_cache() {
+    let dir = std::env::temp_dir().join(format!("ollama-rest-cache-{}", std::process::id()));
+    let server = server().await;
+
+    let embed = serde_json::from_value::<EmbedRequest>(json!({ "model": MODEL, "input": ["Hello", "World"] })).unwrap();
+    let first = server.client()
+        .with_cache(ResponseCache::new(DiskCache::new(&dir)))
+        .embed(&embed)
+        .await
+        .unwrap();
+
+    // Entries outlive the cache that stored them
+    let second = server.client()
+        .with_cache(ResponseCache::new(DiskCache::new(&dir)))
+        .embed(&embed)
+        .await
+        .unwrap();
+
+    assert_eq!(first.embeddings, second.embeddings);
+    assert_eq!(server.requests_to("/api/embed").len(), 1);
+
+    let _ = std::fs::remove_dir_all(dir);
+}
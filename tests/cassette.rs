@@ -0,0 +1,143 @@
+mod common;
+
+use futures::StreamExt;
+use std::io::Cursor;
+
+use ollama_rest::{cassette::{Cassette, CassetteMode}, errors::Error, models::{digest::Digest, generate::GenerationRequest, model::{ModelCopyRequest, ModelDeletionRequest}}, testing::MockServer, Ollama};
+use serde_json::json;
+
+use common::{server, MODEL};
+
+fn cassette_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("ollama-rest-cassette-{}-{name}.json", std::process::id()))
+}
+
+fn generation_request(keep_alive: &str) -> GenerationRequest {
+    serde_json::from_value(json!({
+        "model": MODEL,
+        "prompt": "Why is the sky blue?",
+        "keep_alive": keep_alive,
+        "options": { "temperature": 0.0, "seed": 42 },
+    })).unwrap()
+}
+
+async fn generate(ollama: &Ollama, request: &GenerationRequest) -> String {
+    let mut stream = ollama.generate_streamed(request).await.unwrap();
+
+    let mut text = String::new();
+    while let Some(res) = stream.next().await {
+        text.push_str(&res.unwrap().response);
+    }
+
+    text
+}
+
+#[tokio::test]
+async fn record_then_replay() {
+    let path = cassette_path("record-then-replay");
+
+    let server = server().await;
+
+    let host = server.url();
+    let recorder = Ollama::with_cassette(host.clone(), Cassette::new(&path).with_mode(CassetteMode::Record)).unwrap();
+
+    let text = generate(&recorder, &generation_request("5m")).await;
+    assert!(!text.is_empty());
+    assert_eq!(recorder.version().await.unwrap().version.to_string(), "0.9.0");
+
+    // The cassette is written once the recorder is dropped, and replaying must not touch the server at all
+    drop(recorder);
+    drop(server);
+
+    let player = Ollama::with_cassette(host, Cassette::new(&path).with_mode(CassetteMode::Replay)).unwrap();
+
+    // `keep_alive` and options ordering are ignored when matching
+    let mut request = serde_json::to_value(generation_request("1h")).unwrap();
+    request["options"] = json!({ "seed": 42, "temperature": 0.0 });
+    let request: GenerationRequest = serde_json::from_value(request).unwrap();
+
+    assert_eq!(generate(&player, &request).await, text);
+    assert_eq!(player.version().await.unwrap().version.to_string(), "0.9.0");
+
+    // Each interaction is replayed once, and unknown requests are rejected
+    assert!(matches!(player.version().await, Err(Error::NoRecordedInteraction { .. })));
+    assert!(matches!(
+        player.delete_model(&ModelDeletionRequest { name: MODEL.to_string() }).await,
+        Err(Error::NoRecordedInteraction { .. }),
+    ));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn record_then_replay_status_only_calls() {
+    let path = cassette_path("status-only");
+
+    let server = server().await;
+    let host = server.url();
+
+    let copy = ModelCopyRequest { source: MODEL.to_string(), destination: "backup".to_string() };
+    let delete = ModelDeletionRequest { name: "backup".to_string() };
+
+    // These calls only check the status, so their bodies are never read
+    let recorder = Ollama::with_cassette(host.clone(), Cassette::new(&path).with_mode(CassetteMode::Record)).unwrap();
+    recorder.copy_model(&copy).await.unwrap();
+    recorder.delete_model(&delete).await.unwrap();
+
+    drop(recorder);
+    drop(server);
+
+    let player = Ollama::with_cassette(host, Cassette::new(&path).with_mode(CassetteMode::Replay)).unwrap();
+    player.copy_model(&copy).await.unwrap();
+    player.delete_model(&delete).await.unwrap();
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn auto_mode_records_missing_cassettes() {
+    let path = cassette_path("auto");
+    let _ = std::fs::remove_file(&path);
+
+    let server = MockServer::start().await.unwrap();
+    let ollama = Ollama::with_cassette(server.url(), Cassette::new(&path).with_mode(CassetteMode::Auto)).unwrap();
+    ollama.version().await.unwrap();
+
+    // Recordings are kept in memory until the instance is dropped
+    assert!(!path.exists());
+    drop(ollama);
+    assert!(path.exists());
+
+    let ollama = Ollama::with_cassette(server.url(), Cassette::new(&path).with_mode(CassetteMode::Auto)).unwrap();
+    ollama.version().await.unwrap();
+
+    assert_eq!(server.requests_to("/api/version").len(), 1);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn record_then_replay_blob_uploads() {
+    let path = cassette_path("blob-upload");
+
+    let server = server().await;
+    let host = server.url();
+
+    let data = vec![7u8; 100_000];
+    let digest = Digest::from_bytes(&data);
+
+    let recorder = Ollama::with_cassette(host.clone(), Cassette::new(&path).with_mode(CassetteMode::Record)).unwrap();
+    recorder.upload_blob_from_reader(&digest, Cursor::new(data.clone()), None, None::<fn(&_)>).await.unwrap();
+
+    drop(recorder);
+    drop(server);
+
+    // Streamed bodies are matched by length and digest
+    let player = Ollama::with_cassette(host.clone(), Cassette::new(&path).with_mode(CassetteMode::Replay)).unwrap();
+    let err = player.upload_blob_from_reader(&digest, Cursor::new(vec![8u8; 100_000]), None, None::<fn(&_)>).await.unwrap_err();
+    assert!(matches!(err, Error::NoRecordedInteraction { .. }));
+
+    player.upload_blob_from_reader(&digest, Cursor::new(data), None, None::<fn(&_)>).await.unwrap();
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,28 @@
+//! Helpers shared by the integration tests
+
+#![allow(dead_code)]
+
+use ollama_rest::{models::generate::GenerationRequest, testing::{MockModel, MockServer}};
+use serde_json::json;
+
+/// Model served by [`server()`]
+pub const MODEL: &str = "llama3.2:1b";
+
+/// Start a mock server serving [`MODEL`]
+pub async fn server() -> MockServer {
+    server_with(&[MODEL]).await
+}
+
+/// Start a mock server serving each of `models`
+pub async fn server_with(models: &[&str]) -> MockServer {
+    let server = MockServer::start().await.unwrap();
+    for model in models {
+        server.add_model(MockModel::new(*model));
+    }
+    server
+}
+
+/// Request asking `model` why the sky is blue, streamed or not
+pub fn generation_request(model: &str, stream: bool) -> GenerationRequest {
+    serde_json::from_value(json!({ "model": model, "prompt": "Why is the sky blue?", "stream": stream })).unwrap()
+}
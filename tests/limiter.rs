@@ -0,0 +1,45 @@
+mod common;
+
+use std::time::Duration;
+
+use ollama_rest::{
+    errors::Error,
+    limiter::{ConcurrencyLimiter, Priority},
+};
+
+use common::{generation_request, server, MODEL};
+
+#[tokio::test]
+async fn queue_and_reject() {
+    let server = server().await;
+
+    let limiter = ConcurrencyLimiter::new(1).with_max_queued(1);
+    let ollama = server.client().with_limiter(limiter.clone());
+    let urgent = ollama.clone().with_priority(Priority::High);
+
+    // A streamed response holds its permit until dropped
+    let permit_holder = ollama.generate_streamed(&generation_request(MODEL, true)).await.unwrap();
+    assert_eq!(limiter.stats().running, 1);
+
+    let request = generation_request(MODEL, false);
+    let queued = tokio::spawn(async move { urgent.generate(&request, None::<fn(&_)>).await });
+
+    while limiter.stats().queued == 0 {
+        tokio::task::yield_now().await;
+    }
+
+    // The only permit and queue slot are taken
+    let rejected = ollama.generate(&generation_request(MODEL, false), None::<fn(&_)>).await;
+    assert!(matches!(rejected, Err(Error::QueueFull)));
+
+    // Requests outside generation, chat and embeddings are not limited
+    ollama.version().await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    drop(permit_holder);
+    queued.await.unwrap().unwrap();
+
+    let stats = limiter.stats();
+    assert_eq!((stats.admitted, stats.rejected, stats.waited, stats.running), (2, 1, 1, 0));
+    assert!(stats.max_queue_time >= Duration::from_millis(50));
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use futures::StreamExt;
+use ollama_rest::{
+    limiter::ConcurrencyLimiter,
+    metrics::{MetricsSink, RequestMetrics},
+};
+
+use common::{generation_request, server, MODEL};
+
+#[derive(Default)]
+struct Collected(Mutex<Vec<RequestMetrics>>);
+
+impl MetricsSink for Collected {
+    fn record(&self, metrics: &RequestMetrics) {
+        self.0.lock().unwrap().push(metrics.clone());
+    }
+}
+
+#[tokio::test]
+async fn metrics_of_every_call() {
+    let server = server().await;
+
+    let collected = Arc::new(Collected::default());
+    let ollama = server.client()
+        .with_limiter(ConcurrencyLimiter::new(1))
+        .with_metrics(collected.clone());
+
+    let request = generation_request(MODEL, true);
+    let mut stream = ollama.generate_streamed(&request).await.unwrap();
+    while stream.next().await.is_some() {}
+
+    // Reported once the stream is dropped
+    assert!(collected.0.lock().unwrap().is_empty());
+    drop(stream);
+
+    ollama.version().await.unwrap();
+
+    let missing = generation_request("missing", false);
+    assert!(ollama.generate(&missing, None::<fn(&_)>).await.is_err());
+
+    let collected = collected.0.lock().unwrap();
+    assert_eq!(collected.len(), 3);
+
+    let generate = &collected[0];
+    assert_eq!(generate.endpoint, "/api/generate");
+    assert_eq!(generate.model.as_deref(), Some(MODEL));
+    assert_eq!(generate.status, Some(200));
+    assert!(!generate.failed);
+    assert_eq!(generate.eval_count, Some(5));
+    assert!(generate.queue_time.is_some());
+    assert!(generate.time_to_first_token.is_some_and(|ttft| ttft <= generate.duration));
+    assert!(generate.tokens_per_second().is_some());
+
+    let version = &collected[1];
+    assert_eq!((version.endpoint.as_str(), version.model.as_deref(), version.failed), ("/api/version", None, false));
+    assert_eq!(version.queue_time, None);
+
+    let missing = &collected[2];
+    assert_eq!((missing.model.as_deref(), missing.status, missing.failed), (Some("missing"), Some(404), true));
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use futures::StreamExt;
+use ollama_rest::{
+    errors::Error,
+    middleware::{Middleware, MiddlewareRequest, ResponseContext},
+    models::generate::GenerationRequest,
+    testing::MockServer,
+    transport::BoxError,
+};
+use serde_json::{json, Value};
+
+use common::{generation_request, server, MODEL};
+
+/// Tags requests and keeps the order layers ran in
+struct Tag {
+    name: &'static str,
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+impl Middleware for Tag {
+    fn on_request(&self, request: &mut MiddlewareRequest) -> Result<(), BoxError> {
+        self.log.lock().unwrap().push(format!("request {}", self.name));
+        request.headers.append("x-layer", self.name.parse()?);
+        Ok(())
+    }
+
+    fn on_item(&self, context: &ResponseContext, _: &mut Value) -> Result<(), BoxError> {
+        assert_eq!(context.request_headers.get_all("x-layer").iter().count(), 2);
+        self.log.lock().unwrap().push(format!("item {}", self.name));
+        Ok(())
+    }
+}
+
+/// Redacts prompts and shouts responses
+struct Rewrite;
+
+impl Middleware for Rewrite {
+    fn on_request(&self, request: &mut MiddlewareRequest) -> Result<(), BoxError> {
+        if let Some(prompt) = request.body.as_mut().and_then(|body| body.get_mut("prompt")) {
+            *prompt = Value::from(prompt.as_str().unwrap_or_default().replace("secret", "[redacted]"));
+        }
+        Ok(())
+    }
+
+    fn on_item(&self, _: &ResponseContext, item: &mut Value) -> Result<(), BoxError> {
+        if let Some(response) = item.get_mut("response") {
+            *response = Value::from(response.as_str().unwrap_or_default().to_uppercase());
+        }
+        Ok(())
+    }
+}
+
+struct Deny;
+
+impl Middleware for Deny {
+    fn on_request(&self, request: &mut MiddlewareRequest) -> Result<(), BoxError> {
+        match request.url.path() {
+            "/api/version" => Err("version checks are not allowed".into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn layers_in_order() {
+    let server = server().await;
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let ollama = server.client()
+        .with_middleware(Tag { name: "outer", log: log.clone() })
+        .with_middleware(Tag { name: "inner", log: log.clone() })
+        .with_middleware(Rewrite);
+
+    let request = serde_json::from_value::<GenerationRequest>(json!({ "model": MODEL, "prompt": "My secret is safe", "stream": false })).unwrap();
+    let res = ollama.generate(&request, None::<fn(&_)>).await.unwrap();
+    assert_eq!(res.response, "HELLO FROM THE MOCK SERVER!");
+
+    let sent = &server.requests_to("/api/generate")[0];
+    assert_eq!(sent.json().unwrap()["prompt"], "My [redacted] is safe");
+    assert_eq!(sent.headers.get_all("x-layer").iter().collect::<Vec<_>>(), ["outer", "inner"]);
+
+    assert_eq!(*log.lock().unwrap(), ["request outer", "request inner", "item inner", "item outer"]);
+
+    // Streamed responses go through item by item
+    log.lock().unwrap().clear();
+    let request = generation_request(MODEL, true);
+    let chunks = ollama.generate_streamed(&request).await.unwrap()
+        .map(|res| res.unwrap().response)
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(chunks.concat(), "HELLO FROM THE MOCK SERVER!");
+    assert_eq!(log.lock().unwrap().len(), 2 + 2 * chunks.len());
+}
+
+#[tokio::test]
+async fn rejected_requests() {
+    let server = MockServer::start().await.unwrap();
+    let ollama = server.client().with_middleware(Deny);
+
+    assert!(matches!(ollama.version().await, Err(Error::Middleware(_))));
+    assert!(server.requests().is_empty());
+
+    ollama.running_models().await.unwrap();
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use std::{io::Cursor, time::{Duration, Instant}};
+
+use futures::StreamExt;
+use ollama_rest::{errors::Error, models::{blob::BlobUploadProgress, chat::ChatRequest, digest::Digest, embeddings::EmbedRequest, model::{Capability, ModelDeletionRequest, ModelPullStatus, ModelSyncRequest}}, testing::{MockResponse, MockServer}};
+use reqwest::StatusCode;
+use serde_json::json;
+
+use common::{generation_request, server, MODEL};
+
+#[tokio::test]
+async fn streamed_generation() {
+    let server = server().await;
+    server.set_completion("Because of Rayleigh scattering.");
+
+    let mut stream = server.client().generate_streamed(&generation_request(MODEL, true)).await.unwrap();
+
+    let mut text = String::new();
+    let mut done = false;
+    while let Some(res) = stream.next().await {
+        let res = res.unwrap();
+        text.push_str(&res.response);
+        done = res.done;
+    }
+
+    assert!(done);
+    assert_eq!(text, "Because of Rayleigh scattering.");
+
+    let recorded = server.requests_to("/api/generate");
+    assert_eq!(recorded.len(), 1);
+    assert_eq!(recorded[0].json().unwrap()["prompt"], "Why is the sky blue?");
+}
+
+#[tokio::test]
+async fn chat_and_running_models() {
+    let server = server().await;
+    let ollama = server.client();
+
+    let request = serde_json::from_value::<ChatRequest>(json!({
+        "model": MODEL,
+        "messages": [{ "role": "user", "content": "Hi" }],
+        "stream": false,
+    })).unwrap();
+
+    let res = ollama.chat(&request, None::<fn(&_)>).await.unwrap();
+    assert_eq!(res.message.unwrap().content, "Hello from the mock server!");
+
+    let running = ollama.running_models().await.unwrap();
+    assert_eq!(running.models.len(), 1);
+    assert_eq!(running.models[0].name, MODEL);
+}
+
+#[tokio::test]
+async fn model_management() {
+    let server = MockServer::start().await.unwrap();
+    let ollama = server.client();
+
+    assert!(ollama.local_models().await.unwrap().models.is_empty());
+
+    let mut statuses = Vec::new();
+    ollama.pull_model(&ModelSyncRequest { name: MODEL.to_string(), insecure: None, stream: None }, Some(|res: &ModelPullStatus| {
+        statuses.push(res.status.clone());
+    })).await.unwrap();
+
+    assert_eq!(statuses.last().map(String::as_str), Some("success"));
+    assert_eq!(ollama.local_models().await.unwrap().models[0].name, MODEL);
+    assert_eq!(ollama.model_capabilities(MODEL).await.unwrap(), vec![Capability::Completion]);
+
+    ollama.delete_model(&ModelDeletionRequest { name: MODEL.to_string() }).await.unwrap();
+    assert!(matches!(
+        ollama.delete_model(&ModelDeletionRequest { name: MODEL.to_string() }).await,
+        Err(Error::NotExists),
+    ));
+}
+
+#[tokio::test]
+async fn blobs_and_embeddings() {
+    let server = server().await;
+    let ollama = server.client();
+
+    let path = std::env::temp_dir().join(format!("ollama-rest-mock-{}.bin", std::process::id()));
+    tokio::fs::write(&path, vec![42u8; 200_000]).await.unwrap();
+
+    let digest = ollama.upload_blob_from_path(&path, None::<fn(&_)>).await.unwrap();
+    ollama.blob_exists(&digest).await.unwrap();
+
+    // Already uploaded, so no more POST
+    ollama.upload_blob_from_path(&path, None::<fn(&_)>).await.unwrap();
+    assert_eq!(server.requests().iter().filter(|req| req.method == "POST").count(), 1);
+
+    tokio::fs::remove_file(&path).await.unwrap();
+
+    let res = ollama.embed(&serde_json::from_value::<EmbedRequest>(json!({
+        "model": MODEL,
+        "input": ["first", "second"],
+    })).unwrap()).await.unwrap();
+
+    assert_eq!(res.embeddings.len(), 2);
+    assert_ne!(res.embeddings[0], res.embeddings[1]);
+}
+
+#[tokio::test]
+async fn blob_upload_progress_and_mismatch() {
+    let server = server().await;
+    let ollama = server.client();
+
+    let data = vec![7u8; 200_000];
+    let digest = Digest::from_bytes(&data);
+
+    let chunks = futures::stream::iter(data.chunks(64 * 1024).map(|chunk| Ok::<_, std::io::Error>(chunk.to_vec())).collect::<Vec<_>>());
+    let mut completed = Vec::new();
+    ollama.upload_blob(&digest, chunks, Some(data.len() as u64), Some(|progress: &BlobUploadProgress| completed.push(progress.completed))).await.unwrap();
+
+    assert_eq!(completed, [65_536, 131_072, 196_608, 200_000]);
+    ollama.blob_exists(&digest).await.unwrap();
+
+    // Rejected uploads fail with the server's status
+    let wrong = Digest::from_bytes(b"something else");
+    let err = ollama.upload_blob_from_reader(&wrong, Cursor::new(data.clone()), None, None::<fn(&_)>).await.unwrap_err();
+    assert!(matches!(err, Error::ErrorStatus(StatusCode::BAD_REQUEST)));
+
+    // Servers that don't verify content are caught by the digest check
+    server.mock(&format!("/api/blobs/{wrong}"), MockResponse::status(StatusCode::CREATED));
+    let err = ollama.upload_blob_from_reader(&wrong, Cursor::new(data), None, None::<fn(&_)>).await.unwrap_err();
+    assert!(matches!(err, Error::DigestMismatch { actual, .. } if actual == digest));
+}
+
+#[tokio::test]
+async fn injected_errors_and_latency() {
+    let server = server().await;
+    let ollama = server.client();
+
+    server.mock_once("/api/tags", MockResponse::error(StatusCode::SERVICE_UNAVAILABLE, "busy").with_latency(Duration::from_millis(100)));
+
+    let started = Instant::now();
+    assert!(ollama.local_models().await.is_err());
+    assert!(started.elapsed() >= Duration::from_millis(100));
+
+    // One-shot mocks are consumed
+    assert_eq!(ollama.local_models().await.unwrap().models.len(), 1);
+
+    server.mock("/api/generate", MockResponse::stream([
+        json!({ "model": MODEL, "created_at": "2024-01-01T00:00:00Z", "response": "scripted", "done": false }),
+        json!({ "model": MODEL, "created_at": "2024-01-01T00:00:00Z", "response": "", "done": true }),
+    ]).with_chunk_delay(Duration::from_millis(10)));
+
+    let chunks: Vec<_> = ollama.generate_streamed(&generation_request(MODEL, true)).await.unwrap().collect().await;
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0].as_ref().unwrap().response, "scripted");
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use std::time::Duration;
+
+use futures::StreamExt;
+use ollama_rest::{
+    errors::Error,
+    pool::{CircuitBreakerConfig, CircuitState, OllamaPool, Strategy},
+    testing::MockResponse,
+    Ollama,
+};
+use reqwest::StatusCode;
+
+use common::{generation_request, server, MODEL};
+
+/// Client of a port nothing listens on
+fn dead_host() -> Ollama {
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    format!("http://{addr}").parse().unwrap()
+}
+
+#[tokio::test]
+async fn round_robin() {
+    let (a, b) = (server().await, server().await);
+    let pool = OllamaPool::new([a.client(), b.client()]);
+
+    for _ in 0..4 {
+        pool.generate(&generation_request(MODEL, false), None::<fn(&_)>).await.unwrap();
+    }
+
+    assert_eq!(a.requests_to("/api/generate").len(), 2);
+    assert_eq!(b.requests_to("/api/generate").len(), 2);
+}
+
+#[tokio::test]
+async fn failover_and_circuit_breaker() {
+    let alive = server().await;
+    let pool = OllamaPool::new([dead_host(), alive.client()])
+        .with_circuit_breaker(CircuitBreakerConfig { failure_threshold: 2, cooldown: Duration::from_secs(60) });
+
+    for _ in 0..4 {
+        let mut stream = pool.generate_streamed(&generation_request(MODEL, true)).await.unwrap();
+        while let Some(res) = stream.next().await {
+            res.unwrap();
+        }
+    }
+
+    assert_eq!(alive.requests_to("/api/generate").len(), 4);
+
+    let status = pool.status();
+    assert_eq!(status[0].circuit, CircuitState::Open);
+    assert_eq!(status[1].circuit, CircuitState::Closed);
+    assert!(status.iter().all(|host| host.in_flight == 0));
+
+    let pool = OllamaPool::new([dead_host()]);
+    assert!(matches!(pool.generate(&generation_request(MODEL, false), None::<fn(&_)>).await, Err(Error::ClientCreation(_))));
+    assert!(matches!(OllamaPool::new([]).execute(None, |ollama| ollama.version()).await, Err(Error::NoAvailableHost)));
+}
+
+#[tokio::test]
+async fn health_checks_and_loaded_models() {
+    let (cold, warm) = (server().await, server().await);
+    let pool = OllamaPool::new([dead_host(), cold.client(), warm.client()])
+        .with_strategy(Strategy::PreferLoaded);
+
+    // Loading the model on one host marks it running there
+    warm.client().generate(&generation_request(MODEL, false), None::<fn(&_)>).await.unwrap();
+    pool.check_health().await;
+
+    let status = pool.status();
+    assert!(!status[0].healthy);
+    assert!(status[1].healthy && status[1].loaded_models.is_empty());
+    assert_eq!(status[2].loaded_models, vec![MODEL.to_string()]);
+
+    for _ in 0..3 {
+        pool.generate(&generation_request(MODEL, false), None::<fn(&_)>).await.unwrap();
+    }
+
+    assert!(cold.requests_to("/api/generate").is_empty());
+    assert_eq!(warm.requests_to("/api/generate").len(), 4);
+
+    // A proxy answering `/api/version` with something other than JSON is not healthy
+    cold.mock_once("/api/version", MockResponse::status(StatusCode::OK));
+    pool.check_health().await;
+    assert!(!pool.status()[1].healthy);
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use ollama_rest::{
+    errors::Error,
+    rag::{Chunker, RagPipeline},
+    testing::MockResponse,
+    vector::{Filter, Metadata},
+};
+use reqwest::StatusCode;
+use serde_json::json;
+
+use common::server_with;
+
+const EMBEDDING_MODEL: &str = "nomic-embed-text";
+const CHAT_MODEL: &str = "llama3.2:1b";
+
+const DOCUMENT: &str = "# Sky\n\nThe sky is blue. It scatters light.\n\n# Grass\n\nGrass is green.";
+
+#[tokio::test]
+async fn ask_with_citations() {
+    let server = server_with(&[EMBEDDING_MODEL, CHAT_MODEL]).await;
+    server.set_completion("The sky is blue [1], see also [1, 9].");
+
+    let mut rag = RagPipeline::new(server.client(), EMBEDDING_MODEL, CHAT_MODEL)
+        .with_chunker(Chunker::markdown(200, 0))
+        .with_top_k(2);
+
+    let mut metadata = Metadata::new();
+    metadata.insert("lang".into(), "en".into());
+    assert_eq!(rag.add_document("nature.md", DOCUMENT, metadata.clone()).await.unwrap(), 2);
+    assert_eq!(rag.store().len(), 2);
+    assert_eq!(server.requests_to("/api/embed").len(), 1);
+
+    let chunk = rag.store().get("nature.md#1").unwrap();
+    assert_eq!(chunk.metadata["heading"], "Grass");
+    assert_eq!(chunk.metadata["lang"], "en");
+    assert_eq!(&DOCUMENT[chunk.metadata["start"].as_u64().unwrap() as usize..], "# Grass\n\nGrass is green.");
+
+    // Failed re-indexing keeps the previous chunks
+    server.mock_once("/api/embed", MockResponse::error(StatusCode::INTERNAL_SERVER_ERROR, "out of memory"));
+    assert!(rag.add_document("nature.md", "The sky is blue.", Metadata::new()).await.is_err());
+    assert_eq!(rag.store().len(), 2);
+
+    server.mock_once("/api/embed", MockResponse::json(json!({ "model": EMBEDDING_MODEL, "embeddings": [[1.0, 0.0]] })));
+    assert!(matches!(rag.add_document("nature.md", DOCUMENT, Metadata::new()).await, Err(Error::EmptyResponse)));
+    assert_eq!(rag.store().len(), 2);
+
+    // Adding a source again replaces its chunks
+    rag.add_document("nature.md", "The sky is blue.", metadata).await.unwrap();
+    assert_eq!(rag.store().len(), 1);
+    rag.add_document("nature.md", DOCUMENT, Metadata::new()).await.unwrap();
+
+    let answer = rag.ask("What color is the sky?", None).await.unwrap();
+    assert_eq!(answer.answer, "The sky is blue [1], see also [1, 9].");
+    assert_eq!(answer.context.len(), 2);
+    assert_eq!(answer.citations, [0]);
+    assert_eq!(answer.cited().next().unwrap().source, "nature.md");
+
+    let chat = server.requests_to("/api/chat");
+    let body = chat[0].json().unwrap();
+    let system = body["messages"][0]["content"].as_str().unwrap();
+    assert!(system.contains("[1] Source: nature.md"));
+    assert!(system.contains("[2] Source: nature.md"));
+    assert_eq!(body["messages"][1]["content"], "What color is the sky?");
+
+    let answer = rag.ask("What color is grass?", Some(&Filter::eq("heading", "Grass"))).await.unwrap();
+    assert_eq!(answer.context.len(), 1);
+    assert_eq!(answer.context[0].text, "# Grass\n\nGrass is green.");
+}
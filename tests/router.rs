@@ -0,0 +1,56 @@
+mod common;
+
+use futures::StreamExt;
+use ollama_rest::{
+    errors::Error,
+    models::embeddings::EmbedRequest,
+    pool::OllamaPool,
+    router::ModelRouter,
+    testing::MockModel,
+};
+use serde_json::json;
+
+use common::{generation_request, server_with};
+
+#[tokio::test]
+async fn route_by_inventory() {
+    let (a, b) = (server_with(&["llama3.2:1b"]).await, server_with(&["mistral:latest", "nomic-embed-text:latest"]).await);
+    let router = ModelRouter::new(OllamaPool::new([a.client(), b.client()]));
+
+    for _ in 0..3 {
+        let res = router.generate(&generation_request("llama3.2:1b", false), None::<fn(&_)>).await.unwrap();
+        assert_eq!(res.host, a.client().host());
+    }
+
+    let res = router.generate_streamed(&generation_request("mistral:latest", true)).await.unwrap();
+    assert_eq!(res.host, b.client().host());
+    assert!(res.response.all(|res| async move { res.is_ok() }).await);
+
+    let res = router.embed(&serde_json::from_value::<EmbedRequest>(json!({ "model": "nomic-embed-text:latest", "input": "Hi" })).unwrap()).await.unwrap();
+    assert_eq!(res.host, b.client().host());
+
+    assert_eq!(a.requests_to("/api/generate").len(), 3);
+    // Untagged names mean `latest`
+    assert_eq!(router.hosts_with("mistral"), vec![b.client().host().to_string()]);
+    assert!(matches!(router.generate(&generation_request("phi3", false), None::<fn(&_)>).await, Err(Error::NotExists)));
+}
+
+#[tokio::test]
+async fn pull_on_miss_and_refresh() {
+    let (a, b) = (server_with(&[]).await, server_with(&[]).await);
+    let router = ModelRouter::new(OllamaPool::new([a.client(), b.client()])).with_pull_on_miss(true);
+
+    let first = router.generate(&generation_request("phi3:latest", false), None::<fn(&_)>).await.unwrap();
+    assert_eq!(a.requests_to("/api/pull").len() + b.requests_to("/api/pull").len(), 1);
+
+    // Served by the host it was pulled onto, without pulling again
+    let second = router.generate(&generation_request("phi3:latest", false), None::<fn(&_)>).await.unwrap();
+    assert_eq!(first.host, second.host);
+    assert_eq!(a.requests_to("/api/pull").len() + b.requests_to("/api/pull").len(), 1);
+
+    // Models added behind the router's back are found by refreshing on a miss
+    b.add_model(MockModel::new("gemma2:2b"));
+    let res = router.generate(&generation_request("gemma2:2b", false), None::<fn(&_)>).await.unwrap();
+    assert_eq!(res.host, b.client().host());
+    assert_eq!(a.requests_to("/api/pull").len() + b.requests_to("/api/pull").len(), 1);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex},
+};
+
+use futures::StreamExt;
+use tracing::{field::{Field, Visit}, span, Event, Metadata, Subscriber};
+use tracing_core::span::Current;
+
+use common::{generation_request, server, MODEL};
+
+type Spans = Arc<Mutex<Vec<(String, BTreeMap<String, String>)>>>;
+
+/// Keeps the name and recorded fields of every span
+#[derive(Default)]
+struct Recorder {
+    spans: Spans,
+    metadata: Mutex<Vec<&'static Metadata<'static>>>,
+    entered: Mutex<Vec<span::Id>>,
+    next_id: AtomicU64,
+}
+
+struct Fields<'a>(&'a mut BTreeMap<String, String>);
+
+impl Visit for Fields<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.insert(field.name().to_string(), format!("{value:?}").trim_matches('"').to_string());
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &span::Attributes<'_>) -> span::Id {
+        let mut fields = BTreeMap::new();
+        attrs.record(&mut Fields(&mut fields));
+        self.spans.lock().unwrap().push((attrs.metadata().name().to_string(), fields));
+        self.metadata.lock().unwrap().push(attrs.metadata());
+
+        span::Id::from_u64(self.next_id.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+
+    fn record(&self, id: &span::Id, values: &span::Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        values.record(&mut Fields(&mut spans[id.into_u64() as usize - 1].1));
+    }
+
+    fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+    fn event(&self, _: &Event<'_>) {}
+
+    fn enter(&self, id: &span::Id) {
+        self.entered.lock().unwrap().push(id.clone());
+    }
+
+    fn exit(&self, _: &span::Id) {
+        self.entered.lock().unwrap().pop();
+    }
+
+    fn current_span(&self) -> Current {
+        match self.entered.lock().unwrap().last() {
+            Some(id) => Current::new(id.clone(), self.metadata.lock().unwrap()[id.into_u64() as usize - 1]),
+            None => Current::none(),
+        }
+    }
+}
+
+fn span<'a>(spans: &'a [(String, BTreeMap<String, String>)], name: &str) -> &'a BTreeMap<String, String> {
+    &spans.iter().find(|(span, _)| span == name).unwrap().1
+}
+
+#[tokio::test]
+async fn generation_spans() {
+    let recorder = Recorder::default();
+    let spans = recorder.spans.clone();
+    let _guard = tracing::subscriber::set_default(recorder);
+
+    let server = server().await;
+
+    let request = generation_request(MODEL, true);
+
+    let ollama = server.client();
+    let mut stream = ollama.generate_streamed(&request).await.unwrap();
+    while stream.next().await.is_some() {}
+    drop(stream);
+
+    ollama.version().await.unwrap();
+
+    {
+        let spans = spans.lock().unwrap();
+
+        let generate = span(&spans, "generate_streamed");
+        assert_eq!(generate["endpoint"], "/api/generate");
+        assert_eq!(generate["model"], MODEL);
+        assert_eq!(generate["status"], "200");
+        assert_eq!(generate["chunks"], "6");
+        assert_eq!(generate["eval_count"], "5");
+        assert!(generate.contains_key("ttft_ms"));
+        assert!(generate.contains_key("prompt_eval_count"));
+
+        // Prompts stay out of spans unless enabled
+        assert!(!generate.contains_key("prompt"));
+
+        assert_eq!(span(&spans, "version")["status"], "200");
+    }
+
+    ollama.clone().with_prompt_tracing(true).generate(&request, Some(|_: &_| {})).await.unwrap();
+
+    let spans = spans.lock().unwrap();
+    assert_eq!(span(&spans, "generate")["prompt"], "Why is the sky blue?");
+}
@@ -0,0 +1,63 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{path::PathBuf, str::FromStr};
+
+use futures::StreamExt;
+use ollama_rest::{testing::MockServer, Ollama};
+use tokio::net::{TcpStream, UnixListener};
+
+use common::{generation_request, server, MODEL};
+
+/// Forward every connection on a Unix socket to the mock server, like a local proxy would
+async fn proxy(server: &MockServer, name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("ollama-rest-{}-{name}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let listener = UnixListener::bind(&path).unwrap();
+    let addr = format!("{}:{}", server.url().host_str().unwrap(), server.url().port().unwrap());
+
+    tokio::spawn(async move {
+        while let Ok((mut inbound, _)) = listener.accept().await {
+            let mut outbound = TcpStream::connect(&addr).await.unwrap();
+            tokio::spawn(async move {
+                let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+            });
+        }
+    });
+
+    path
+}
+
+#[tokio::test]
+async fn endpoints_over_unix_socket() {
+    let server = server().await;
+    server.set_completion("Hello over a socket");
+
+    let socket = proxy(&server, "endpoints").await;
+    let ollama = Ollama::from_str(&format!("unix://{}", socket.display())).unwrap();
+
+    assert!(ollama.host().starts_with("unix:///"));
+    assert_eq!(ollama.version().await.unwrap().version.to_string(), "0.9.0");
+
+    let request = generation_request(MODEL, true);
+    let mut stream = ollama.generate_streamed(&request).await.unwrap();
+
+    let mut text = String::new();
+    while let Some(res) = stream.next().await {
+        text.push_str(&res.unwrap().response);
+    }
+    assert_eq!(text, "Hello over a socket");
+
+    let blob = std::env::temp_dir().join(format!("ollama-rest-uds-{}.bin", std::process::id()));
+    tokio::fs::write(&blob, vec![7u8; 100_000]).await.unwrap();
+
+    let digest = ollama.upload_blob_from_path(&blob, None::<fn(&_)>).await.unwrap();
+    ollama.blob_exists(&digest).await.unwrap();
+
+    tokio::fs::remove_file(&blob).await.unwrap();
+    std::fs::remove_file(&socket).unwrap();
+
+    assert_eq!(server.requests_to("/api/generate").len(), 1);
+}